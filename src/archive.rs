@@ -0,0 +1,249 @@
+//! `--archive`: packing the finished clips into one zip or tar file as they
+//! come out, rather than leaving a separate step over hundreds of files.
+//!
+//! Zip files store the clips as they are (they're compressed already). Tar
+//! files can be compressed by piping them through `gzip`, `xz`, or `zstd`,
+//! chosen by the extension: `.tar.gz` (or `.tgz`), `.tar.xz`, or `.tar.zst`.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::time::UNIX_EPOCH;
+
+pub struct Archive {
+    format: Format,
+    out: Box<dyn Write>,
+    /// The compressor the output goes through, for compressed tar files.
+    compressor: Option<Child>,
+    /// Bytes written so far, which is where the next zip entry starts.
+    written: u64,
+    /// Central directory records of the zip entries written so far.
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Format {
+    Zip,
+    Tar,
+}
+
+impl Archive {
+    /// Creates the archive at `path`, in the format its extension names.
+    pub fn create(path: &Path) -> io::Result<Archive> {
+        let name = path.to_string_lossy().to_ascii_lowercase();
+        let (format, compressor) = if name.ends_with(".zip") {
+            (Format::Zip, None)
+        } else if name.ends_with(".tar") {
+            (Format::Tar, None)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            (Format::Tar, Some("gzip"))
+        } else if name.ends_with(".tar.xz") {
+            (Format::Tar, Some("xz"))
+        } else if name.ends_with(".tar.zst") {
+            (Format::Tar, Some("zstd"))
+        } else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "expected a .zip, .tar, .tar.gz, .tar.xz, or .tar.zst file"));
+        };
+        let file = File::create(path)?;
+        let (out, compressor): (Box<dyn Write>, _) = match compressor {
+            None => (Box::new(BufWriter::new(file)), None),
+            Some(program) => {
+                let mut child = Command::new(program)
+                    .args(["-q", "-c"])
+                    .stdin(Stdio::piped())
+                    .stdout(file)
+                    .spawn()
+                    .map_err(|err| io::Error::new(err.kind(), format!("failed to spawn {}", program)))?;
+                (Box::new(BufWriter::new(child.stdin.take().unwrap())), Some(child))
+            }
+        };
+        Ok(Archive { format, out, compressor, written: 0, directory: Vec::new(), entries: 0 })
+    }
+
+    /// Adds a file, or a directory and everything in it, under `name`.
+    pub fn add(&mut self, path: &Path, name: &str) -> io::Result<()> {
+        if path.is_dir() {
+            let mut children: Vec<_> = fs::read_dir(path)?.collect::<io::Result<_>>()?;
+            children.sort_by_key(|c| c.file_name());
+            for child in children {
+                self.add(&child.path(), &format!("{}/{}", name, child.file_name().to_string_lossy()))?;
+            }
+            return Ok(());
+        }
+        let metadata = fs::metadata(path)?;
+        let mtime = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_secs());
+        match self.format {
+            Format::Zip => self.add_zip(path, name, metadata.len(), mtime),
+            Format::Tar => self.add_tar(path, name, metadata.len(), mtime),
+        }
+    }
+
+    /// Writes the end of the archive and waits for any compressor.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            Format::Zip => {
+                let directory = std::mem::take(&mut self.directory);
+                let offset = self.offset32()?;
+                self.write(&directory)?;
+                let mut end = Vec::new();
+                end.extend(0x06054b50u32.to_le_bytes());
+                end.extend([0; 4]);
+                end.extend(self.entries.to_le_bytes());
+                end.extend(self.entries.to_le_bytes());
+                end.extend((directory.len() as u32).to_le_bytes());
+                end.extend(offset.to_le_bytes());
+                end.extend([0; 2]);
+                self.write(&end)?;
+            }
+            Format::Tar => self.write(&[0; 1024])?,
+        }
+        self.out.flush()?;
+        drop(self.out);
+        if let Some(mut compressor) = self.compressor {
+            if !compressor.wait()?.success() {
+                return Err(io::Error::other("the compressor failed"));
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn copy(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = File::open(path)?;
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.write(&buf[..n])?;
+        }
+    }
+
+    fn offset32(&self) -> io::Result<u32> {
+        u32::try_from(self.written).map_err(|_| io::Error::other("the zip file would be over 4 GB; use a .tar file instead"))
+    }
+
+    fn add_zip(&mut self, path: &Path, name: &str, size: u64, mtime: u64) -> io::Result<()> {
+        let size = u32::try_from(size).map_err(|_| io::Error::other(format!("{} is over 4 GB; use a .tar file instead", path.display())))?;
+        // the checksum goes before the data, so the file is read twice
+        let mut crc = !0u32;
+        let mut file = File::open(path)?;
+        let mut buf = vec![0; 1 << 16];
+        loop {
+            let n = file.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            crc = crc32(crc, &buf[..n]);
+        }
+        let crc = !crc;
+        let (time, date) = dos_time(mtime);
+        let offset = self.offset32()?;
+
+        // version needed, flags (UTF-8 names), method (stored), time, date,
+        // checksum, compressed and uncompressed sizes, name length, extra length
+        let mut common = Vec::new();
+        common.extend(20u16.to_le_bytes());
+        common.extend(0x0800u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(time.to_le_bytes());
+        common.extend(date.to_le_bytes());
+        common.extend(crc.to_le_bytes());
+        common.extend(size.to_le_bytes());
+        common.extend(size.to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+
+        let mut local = 0x04034b50u32.to_le_bytes().to_vec();
+        local.extend(&common);
+        local.extend(name.as_bytes());
+        self.write(&local)?;
+        self.copy(path)?;
+
+        // version made by, the common fields, comment length, disk, internal
+        // and external attributes, and where the local header is
+        self.directory.extend(0x02014b50u32.to_le_bytes());
+        self.directory.extend(20u16.to_le_bytes());
+        self.directory.extend(&common);
+        self.directory.extend([0; 10]);
+        self.directory.extend(offset.to_le_bytes());
+        self.directory.extend(name.as_bytes());
+        self.entries = self.entries.checked_add(1).ok_or_else(|| io::Error::other("too many files for a zip file; use a .tar file instead"))?;
+        Ok(())
+    }
+
+    fn add_tar(&mut self, path: &Path, name: &str, size: u64, mtime: u64) -> io::Result<()> {
+        // ustar splits long names into a prefix (up to 155 bytes) and a name
+        // (up to 100), at a slash
+        let (prefix, short) = if name.len() <= 100 {
+            ("", name)
+        } else {
+            name.char_indices()
+                .filter(|&(i, c)| c == '/' && i <= 155 && name.len() - i - 1 <= 100)
+                .map(|(i, _)| (&name[..i], &name[i + 1..]))
+                .next()
+                .ok_or_else(|| io::Error::other(format!("{} is too long a name for a tar file", name)))?
+        };
+        if size >= 1 << 33 {
+            return Err(io::Error::other(format!("{} is too large for a tar file", path.display())));
+        }
+        let mut header = [0u8; 512];
+        let mut field = |at: usize, value: &[u8]| header[at..at + value.len()].copy_from_slice(value);
+        field(0, short.as_bytes());
+        field(100, b"0000644\0");
+        field(108, b"0000000\0");
+        field(116, b"0000000\0");
+        field(124, format!("{:011o}\0", size).as_bytes());
+        field(136, format!("{:011o}\0", mtime).as_bytes());
+        field(148, b"        ");
+        field(156, b"0");
+        field(257, b"ustar\0");
+        field(263, b"00");
+        field(345, prefix.as_bytes());
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+        self.write(&header)?;
+        self.copy(path)?;
+        let padding = (512 - size % 512) % 512;
+        self.write(&vec![0; padding as usize])
+    }
+}
+
+/// Updates a CRC-32 (as zip uses it) with `bytes`.
+fn crc32(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb88320 } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+/// A Unix time as the MS-DOS time and date that zip files use (in UTC,
+/// which is as good a guess as any, since they don't say).
+fn dos_time(secs: u64) -> (u16, u16) {
+    let secs = secs.max(315532800); // DOS dates start in 1980
+    let (days, rem) = (secs / 86400, secs % 86400);
+    // civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let time = ((rem / 3600) << 11 | (rem / 60 % 60) << 5 | (rem % 60 / 2)) as u16;
+    let date = (((year - 1980).min(127) as u64) << 9 | (month as u64) << 5 | day as u64) as u16;
+    (time, date)
+}
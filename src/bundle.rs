@@ -0,0 +1,81 @@
+//! `--bundle`: one directory per clip instead of loose files, holding
+//!
+//! ```text
+//! video.EXT         the clip
+//! thumbnail.jpg     its middle frame (if it has video)
+//! waveform.png      a picture of its audio (if it has audio)
+//! subtitles.ass     with --ass-subtitles, and the fonts in subtitles.fonts/
+//! metadata.json     where the clip came from, and the names of the above
+//! ```
+//!
+//! Each directory is named like the clip would have been, without the
+//! extension.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::json::{self, Value};
+use crate::{formats, Span};
+
+const THUMBNAIL: &str = "thumbnail.jpg";
+const WAVEFORM: &str = "waveform.png";
+const SUBTITLES: &str = "subtitles";
+
+/// Where the video of the bundle for a clip that would have been written to
+/// `output` goes.
+pub fn video_path(output: &str) -> String {
+    let path = Path::new(output);
+    let ext = path.extension().map_or("mp4".into(), |e| e.to_string_lossy());
+    path.with_extension("").join(format!("video.{}", ext)).to_string_lossy().into_owned()
+}
+
+/// The base path (without `.ass`) for the subtitles in the bundle of `video`.
+pub fn subtitles_base(video: &str) -> String {
+    dir(video).join(SUBTITLES).to_string_lossy().into_owned()
+}
+
+pub fn dir(video: &str) -> PathBuf {
+    Path::new(video).parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Adds the thumbnail, waveform, and metadata to the bundle of a finished
+/// clip. A thumbnail or waveform ffmpeg can't make is left out (and out of
+/// the metadata) rather than failing the clip.
+pub fn finish(video: &str, span: &Span, label: Option<&str>, input: &Path, subtitles: bool) -> Result<(), String> {
+    let dir = dir(video);
+    let ext = Path::new(video).extension().map_or("".into(), |e| e.to_string_lossy());
+    let (has_video, has_audio) = formats::keeps_streams(&ext);
+    let length = span.duration();
+    let middle = length / 2;
+    let middle = format!("{}.{:03}", middle.as_secs(), middle.subsec_millis());
+    let thumbnail = has_video && ffmpeg(&["-ss", &middle, "-i", video, "-frames:v", "1", "-q:v", "2"], &dir.join(THUMBNAIL));
+    let waveform = has_audio && ffmpeg(&["-i", video, "-filter_complex", "[0:a:0]showwavespic=s=1280x240", "-frames:v", "1"], &dir.join(WAVEFORM));
+
+    let name = |present: bool, name: &str| if present { Value::from(name) } else { Value::Null };
+    let file_name = Path::new(video).file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let seconds = |ms: u64| ms as f64 / 1000.0;
+    let metadata = json::object([
+        ("input", input.to_string_lossy().into_owned().into()),
+        ("start", seconds(span.start.as_millis()).into()),
+        ("end", seconds(span.end.as_millis()).into()),
+        ("duration", seconds(length.as_millis() as u64).into()),
+        ("label", label.into()),
+        ("video", file_name.into()),
+        ("thumbnail", name(thumbnail, THUMBNAIL)),
+        ("waveform", name(waveform, WAVEFORM)),
+        ("subtitles", name(subtitles, &format!("{}.ass", SUBTITLES))),
+    ]);
+    fs::write(dir.join("metadata.json"), format!("{}\n", metadata)).map_err(|err| err.to_string())
+}
+
+/// Runs ffmpeg writing to `output`, returning whether it worked.
+fn ffmpeg(args: &[&str], output: &Path) -> bool {
+    crate::ffmpeg()
+        .args(["-v", "error", "-nostdin", "-y"])
+        .args(args)
+        .arg(output)
+        .stdin(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
@@ -0,0 +1,85 @@
+//! `--cache-input`: read a slow input (a URL, or a file on a network mount)
+//! once into the temporary directory, so probing and cutting every span don't
+//! each go back to the remote copy.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::error::{Result, XclipsError};
+use crate::{ffmpeg, join};
+
+lazy_static! {
+    /// Local copies to remove when xclips exits.
+    static ref COPIES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+}
+
+/// Whether the input names something ffmpeg reads over a protocol, rather
+/// than a path.
+fn is_url(input: &Path) -> bool {
+    lazy_static! {
+        static ref URL_RE: Regex = Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*://").unwrap();
+    }
+    input.to_str().is_some_and(|s| URL_RE.is_match(s))
+}
+
+/// Makes a local copy of `input` and returns its path. The copy keeps the
+/// input's extension and is removed when the process exits.
+pub fn fetch(input: &Path) -> Result<PathBuf> {
+    let name = input.to_string_lossy();
+    // drop any query string, so the copy gets the right extension
+    let name = name.split(['?', '#']).next().unwrap_or_default();
+    let name = name.rsplit('/').next().filter(|n| !n.is_empty()).unwrap_or("input");
+    let copy = join::temp_path(name);
+    remove_at_exit(copy.clone());
+    if is_url(input) {
+        // ffmpeg understands every protocol it can cut from, so let it do the download
+        let status = ffmpeg()
+            .args(["-v", "error", "-nostdin", "-y", "-i"])
+            .arg(input)
+            .args(["-map", "0", "-c", "copy"])
+            .arg(&copy)
+            .stdin(Stdio::null())
+            .status()
+            .map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+        if !status.success() {
+            return Err(XclipsError::Command(format!("ffmpeg could not download {}", input.display())));
+        }
+    } else {
+        fs::copy(input, &copy).map_err(|err| XclipsError::io(format!("cannot make a local copy of {}", input.display()), err))?;
+    }
+    Ok(copy)
+}
+
+/// Removes a temporary file when the process exits.
+pub fn remove_at_exit(path: PathBuf) {
+    let mut copies = COPIES.lock().unwrap();
+    if copies.is_empty() {
+        remove_on_exit();
+    }
+    copies.push(path);
+}
+
+extern "C" fn remove_copies() {
+    if let Ok(mut copies) = COPIES.lock() {
+        for path in copies.drain(..) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+// main exits through process::exit, which skips destructors but still runs
+// these handlers
+#[cfg(unix)]
+fn remove_on_exit() {
+    unsafe {
+        libc::atexit(remove_copies);
+    }
+}
+
+#[cfg(not(unix))]
+fn remove_on_exit() {}
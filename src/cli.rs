@@ -0,0 +1,2044 @@
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+use crate::archive::Archive;
+use crate::{
+    bundle, cache, compare, config, error, existing, filters, formats, glob, history, hook, input, job, join, join_files, keyframes, lint, pick, plan,
+    probe, queue, report, schedule, screenshot, sequence, server, sidecar, subs, template, timeline, trailer, transform, ui,
+};
+#[cfg(feature = "web")]
+use crate::web;
+use crate::{ffmpeg, ffmpeg_path, parse_duration, shell_quote, shell_words, use_ffmpeg, ParseErr, Span, Timestamp};
+use crate::error::XclipsError;
+use crate::input::{read_spans, Entry, SpanSource};
+use crate::job::{Bumpers, Failure, Job, RunOptions, Segment, Shrink};
+use crate::sequence::Sequence;
+use crate::upload::Destination;
+
+/// How much of each clip `--preflight` decodes.
+const PREFLIGHT_LENGTH: Duration = Duration::from_secs(3);
+
+/// How far before a span's start `--accurate-seek` fast-seeks to, decoding
+/// the rest of the way.
+const ACCURATE_SEEK_LEAD: Duration = Duration::from_secs(5);
+
+/// How many finished clips apart `--json` reports are rewritten during a
+/// run, so one that's killed can be picked up from its report.
+const REPORT_CHECKPOINT: usize = 100;
+
+/// Most clips cut by one ffmpeg run with `--single-pass`, which keeps every
+/// output open at once.
+const SINGLE_PASS_OUTPUTS: usize = 64;
+
+/// The names `main` dispatches to a subcommand rather than taking as
+/// ordinary arguments.
+pub(crate) const SUBCOMMANDS: &[&str] =
+    &["compare", "history", "join-files", "lint", "queue", "rerun", "screenshot", "serve", "timeline", "trailer", "web"];
+
+const SUBCOMMANDS_HELP: &str = "SUBCOMMANDS:
+    compare       Stack the same spans of two files side by side
+    history       List past runs, or show one's clips
+    join-files    Join existing files into one
+    lint          Check a list of spans for common mistakes
+    queue         Inspect or work on a job queue (see --queue)
+    rerun         Repeat a past run (the last one, by default)
+    screenshot    Extract stills at single timestamps
+    serve         Accept clipping jobs over a local HTTP API
+    timeline      Draw which parts of a file a list of spans covers
+    trailer       Assemble a highlight reel of a given length
+    web           Mark spans in a browser (needs the `web` feature)
+
+EXIT STATUS:
+    0 success, 1 some clips failed (or bad arguments), 2 options that don't go together, 3 spans that can't be parsed,
+    4 file errors, 5 ffprobe couldn't tell, 6 a program couldn't be started, 7 ffmpeg failed, 8 another command failed";
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "xclips", after_help = SUBCOMMANDS_HELP, setting = AppSettings::AllowNegativeNumbers)]
+struct Opt {
+    #[structopt(flatten)]
+    spans: SpanSource,
+
+    #[structopt(short = "o", long = "output")]
+    output: Option<PathBuf>,
+
+    /// Write the clips into this directory (made if it doesn't exist), named as they would be beside the input (or -o)
+    #[structopt(long = "output-dir", parse(from_os_str))]
+    output_dir: Option<PathBuf>,
+
+    /// Output filename template, with placeholders like {base}, {index}, {ext}, {input_stem}, {start}, {end}, {duration}, {label}, {env:VAR},
+    /// {date:%Y%m%d} (by default, spans with a label are named {base}_{label}.{ext} instead)
+    #[structopt(long = "name-template", default_value = template::DEFAULT)]
+    name_template: String,
+
+    /// Replace {index} in output names with a hash of the input filename and span, so adding or removing spans doesn't
+    /// rename the other clips
+    #[structopt(long = "stable-names")]
+    stable_names: bool,
+
+    /// How {index} numbers clips: digits (0, 1, ...), letters (a, b, ...), roman (i, ii, ...), or label (each clip's
+    /// label, as lowercase words joined by dashes)
+    #[structopt(long = "number-scheme", default_value = "digits")]
+    number_scheme: template::NumberScheme,
+
+    /// Cut each region not covered by any span, instead of the spans themselves (--invert for short); with --concat,
+    /// makes one output with the spans cut out
+    #[structopt(long = "gaps", alias = "invert")]
+    gaps: bool,
+
+    /// Widen each span by this much on both sides
+    #[structopt(long = "expand", parse(try_from_str = parse_duration))]
+    expand: Option<Duration>,
+
+    /// Narrow each span by this much on both sides
+    #[structopt(long = "contract", parse(try_from_str = parse_duration))]
+    contract: Option<Duration>,
+
+    /// Cut each span into consecutive clips of this length (the last may be shorter)
+    #[structopt(long = "split-every", parse(try_from_str = parse_duration))]
+    split_every: Option<Duration>,
+
+    /// Merge overlapping and duplicate spans into one, rather than refusing to cut them (only spans written out in full
+    /// are refused)
+    #[structopt(long = "merge-overlaps", conflicts_with = "allow-overlaps")]
+    merge_overlaps: bool,
+
+    /// Cut overlapping and duplicate spans as they are, each into a clip of its own
+    #[structopt(long = "allow-overlaps")]
+    allow_overlaps: bool,
+
+    /// Reject spans shorter than this duration (e.g. 0.5s, 500ms, 1:30)
+    #[structopt(long = "min-duration", parse(try_from_str = parse_duration))]
+    min_duration: Option<Duration>,
+
+    /// Drop spans shorter than --min-duration with a warning instead of failing
+    #[structopt(long = "drop-short", requires = "min-duration")]
+    drop_short: bool,
+
+    /// Read a remote or network-mounted input once into a local copy, used for every clip and removed afterwards
+    #[structopt(long = "cache-input")]
+    cache_input: bool,
+
+    /// Re-encode clips instead of copying streams, so they start exactly at the span rather than at the keyframe before
+    #[structopt(long = "reencode")]
+    reencode: bool,
+
+    /// Video codec for re-encoded clips (with --reencode, or when a filter needs it), like libx265
+    #[structopt(long = "vcodec")]
+    vcodec: Option<String>,
+
+    /// Audio codec for re-encoded clips, like libopus (by default, aac with --reencode and a copy otherwise)
+    #[structopt(long = "acodec")]
+    acodec: Option<String>,
+
+    /// Constant rate factor for re-encoded video; lower is better quality
+    #[structopt(long = "crf")]
+    crf: Option<u32>,
+
+    /// Encoder preset for re-encoded video, like fast or slow
+    #[structopt(long = "preset")]
+    preset: Option<String>,
+
+    /// Warn about stream-copied clips likely to be bigger than this, like 25MB or 500k (k, M, and G are powers of 1000;
+    /// KiB, MiB, and GiB of 1024)
+    #[structopt(long = "size-budget", parse(try_from_str = parse_size))]
+    size_budget: Option<u64>,
+
+    /// Re-encode clips that would go over --size-budget at a bitrate that fits it, instead of only warning
+    #[structopt(long = "auto-fit", requires = "size-budget")]
+    auto_fit: bool,
+
+    /// Frame rate of GIF and WebP outputs (15 by default)
+    #[structopt(long = "fps")]
+    fps: Option<f64>,
+
+    /// Width to scale GIF and WebP outputs down to, if they're wider (480 by default; --width for short)
+    #[structopt(long = "max-width", alias = "width")]
+    max_width: Option<u32>,
+
+    /// How to dither GIF outputs' colors (ffmpeg's default is sierra2_4a)
+    #[structopt(long = "dither", possible_values = formats::DITHERS)]
+    dither: Option<String>,
+
+    /// How many times GIF and WebP outputs play, or 0 to loop forever
+    #[structopt(long = "loop-count", default_value = "0")]
+    loop_count: u32,
+
+    /// Cut GIF and WebP outputs again at lower frame rates and widths until they're no bigger than this, like 8MB
+    #[structopt(long = "max-size", parse(try_from_str = parse_size), conflicts_with_all = &["queue", "single-pass"])]
+    max_size: Option<u64>,
+
+    /// Re-encode clips to an edit-friendly intermediate codec (prores or dnxhd), written as .mov files
+    #[structopt(long = "mezzanine")]
+    mezzanine: Option<formats::Mezzanine>,
+
+    /// What to do when a stream-copied clip's container can't hold the input's codecs (like VP9 in .mp4): remux it
+    /// into .mkv instead (the default), reencode it, or fail
+    #[structopt(long = "incompatible", default_value = "remux")]
+    incompatible: formats::Incompatible,
+
+    /// What to do when copying a clip's streams fails, or makes an empty or broken file: cut it again, re-encoding it
+    /// (the default, noted in the --json report), or fail
+    #[structopt(long = "copy-fallback", default_value = "reencode")]
+    copy_fallback: formats::CopyFallback,
+
+    /// Write each clip as an animated image, gif or webp, whatever the output name's extension
+    #[structopt(long = "format", possible_values = &["gif", "webp"], conflicts_with_all = &["audio-only", "mezzanine"])]
+    format: Option<String>,
+
+    /// Keep just the audio of each span, written as --audio-format files whatever the output name's extension
+    #[structopt(long = "audio-only", conflicts_with = "mezzanine")]
+    audio_only: bool,
+
+    /// The format --audio-only writes: mp3 (the default), aac (as .m4a), flac, or wav
+    #[structopt(long = "audio-format", requires = "audio-only", possible_values = &["mp3", "aac", "flac", "wav"])]
+    audio_format: Option<String>,
+
+    /// Embed a frame of each MP4/MOV clip as its cover picture: `first`, or an offset into the clip like 2s
+    #[structopt(long = "poster", parse(try_from_str = parse_poster), conflicts_with = "queue")]
+    poster: Option<Duration>,
+
+    /// Remove silences longer than this from inside each clip; audio outputs only, since video would go out of sync
+    #[structopt(long = "compact-silence", parse(try_from_str = parse_duration))]
+    compact_silence: Option<Duration>,
+
+    /// Reduce noise in the video (light, medium, or heavy), re-encoding it
+    #[structopt(long = "denoise")]
+    denoise: Option<filters::Denoise>,
+
+    /// Scale each clip to fit an exact canvas like 1920x1080, re-encoding it
+    #[structopt(long = "fit")]
+    fit: Option<filters::Canvas>,
+
+    /// What fills the rest of the --fit canvas: black or blur
+    #[structopt(long = "background", default_value = "black")]
+    background: filters::Background,
+
+    /// Overlay this video on each clip (picture-in-picture), over the same span, re-encoding it
+    #[structopt(long = "pip", parse(from_os_str), conflicts_with = "single-pass")]
+    pip: Option<PathBuf>,
+
+    /// Corner for the --pip overlay: tl, tr, bl, or br
+    #[structopt(long = "pip-position", default_value = "tr")]
+    pip_position: filters::Corner,
+
+    /// Width of the --pip overlay, as a fraction of the clip's width
+    #[structopt(long = "pip-scale", default_value = "0.25")]
+    pip_scale: f64,
+
+    /// How far ahead (or with a leading -, behind) the --pip overlay is of the input, like 2.5s
+    #[structopt(long = "pip-offset", default_value = "0", allow_hyphen_values = true, parse(try_from_str = parse_offset))]
+    pip_offset: i64,
+
+    /// Sharpen the video, re-encoding it
+    #[structopt(long = "sharpen")]
+    sharpen: bool,
+
+    /// Hold the last frame of each clip for this long, re-encoding it
+    #[structopt(long = "endcard", parse(try_from_str = parse_duration), conflicts_with = "single-pass")]
+    endcard: Option<Duration>,
+
+    /// Text shown over the --endcard, like a call to action
+    #[structopt(long = "endcard-text", requires = "endcard")]
+    endcard_text: Option<String>,
+
+    /// Don't color the status lines (also off when NO_COLOR is set or output isn't a terminal)
+    #[structopt(long = "no-color")]
+    no_color: bool,
+
+    /// Put this file before each clip (or the joined file, with --concat), converted to its size and frame rate
+    #[structopt(long = "intro", parse(from_os_str), conflicts_with = "queue")]
+    intro: Option<PathBuf>,
+
+    /// Put this file after each clip (or the joined file, with --concat), converted to its size and frame rate
+    #[structopt(long = "outro", parse(from_os_str), conflicts_with = "queue")]
+    outro: Option<PathBuf>,
+
+    /// Tolerate corrupt or truncated input, warning about each clip where errors were ignored
+    #[structopt(long = "ignore-errors")]
+    ignore_errors: bool,
+
+    /// Move each span's start back to the preceding keyframe, so stream-copied clips still end where requested
+    #[structopt(long = "snap-keyframes")]
+    snap_keyframes: bool,
+
+    /// For .mp4/.mov outputs, stream-copy each clip from the keyframe before its start and write an edit list so playback
+    /// still begins exactly at the requested time
+    #[structopt(long = "edit-list", conflicts_with_all = &["snap-keyframes", "copy-lead-in", "reencode", "single-pass", "concat", "endcard"])]
+    edit_list: bool,
+
+    /// Start each clip exactly where its span does: fast-seek to a little before it, then decode the rest of the way,
+    /// re-encoding clips that don't start on a keyframe (which a stream copy can't)
+    #[structopt(long = "accurate-seek", conflicts_with_all = &["snap-keyframes", "edit-list", "copy-lead-in", "single-pass", "endcard", "pip"])]
+    accurate_seek: bool,
+
+    /// Record jobs in this SQLite database and work through them, so the batch can be resumed or shared
+    #[structopt(long = "queue", parse(from_os_str))]
+    queue: Option<PathBuf>,
+
+    /// Run this shell command as a hook for each clip (described in XCLIPS_* variables); it may print name=PATH, arg=ARG, or skip
+    #[structopt(long = "hook")]
+    hook: Option<String>,
+
+    /// Print the path of each finished clip on stdout
+    #[structopt(long = "list-outputs")]
+    list_outputs: bool,
+
+    /// Cut all clips in one ffmpeg run that reads the input once, which is much faster for many short clips; with
+    /// -c copy, clips may start with a few undecodable frames
+    #[structopt(long = "single-pass", conflicts_with = "queue")]
+    single_pass: bool,
+
+    /// Kill ffmpeg (and retry, waiting longer each time) if it makes no progress for this long, as when a network mount
+    /// hangs
+    #[structopt(long = "stall-timeout", parse(try_from_str = parse_duration))]
+    stall_timeout: Option<Duration>,
+
+    /// How many times to retry a clip after ffmpeg stalls, before giving up on it
+    #[structopt(long = "stall-retries", default_value = "2")]
+    stall_retries: u32,
+
+    /// Join the clips into one file (the -o path, or BASE_joined.EXT) with the concat demuxer, instead of keeping them
+    /// as separate files, with a chapter for each; --intro and --outro then go around the joined file
+    #[structopt(long = "concat", conflicts_with_all = &["queue", "bundle", "ass-subtitles"])]
+    concat: bool,
+
+    /// With --concat into an audio file, crossfade consecutive clips by this much
+    #[structopt(long = "audio-crossfade", parse(try_from_str = parse_duration), requires = "concat", conflicts_with = "gap")]
+    audio_crossfade: Option<Duration>,
+
+    /// With --concat into an audio file, put this much silence between clips
+    #[structopt(long = "gap", parse(try_from_str = parse_duration), requires = "concat")]
+    gap: Option<Duration>,
+
+    /// Put each clip in a directory of its own, with a thumbnail, a waveform, any subtitles, and a metadata.json
+    #[structopt(long = "bundle", conflicts_with = "queue")]
+    bundle: bool,
+
+    /// Write the input's ASS subtitles, retimed, next to each clip as CLIP.ass, with the fonts they use in CLIP.fonts/
+    #[structopt(long = "ass-subtitles", conflicts_with = "queue")]
+    ass_subtitles: bool,
+
+    /// Pack the clips into one archive as they're made: a .zip, .tar, .tar.gz, .tar.xz, or .tar.zst file
+    #[structopt(long = "archive", parse(from_os_str), conflicts_with = "queue")]
+    archive: Option<PathBuf>,
+
+    /// Delete each clip (or bundle) once it's in the --archive
+    #[structopt(long = "remove-archived", requires = "archive")]
+    remove_archived: bool,
+
+    /// Copy each clip (or bundle) there once it's made, with rclone or rsync: rclone:REMOTE:PATH or rsync:HOST:PATH
+    #[structopt(long = "upload", conflicts_with_all = &["queue", "concat"])]
+    upload: Option<Destination>,
+
+    /// How many times to retry an upload that fails, before giving up on it
+    #[structopt(long = "upload-retries", default_value = "3")]
+    upload_retries: u32,
+
+    /// Set the language of an audio track, numbered from 1, like 2=eng (otherwise each track keeps the input's)
+    #[structopt(long = "audio-language", number_of_values = 1)]
+    audio_language: Vec<TrackTag>,
+
+    /// Set the title of an audio track, numbered from 1, like 2=Commentary (otherwise each track keeps the input's)
+    #[structopt(long = "audio-title", number_of_values = 1)]
+    audio_title: Vec<TrackTag>,
+
+    /// Wait until this local time of day (like 02:00) before cutting anything
+    #[structopt(long = "start-at")]
+    start_at: Option<schedule::TimeOfDay>,
+
+    /// Wait this long between clips, e.g. to leave a shared disk or network some breathing room
+    #[structopt(long = "pause-between", parse(try_from_str = parse_duration), conflicts_with = "queue")]
+    pause_between: Option<Duration>,
+
+    /// How many clips to cut at the same time (by default, one per CPU)
+    #[structopt(short = "j", long = "jobs", conflicts_with_all = &["queue", "pause-between"])]
+    jobs: Option<usize>,
+
+    /// Give up on a clip (and move on) if ffmpeg runs longer than this
+    #[structopt(long = "clip-timeout", parse(try_from_str = parse_duration))]
+    clip_timeout: Option<Duration>,
+
+    /// Check each output with ffprobe for missing video/audio streams
+    #[structopt(long = "verify")]
+    verify: bool,
+
+    /// Don't cut clips whose output already exists with the expected duration and size
+    #[structopt(long = "skip-existing")]
+    skip_existing: bool,
+
+    /// Replace outputs that already exist, rather than refusing to start (--skip-existing and --from-report replace
+    /// the ones they redo)
+    #[structopt(long = "overwrite")]
+    overwrite: bool,
+
+    /// Compare the planned outputs with existing files and print what a run would do, without cutting
+    #[structopt(long = "diff-existing")]
+    diff_existing: bool,
+
+    /// Before cutting, list the planned clips and choose which of them to cut
+    #[structopt(long = "interactive")]
+    interactive: bool,
+
+    /// Before cutting, decode the first few seconds of each clip to check that the input can be read there
+    #[structopt(long = "preflight")]
+    preflight: bool,
+
+    /// Before cutting, save the first and last frames of each clip as OUTPUT.first.jpg and OUTPUT.last.jpg
+    #[structopt(long = "boundary-previews")]
+    boundary_previews: bool,
+
+    /// Put the outputs in a directory of their own, named after the output and ID (by default, the time and
+    /// process ID), so other runs over the same input don't mix with them
+    #[structopt(long = "run-id", min_values = 0, max_values = 1)]
+    run_id: Option<Option<String>>,
+
+    /// Hide ffmpeg's own output entirely, even when it fails
+    #[structopt(short = "q", long = "quiet")]
+    quiet: bool,
+
+    /// Start stream-copied .mp4/.mov clips this much earlier, hiding the extra with an edit list (and noting the intended
+    /// start in the xclips_in_point tag), so players that honor edit lists start exactly on the span
+    #[structopt(long = "copy-lead-in", parse(try_from_str = parse_duration), conflicts_with_all = &["single-pass", "concat", "endcard"])]
+    copy_lead_in: Option<Duration>,
+
+    /// Go on with the other clips when one fails, moving what's left of it (and ffmpeg's log) into a failed/ directory,
+    /// and list how each one went at the end
+    #[structopt(long = "keep-going", conflicts_with_all = &["concat", "queue"])]
+    keep_going: bool,
+
+    /// After cutting, report how far before its span's start each clip begins (judged by how much longer than the
+    /// span it came out), to see how much stream copies drift to the keyframe before
+    #[structopt(long = "measure-accuracy", conflicts_with_all = &["endcard", "intro", "outro", "compact-silence", "concat"])]
+    measure_accuracy: bool,
+
+    /// Write a JSON file next to each clip (CLIP.json) with its source, span, ffmpeg arguments, checksum, and the
+    /// xclips version
+    #[structopt(long = "sidecar")]
+    sidecar: bool,
+
+    /// Put the index of .mp4, .mov, and .m4a clips at the front, so they can start playing on the web before they've
+    /// downloaded
+    #[structopt(long = "faststart")]
+    faststart: bool,
+
+    /// Write .mp4, .mov, and .m4a clips as fragmented MP4, for streaming and for outputs that stay readable if
+    /// cutting is interrupted
+    #[structopt(long = "fragmented-mp4", conflicts_with = "faststart")]
+    fragmented_mp4: bool,
+
+    /// Extra ffmpeg options for each clip, split into words as a shell would, like "-map 0 -avoid_negative_ts
+    /// make_zero" (they go just before the output, as can anything after a final --)
+    #[structopt(long = "ffmpeg-args", number_of_values = 1, allow_hyphen_values = true)]
+    ffmpeg_args: Vec<String>,
+
+    /// Run this ffmpeg instead of the one on the PATH
+    #[structopt(long = "ffmpeg-path", parse(from_os_str))]
+    ffmpeg_path: Option<PathBuf>,
+
+    /// Print the ffmpeg commands and outputs a run would make, and check the input and spans, without cutting anything
+    #[structopt(long = "dry-run")]
+    dry_run: bool,
+
+    /// Before cutting, print how long each clip will be (going by the input's actual length) and the total
+    #[structopt(long = "print-durations")]
+    print_durations: bool,
+
+    /// Cut spans that run past the end of the input, with a warning, rather than refusing to
+    #[structopt(long = "allow-out-of-range")]
+    allow_out_of_range: bool,
+
+    /// Refuse to run if any clip would be empty, e.g. because it starts after the input ends
+    #[structopt(long = "strict")]
+    strict: bool,
+
+    /// Write a JSON report of the run to this file (rewritten every 100 clips along the way, so a run that's killed still
+    /// leaves a record of the clips it finished)
+    #[structopt(long = "json", parse(from_os_str), conflicts_with = "queue")]
+    json: Option<PathBuf>,
+
+    /// Take the input and spans from an earlier --json report, writing to the same outputs unless -o or --name-template is given
+    #[structopt(long = "from-report", parse(from_os_str))]
+    from_report: Option<PathBuf>,
+
+    /// With --from-report, only redo clips that didn't finish successfully
+    #[structopt(long = "only-failed", requires = "from-report")]
+    only_failed: bool,
+
+    /// Only cut clips whose labels match one of these regular expressions (which must match the whole label)
+    #[structopt(long = "only-label", parse(try_from_str = parse_label_pattern))]
+    only_label: Vec<Regex>,
+
+    /// Only cut these clips, numbered from 1 in span order, like 3,7-9
+    #[structopt(long = "only")]
+    only: Option<ClipNumbers>,
+
+    /// Don't cut these clips, numbered as for --only
+    #[structopt(long = "skip")]
+    skip: Option<ClipNumbers>,
+
+    /// Leave out the first this many of the clips that would be cut, for working through a long list a window at a time
+    #[structopt(long = "offset", default_value = "0")]
+    offset: usize,
+
+    /// Cut at most this many clips (after --offset)
+    #[structopt(long = "limit")]
+    limit: Option<usize>,
+
+    /// Input files matching this pattern, like 'recordings/*.mp4', as well as any FILEs
+    #[structopt(long = "glob", conflicts_with = "from-report")]
+    glob: Option<String>,
+
+    /// Input media files, each cut the same way (optional with --from-report)
+    #[structopt(name = "FILE", parse(from_os_str), required_unless_one = &["from-report", "glob"])]
+    files: Vec<PathBuf>,
+
+    /// Extra ffmpeg options for each clip, after --, as for --ffmpeg-args
+    #[structopt(name = "FFMPEG_ARGS", last = true)]
+    trailing_ffmpeg_args: Vec<String>,
+}
+
+/// Parses where in a clip to take its poster frame: `first`, or an offset
+/// into the clip as for `parse_duration`.
+fn parse_poster(s: &str) -> Result<Duration, ParseErr> {
+    if s == "first" {
+        Ok(Duration::ZERO)
+    } else {
+        parse_duration(s)
+    }
+}
+
+/// Parses a signed offset in milliseconds: a duration as for
+/// `parse_duration`, optionally with a leading `-`.
+fn parse_offset(s: &str) -> Result<i64, ParseErr> {
+    match s.strip_prefix('-') {
+        Some(rest) => parse_duration(rest).map(|d| -(d.as_millis() as i64)),
+        None => parse_duration(s).map(|d| d.as_millis() as i64),
+    }
+}
+
+/// Parses a size in bytes, like `25MB`, `500k`, or `1.5GiB`.
+fn parse_size(s: &str) -> Result<u64, ParseErr> {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"(?i)^(\d+(?:\.\d+)?)\s*([kmg]i?)?b?$").unwrap();
+    }
+    let captures = RE.captures(s.trim()).ok_or(ParseErr("not a size like 25MB or 500k"))?;
+    let n: f64 = captures[1].parse().unwrap();
+    let unit = captures.get(2).map_or(String::new(), |u| u.as_str().to_ascii_lowercase());
+    let scale: u64 = match unit.as_str() {
+        "" => 1,
+        "k" => 1000,
+        "m" => 1_000_000,
+        "g" => 1_000_000_000,
+        "ki" => 1 << 10,
+        "mi" => 1 << 20,
+        _ => 1 << 30,
+    };
+    Ok((n * scale as f64).round() as u64)
+}
+
+/// A size in bytes for messages, like `31.2 MB`.
+fn human_size(bytes: u64) -> String {
+    match bytes {
+        0..=999 => format!("{} bytes", bytes),
+        1000..=999_999 => format!("{:.1} kB", bytes as f64 / 1e3),
+        1_000_000..=999_999_999 => format!("{:.1} MB", bytes as f64 / 1e6),
+        _ => format!("{:.2} GB", bytes as f64 / 1e9),
+    }
+}
+
+/// Parses a regular expression that has to match a whole label.
+fn parse_label_pattern(s: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", s))
+}
+
+/// A set of clip numbers, like `3,7-9`.
+#[derive(Clone, Debug)]
+struct ClipNumbers(Vec<(usize, usize)>);
+
+impl FromStr for ClipNumbers {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |n: &str| n.trim().parse::<usize>().map_err(|_| ParseErr("not a list of clip numbers like 3,7-9"));
+        s.split(',')
+            .map(|part| {
+                let (first, last) = match part.split_once('-') {
+                    Some((first, last)) => (number(first)?, number(last)?),
+                    None => (number(part)?, number(part)?),
+                };
+                if first > last {
+                    return Err(ParseErr("range ends before it starts"));
+                }
+                Ok((first, last))
+            })
+            .collect::<Result<_, _>>()
+            .map(ClipNumbers)
+    }
+}
+
+impl ClipNumbers {
+    fn contains(&self, n: usize) -> bool {
+        self.0.iter().any(|&(first, last)| first <= n && n <= last)
+    }
+}
+
+/// A value for one audio track, like `2=eng`.
+#[derive(Clone, Debug)]
+struct TrackTag {
+    track: usize,
+    value: String,
+}
+
+impl FromStr for TrackTag {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (track, value) = s.split_once('=').ok_or(ParseErr("expected TRACK=VALUE, like 2=eng"))?;
+        let track = track.trim().parse().ok().filter(|&t| t > 0).ok_or(ParseErr("tracks are numbered from 1"))?;
+        Ok(TrackTag { track, value: value.to_string() })
+    }
+}
+
+pub fn main() {
+    let args: Vec<OsString> = env::args_os().collect();
+    // the project's settings apply to every subcommand
+    let result = config::load().and_then(|()| match args.get(1).and_then(|a| a.to_str()) {
+        Some("compare") => compare::run(compare::CompareOpt::from_iter(&args[1..])),
+        Some("history") => history::run(history::HistoryOpt::from_iter(&args[1..])),
+        Some("join-files") => join_files::run(join_files::JoinFilesOpt::from_iter(&args[1..])),
+        Some("lint") => lint::run(lint::LintOpt::from_iter(&args[1..])),
+        Some("queue") => queue::run(queue::QueueOpt::from_iter(&args[1..])),
+        Some("rerun") => history::rerun(history::RerunOpt::from_iter(&args[1..])),
+        Some("screenshot") => screenshot::run(screenshot::ScreenshotOpt::from_iter(&args[1..])),
+        Some("serve") => server::run(server::ServeOpt::from_iter(&args[1..])),
+        Some("timeline") => timeline::run(timeline::TimelineOpt::from_iter(&args[1..])),
+        Some("trailer") => trailer::run(trailer::TrailerOpt::from_iter(&args[1..])),
+        #[cfg(feature = "web")]
+        Some("web") => web::run(web::WebOpt::from_iter(&args[1..])),
+        _ => {
+            let opt = Opt::from_iter(&args);
+            let dry_run = opt.dry_run;
+            let result = run(opt);
+            if !dry_run {
+                history::record(&args, &result);
+            }
+            result
+        }
+    });
+    if let Err(err) = result {
+        print_error(&err);
+        process::exit(err.exit_code());
+    }
+}
+
+/// Prints an error and whatever caused it.
+fn print_error(err: &XclipsError) {
+    eprintln!("{}", err);
+    let mut cause = err.source();
+    while let Some(err) = cause {
+        eprintln!("  caused by: {}", err);
+        cause = err.source();
+    }
+}
+
+/// Cuts each input in turn, going on to the rest when one fails.
+fn run(opt: Opt) -> error::Result<()> {
+    if let Some(ref path) = opt.ffmpeg_path {
+        use_ffmpeg(path.clone());
+    }
+    let mut inputs = opt.files.clone();
+    if let Some(ref pattern) = opt.glob {
+        inputs.extend(glob::expand(pattern).map_err(XclipsError::Usage)?);
+    }
+    if inputs.len() <= 1 {
+        return run_input(&opt, inputs.pop());
+    }
+    // what's named once for the whole run would be overwritten by each input
+    if opt.output.is_some() && !opt.name_template.contains("{input_stem}") {
+        return Err(XclipsError::Usage("with several inputs, -o names every input's clips the same; add {input_stem} to --name-template".to_string()));
+    }
+    let once = [(opt.archive.is_some(), "--archive"), (opt.json.is_some(), "--json"), (opt.spans.timestamps_file.as_deref() == Some(Path::new("-")), "-f -")];
+    if let Some((_, flag)) = once.iter().find(|(given, _)| *given) {
+        return Err(XclipsError::Usage(format!("{} can't be used with several inputs", flag)));
+    }
+    let mut failed = 0;
+    for file in &inputs {
+        eprintln!("{}:", file.display());
+        if let Err(err) = run_input(&opt, Some(file.clone())) {
+            print_error(&err);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(XclipsError::Failed(format!("{} of {} inputs failed", failed, inputs.len())));
+    }
+    Ok(())
+}
+
+/// Cuts the clips out of one input, which is only missing for `--from-report`.
+fn run_input(opt: &Opt, input: Option<PathBuf>) -> error::Result<()> {
+    // get spans, either fresh or from an earlier run
+    let (file, mut entries): (PathBuf, Vec<Entry>) = match opt.from_report {
+        Some(ref report) => {
+            let (input_of_report, clips) = report::read(report)?;
+            let file = input.clone().unwrap_or(input_of_report);
+            let mut entries: Vec<Entry> = clips
+                .into_iter()
+                .filter(|c| !opt.only_failed || c.status != "ok")
+                .map(|c| c.entry)
+                .collect();
+            // redone clips replace the earlier outputs unless told otherwise
+            if opt.output.is_some() || opt.name_template != template::DEFAULT {
+                for entry in &mut entries {
+                    entry.output = None;
+                }
+            }
+            entries.extend(read_spans(&opt.spans, &file)?);
+            (file, entries)
+        }
+        None => {
+            let file = input.clone().unwrap();
+            let entries = read_spans(&opt.spans, &file)?;
+            (file, entries)
+        }
+    };
+    entries.sort_by_key(|e| e.span);
+
+    if opt.dry_run && !file.to_string_lossy().contains("://") && !sequence::is_sequence(&file) && !file.exists() {
+        return Err(XclipsError::Usage(format!("input {} does not exist", file.display())));
+    }
+
+    // an image sequence is read a range of frames at a time, and encoded
+    let sequence = match sequence::is_sequence(&file) {
+        true => Some(Sequence::open(&file, opt.spans.frame_rate)?),
+        false => None,
+    };
+    if sequence.is_some() {
+        let unreadable = [("--cache-input", opt.cache_input), ("--preflight", opt.preflight), ("--boundary-previews", opt.boundary_previews)];
+        let given: Vec<&str> = unreadable.iter().filter(|(_, given)| *given).map(|(name, _)| *name).collect();
+        if !given.is_empty() {
+            return Err(XclipsError::Usage(format!("{} is an image sequence, which {} can't read", file.display(), given.join(", "))));
+        }
+    }
+
+    // everything from here on reads the input, so point it at the local copy
+    // (not for a dry run, which shouldn't wait on copying it)
+    let source = if opt.cache_input && !opt.dry_run {
+        cache::fetch(&file)?
+    } else {
+        file.clone()
+    };
+
+    // an input with only audio (like an .mp3 or .flac) has no use for the
+    // options that work on video, and is copied into outputs that hold it
+    let audio_input = sequence.is_none()
+        && match probe::has_video(&source) {
+            Some(has_video) => !has_video,
+            None => source.extension().is_some_and(|e| formats::keeps_streams(&e.to_string_lossy()) == (false, true)),
+        };
+    if audio_input {
+        let video_options = [
+            ("--fit", opt.fit.is_some()),
+            ("--sharpen", opt.sharpen),
+            ("--denoise", opt.denoise.is_some()),
+            ("--pip", opt.pip.is_some()),
+            ("--endcard", opt.endcard.is_some()),
+            ("--intro", opt.intro.is_some()),
+            ("--outro", opt.outro.is_some()),
+            ("--poster", opt.poster.is_some()),
+            ("--mezzanine", opt.mezzanine.is_some()),
+            ("--format", opt.format.is_some()),
+            ("--edit-list", opt.edit_list),
+            ("--ass-subtitles", opt.ass_subtitles),
+            ("--boundary-previews", opt.boundary_previews),
+        ];
+        let given: Vec<&str> = video_options.iter().filter(|(_, given)| *given).map(|(name, _)| *name).collect();
+        if !given.is_empty() {
+            return Err(XclipsError::Usage(format!("{} has no video for {} to work on", file.display(), given.join(", "))));
+        }
+    }
+    let audio_codec = audio_input.then(|| probe::audio_codec(&source).unwrap_or_default());
+
+    if opt.gaps {
+        let duration = input::input_duration(&opt.spans, &source).map_err(|_| XclipsError::Probe { what: "duration", path: file.clone() })?;
+        let spans: Vec<Span> = entries.iter().map(|e| e.span).collect();
+        entries = transform::gaps(&spans, Timestamp::from_millis(duration.as_millis() as u64))
+            .into_iter()
+            .enumerate()
+            .map(|(n, span)| Entry::new(format!("gap #{}", n + 1), span))
+            .collect();
+    }
+
+    // overlapping spans cut the same footage twice, which among spans written
+    // out by hand is usually a mistake; generated ones (like samples) and
+    // ones running to the end may overlap by design, and so may any once
+    // --expand has widened them
+    if opt.merge_overlaps {
+        entries = resolve_overlaps(entries, true).0;
+    } else if !opt.allow_overlaps {
+        let (_, overlaps) = resolve_overlaps(entries.iter().filter(|e| e.written).cloned().collect(), false);
+        if overlaps > 0 {
+            return Err(XclipsError::Usage(format!(
+                "{} span(s) overlap others (--merge-overlaps merges them, and --allow-overlaps cuts them anyway)",
+                overlaps
+            )));
+        }
+    }
+    for entry in &mut entries {
+        if let Some(by) = opt.expand {
+            entry.span = transform::expand(entry.span, by);
+        }
+        if let Some(by) = opt.contract {
+            entry.span = transform::contract(entry.span, by);
+        }
+    }
+    if let Some(every) = opt.split_every.filter(|e| !e.is_zero()) {
+        entries = entries
+            .into_iter()
+            .flat_map(|entry| {
+                let pieces = transform::split(entry.span, every);
+                let count = pieces.len();
+                pieces.into_iter().enumerate().map(move |(k, span)| {
+                    if count == 1 {
+                        return entry.clone();
+                    }
+                    // each piece needs a name of its own
+                    Entry { origin: format!("{} (part {})", entry.origin, k + 1), span, output: None, ..entry.clone() }
+                })
+            })
+            .collect();
+    }
+
+    // reject (or drop) spans that are too short
+    if let Some(min) = opt.min_duration {
+        let (keep, short): (Vec<Entry>, Vec<Entry>) = entries.into_iter().partition(|e| e.span.duration() >= min);
+        for entry in &short {
+            if opt.drop_short {
+                eprintln!("warning: dropping span {} shorter than minimum duration", entry.span);
+            } else {
+                eprintln!("span {} is shorter than minimum duration", entry.span);
+            }
+        }
+        if !short.is_empty() && !opt.drop_short {
+            return Err(XclipsError::Usage(format!("{} span(s) shorter than minimum duration", short.len())));
+        }
+        entries = keep;
+    }
+
+    // audio and image sequences can be cut at any of their frames, so
+    // there's nothing to snap to
+    if opt.snap_keyframes && !audio_input && sequence.is_none() {
+        let index = keyframes::index(&source).ok_or_else(|| XclipsError::Probe { what: "keyframes", path: file.clone() })?;
+        for entry in &mut entries {
+            if let Some(ms) = keyframes::snap_back(&index, entry.span.start.as_millis()) {
+                entry.span.start = Timestamp::from_millis(ms);
+            }
+        }
+    }
+
+    // --edit-list leads each clip in from the keyframe before it
+    let edit_list_keyframes = match opt.edit_list {
+        true => Some(keyframes::index(&source).ok_or_else(|| XclipsError::Probe { what: "keyframes", path: file.clone() })?),
+        false => None,
+    };
+
+    // --accurate-seek only copies clips that start on a keyframe
+    let accurate_keyframes = match opt.accurate_seek && !audio_input && sequence.is_none() {
+        true => {
+            let index = keyframes::index(&source);
+            if index.is_none() {
+                eprintln!("warning: cannot find the keyframes of {}, so --accurate-seek re-encodes every clip", file.display());
+            }
+            index
+        }
+        false => None,
+    };
+
+    let input_file = source.clone().into_os_string().into_string().unwrap();
+
+    // get info to prepare output filename
+    let output = match sequence {
+        Some(_) => opt.output.clone().unwrap_or_else(|| sequence::default_output(&file)),
+        None => opt.output.clone().unwrap_or_else(|| file.clone()),
+    };
+    let (base, ext) = plan::split_output(output.as_os_str().to_str().unwrap())
+        .ok_or_else(|| XclipsError::Usage("output filename does not have a file extension".to_string()))?;
+    let base = match opt.output_dir {
+        Some(ref dir) => in_dir(dir, &base),
+        None => base,
+    };
+    // a run's outputs go in a directory of their own, so runs over the same
+    // input can't mix up (or overwrite) each other's clips
+    let run_dir = match opt.run_id {
+        Some(ref id) => {
+            let id = id.clone().unwrap_or_else(generated_run_id);
+            if id.is_empty() || id.contains(['/', '\\']) {
+                return Err(XclipsError::Usage(format!("--run-id {} has to be a single directory name", id)));
+            }
+            Some(PathBuf::from(format!("{}-{}", base, id)))
+        }
+        None => None,
+    };
+    let base = match run_dir {
+        Some(ref dir) => in_dir(dir, &base),
+        None => base,
+    };
+    let (base, ext) = (base.as_str(), ext.as_str());
+    let audio_ext = opt.audio_only.then_some(match opt.audio_format.as_deref() {
+        Some("aac") => "m4a",
+        Some(format) => format,
+        None => "mp3",
+    });
+    let forced_ext = opt.format.as_deref().or(audio_ext);
+    let ext = forced_ext.unwrap_or(ext);
+    let input_stem = match sequence {
+        Some(_) => sequence::stem(&file),
+        None => file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+    };
+    let input_name = file.file_name().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let joined_ext = if opt.mezzanine.is_some() { formats::MEZZANINE_EXT } else { ext };
+    let joined = match (&opt.output, &opt.output_dir) {
+        (Some(output), Some(dir)) => in_dir(dir, &output.to_string_lossy()),
+        (Some(output), None) => output.to_string_lossy().into_owned(),
+        (None, _) => format!("{}_joined.{}", base, joined_ext),
+    };
+    let (audio_crossfade, gap) = (opt.audio_crossfade.unwrap_or_default(), opt.gap.unwrap_or_default());
+    if (opt.audio_crossfade.is_some() || opt.gap.is_some()) && formats::keeps_streams(joined_ext).0 {
+        return Err(XclipsError::Usage(format!("--audio-crossfade and --gap only join audio, but {} keeps video", joined)));
+    }
+
+    // the kinds of streams in the input, which outputs should keep when verifying
+    let input_streams: Vec<String> = if opt.verify {
+        let types = probe::stream_types(&source).ok_or_else(|| XclipsError::Probe { what: "streams", path: file.clone() })?;
+        // cover art isn't copied along with the audio
+        let types: Vec<String> = if audio_input { types.into_iter().filter(|t| t == "audio").collect() } else { types };
+        ["video", "audio"].into_iter().filter(|t| types.iter().any(|s| s == t)).map(String::from).collect()
+    } else {
+        Vec::new()
+    };
+
+    // stream copies can lose audio track tags in some containers, so they're
+    // set explicitly; with several tracks, every one of them is kept
+    let mut audio_tags = probe::audio_tags(&source).unwrap_or_default();
+    for (tag, is_language) in opt.audio_language.iter().map(|t| (t, true)).chain(opt.audio_title.iter().map(|t| (t, false))) {
+        let tags = audio_tags.get_mut(tag.track - 1).ok_or_else(|| XclipsError::Usage(format!("{} has no audio track {}", file.display(), tag.track)))?;
+        if is_language {
+            tags.language = Some(tag.value.clone());
+        } else {
+            tags.title = Some(tag.value.clone());
+        }
+    }
+    let all_audio = audio_tags.len() > 1;
+
+    let defaults = formats::Animation::default();
+    let animation = formats::Animation {
+        fps: opt.fps.unwrap_or(defaults.fps),
+        max_width: opt.max_width.unwrap_or(defaults.max_width),
+        dither: opt.dither.clone(),
+        plays: opt.loop_count,
+    };
+    let encoding = formats::Encoding { vcodec: opt.vcodec.clone(), acodec: opt.acodec.clone(), crf: opt.crf, preset: opt.preset.clone(), bitrate: None, animation: animation.clone() };
+    // for estimating the size of stream copies, to hold them to --size-budget
+    let size_estimates = opt.size_budget.map(|_| existing::Expectations::for_input(&source, opt.spans.duration));
+    let mezzanine_args = match opt.mezzanine {
+        Some(m) => Some(m.codec_args(&probe::color(&source).ok_or_else(|| XclipsError::Probe { what: "color properties", path: file.clone() })?)),
+        None => None,
+    };
+    // what stream copies carry over, to check the outputs can hold it
+    let input_codecs = match audio_codec.as_deref() {
+        Some("") => Vec::new(),
+        Some(codec) => vec![codec.to_string()],
+        None => probe::codecs(&source).unwrap_or_default(),
+    };
+    let mut noted_incompatible = false;
+    let mut noted_accurate = false;
+    // whatever else ffmpeg should be told, that xclips has no option for
+    let mut passthrough_args = Vec::new();
+    for args in &opt.ffmpeg_args {
+        passthrough_args.extend(shell_words(args).map_err(|err| XclipsError::Usage(format!("--ffmpeg-args: {}", err)))?);
+    }
+    passthrough_args.extend(opt.trailing_ffmpeg_args.iter().cloned());
+
+    let pip_filter = opt.pip.as_ref().map(|_| filters::pip(opt.pip_position, opt.pip_scale));
+    let pip_input = opt.pip.as_ref().map(|p| p.clone().into_os_string().into_string().unwrap());
+    let mut video_filters: Vec<&str> = Vec::new();
+    if let Some(ref filter) = pip_filter {
+        video_filters.push(filter);
+    }
+    if let Some(denoise) = opt.denoise {
+        video_filters.push(denoise.filter());
+    }
+    let fit_filter = opt.fit.map(|canvas| filters::fit(canvas, opt.background));
+    if let Some(ref filter) = fit_filter {
+        video_filters.push(filter);
+    }
+    if opt.sharpen {
+        video_filters.push(filters::SHARPEN);
+    }
+    let video_filter = video_filters.join(",");
+    let endcard_text = match opt.endcard_text {
+        Some(ref text) => {
+            let path = join::temp_path("endcard.txt");
+            cache::remove_at_exit(path.clone());
+            fs::write(&path, text).map_err(|err| XclipsError::io(format!("cannot write {}", path.display()), err))?;
+            Some(path.into_os_string().into_string().unwrap())
+        }
+        None => None,
+    };
+    let audio_filters: Vec<String> = opt.compact_silence.map(filters::compact_silence).into_iter().collect();
+    // filtered audio can't be copied
+    let audio_input_codec = audio_codec.as_deref().filter(|_| audio_filters.is_empty());
+
+    let bumper = |path: &PathBuf| {
+        let duration = probe::duration(path).ok_or_else(|| XclipsError::Probe { what: "duration", path: path.clone() })?;
+        Ok((path.clone().into_os_string().into_string().unwrap(), duration))
+    };
+    let bumpers = if opt.intro.is_some() || opt.outro.is_some() {
+        Some(Arc::new(Bumpers { intro: opt.intro.as_ref().map(bumper).transpose()?, outro: opt.outro.as_ref().map(bumper).transpose()? }))
+    } else {
+        None
+    };
+
+    let subtitles = if opt.ass_subtitles {
+        Some(subs::Subtitles::extract(&source).map_err(|err| XclipsError::Command(format!("cannot read subtitles of {}: {}", file.display(), err)))?)
+    } else {
+        None
+    };
+
+    let error_args: &[&str] = if opt.ignore_errors { &["-err_detect", "ignore_err", "-fflags", "+genpts+discardcorrupt"] } else { &[] };
+
+    // which clips to cut, after --only, --skip, --only-label, --offset, and
+    // --limit; the rest keep their place, so numbering and names don't change
+    let selected: Vec<usize> = (0..entries.len())
+        .filter(|&i| opt.only.as_ref().is_none_or(|only| only.contains(i + 1)))
+        .filter(|&i| opt.skip.as_ref().is_none_or(|skip| !skip.contains(i + 1)))
+        .filter(|&i| opt.only_label.is_empty() || entries[i].label.as_ref().is_some_and(|l| opt.only_label.iter().any(|re| re.is_match(l))))
+        .skip(opt.offset)
+        .take(opt.limit.unwrap_or(usize::MAX))
+        .collect();
+
+    // cut heavily overlapping spans from one copy of the stretch they cover,
+    // rather than reading that stretch of the input again for each; not when
+    // queueing, since the copy goes away when this process exits
+    let mut segments: Vec<Option<(Timestamp, Arc<Segment>)>> = vec![None; entries.len()];
+    if opt.queue.is_none() && !opt.single_pass && sequence.is_none() {
+        let spans: Vec<Span> = selected.iter().map(|&i| entries[i].span).collect();
+        let input_ext = source.extension().map_or("mkv".into(), |e| e.to_string_lossy());
+        for (cover, members) in transform::shared_segments(&spans) {
+            let path = join::temp_path(&format!("segment.{}", input_ext));
+            cache::remove_at_exit(path.clone());
+            let output = path.into_os_string().into_string().unwrap();
+            let duration = cover.duration();
+            let time = format!("{}.{:03}", duration.as_secs(), duration.subsec_millis());
+            let mut args: Vec<String> = error_args.iter().map(|a| a.to_string()).collect();
+            args.extend(["-ss".into(), cover.start.to_string(), "-i".into(), input_file.clone(), "-t".into(), time, "-c".into(), "copy".into()]);
+            if all_audio {
+                args.extend(["-map", "0:v?", "-map", "0:a"].map(String::from));
+            }
+            args.push(output.clone());
+            let segment = Arc::new(Segment::new(output, args));
+            for k in members {
+                segments[selected[k]] = Some((cover.start, segment.clone()));
+            }
+        }
+    }
+
+    let mut jobs: Vec<Job> = Vec::new();
+    let mut planned: Vec<&Entry> = Vec::new();
+    for &i in &selected {
+        let entry = &entries[i];
+        let span = &entry.span;
+        let mut ext = if opt.mezzanine.is_some() { formats::MEZZANINE_EXT } else { forced_ext.or(entry.ext.as_deref()).unwrap_or(ext) };
+        // frames can only be copied into more frames
+        let mut reencode = opt.reencode || (sequence.is_some() && !sequence::is_image(ext));
+        let copies = formats::CodecChoice { ext, video_filter: &video_filter, mezzanine: mezzanine_args.as_deref(), reencode, encoding: &encoding, audio_input: audio_input_codec }.copies();
+        if let Some(codec) = formats::unsupported_codec(ext, &input_codecs).filter(|_| copies) {
+            let instead = match opt.incompatible {
+                formats::Incompatible::Remux => format!("copying clips into .{} instead", formats::ANY_CODEC_EXT),
+                formats::Incompatible::Reencode => "re-encoding them".to_string(),
+                formats::Incompatible::Fail => {
+                    return Err(XclipsError::Usage(format!(
+                        "{}: .{} can't hold the input's {} without re-encoding (--incompatible remux or reencode works around it)",
+                        entry.origin, ext, codec
+                    )))
+                }
+            };
+            if !noted_incompatible {
+                eprintln!("note: .{} can't hold the input's {}, so {}", ext, codec, instead);
+                noted_incompatible = true;
+            }
+            match opt.incompatible {
+                formats::Incompatible::Remux => ext = formats::ANY_CODEC_EXT,
+                _ => reencode = true,
+            }
+        }
+        if opt.compact_silence.is_some() && formats::keeps_streams(ext).0 {
+            return Err(XclipsError::Usage(format!("--compact-silence needs audio outputs (like .mp3 or .wav), not .{}", ext)));
+        }
+        let stable_id = opt.stable_names.then(|| keyframes::fnv1a(format!("{}\0{}", input_name, span).as_bytes()));
+        let vars = template::Vars { base, ext, index: i, count: entries.len(), input_stem: &input_stem, span: *span, label: entry.label.as_deref(), stable_id, scheme: opt.number_scheme };
+        let mut output_filename = match entry.output {
+            Some(ref output) => output.clone(),
+            // labeled clips are named by their labels, unless asked for numbers
+            None if opt.name_template == template::DEFAULT && opt.number_scheme == template::NumberScheme::Digits && entry.label.is_some() => {
+                template::render(template::LABELED, &vars).map_err(|err| XclipsError::Parse(format!("cannot use name template {}: {}", template::LABELED, err)))?
+            }
+            None => template::render(&opt.name_template, &vars)
+                .map_err(|err| XclipsError::Parse(format!("cannot use name template {}: {}", opt.name_template, err)))?,
+        };
+        let mut extra_args: Vec<String> = Vec::new();
+        if let Some(ref hook) = opt.hook {
+            let decision = hook::decide(hook, &input_file, i, entries.len(), span, &output_filename)?;
+            if decision.skip {
+                continue;
+            }
+            if let Some(name) = decision.name {
+                output_filename = name;
+            }
+            extra_args = decision.args;
+        }
+        if opt.bundle {
+            output_filename = bundle::video_path(&output_filename);
+        }
+        if opt.concat {
+            if !ext.eq_ignore_ascii_case(joined_ext) {
+                return Err(XclipsError::Usage(format!("--concat needs every clip in the same format, but {} is .{}", entry.origin, ext)));
+            }
+            // the clips are only pieces of the joined file
+            let path = join::temp_path(&format!("clip{}.{}", i, ext));
+            cache::remove_at_exit(path.clone());
+            output_filename = path.into_os_string().into_string().unwrap();
+        }
+
+        let (seek, input) = match segments[i] {
+            Some((offset, ref segment)) => (Timestamp::from_millis(span.start.as_millis() - offset.as_millis()), segment.output.as_str()),
+            None => (span.start, input_file.as_str()),
+        };
+        let pip_seek = Timestamp::from_millis((span.start.as_millis() as i64 + opt.pip_offset).max(0) as u64).to_string();
+        let duration = span.duration();
+        let mut time = format!("{}.{:03}", duration.as_secs(), duration.subsec_millis());
+        let on_keyframe = audio_input || accurate_keyframes.as_ref().is_some_and(|index| index.binary_search(&span.start.as_millis()).is_ok());
+        if opt.accurate_seek && copies && !on_keyframe {
+            if !noted_accurate {
+                eprintln!("note: re-encoding clips that don't start on a keyframe, so --accurate-seek can start them exactly");
+                noted_accurate = true;
+            }
+            reencode = true;
+        }
+        // copies start a little early, with an edit list hiding the extra so
+        // players still start where the span does
+        let copies = copies && !reencode;
+        let lead = match edit_list_keyframes {
+            Some(ref index) => {
+                if !copies || !formats::has_edit_lists(ext) {
+                    return Err(XclipsError::Usage(format!("--edit-list needs stream-copied .mp4 or .mov outputs, not {}", output_filename)));
+                }
+                let keyframe = keyframes::snap_back(index, span.start.as_millis()).unwrap_or(0);
+                Some(Duration::from_millis(span.start.as_millis() - keyframe))
+            }
+            None => opt.copy_lead_in.filter(|_| copies && formats::has_edit_lists(ext)),
+        };
+        let lead = lead.map(|lead| lead.min(Duration::from_millis(seek.as_millis()))).filter(|lead| !lead.is_zero());
+        // re-encoded clips can seek quickly to near the start, then decode
+        // the rest of the way to it
+        let (seek, accurate_rest) = if opt.accurate_seek && !copies && sequence.is_none() {
+            let fast = Timestamp::from_millis(seek.as_millis().saturating_sub(ACCURATE_SEEK_LEAD.as_millis() as u64));
+            (fast, Some(Timestamp::from_millis(seek.as_millis() - fast.as_millis()).to_string()))
+        } else {
+            (seek, None)
+        };
+        let mut mux_args: Vec<String> = Vec::new();
+        let mut movflags = String::new();
+        let seek = match lead {
+            Some(lead) => {
+                let long = duration + lead;
+                time = format!("{}.{:03}", long.as_secs(), long.subsec_millis());
+                mux_args.extend(["-output_ts_offset".to_string(), format!("-{}.{:03}", lead.as_secs(), lead.subsec_millis())]);
+                mux_args.extend(["-metadata".to_string(), format!("xclips_in_point={}", span.start)]);
+                movflags.push_str("+use_metadata_tags");
+                Timestamp::from_millis(seek.as_millis() - lead.as_millis() as u64).to_string()
+            }
+            None => seek.to_string(),
+        };
+        if formats::takes_movflags(ext) {
+            if opt.faststart {
+                movflags.push_str("+faststart");
+            }
+            if opt.fragmented_mp4 {
+                movflags.push_str("+frag_keyframe+empty_moov+default_base_moof");
+            }
+        }
+        // ffmpeg only takes the last -movflags, so they all go in one
+        if !movflags.is_empty() {
+            mux_args.extend(["-movflags".to_string(), movflags]);
+        }
+        // rendered frames are often RGB, which few players take in video
+        if sequence.is_some() && opt.mezzanine.is_none() && formats::keeps_streams(ext) == (true, true) && !sequence::is_image(ext) {
+            mux_args.extend(["-pix_fmt", "yuv420p"].map(String::from));
+        }
+        let sequence_args = sequence.as_ref().map(|sequence| sequence.span_args(span));
+
+        let mut args: Vec<&str> = Vec::new();
+        if opt.single_pass {
+            // just the output options; see the shared input options below
+            args.extend(["-ss", &seek, "-t", &time]);
+        } else {
+            args.extend(error_args);
+            if let Some(ref sequence_args) = sequence_args {
+                // the input itself ends with the clip's last frame
+                args.extend(sequence_args.iter().map(String::as_str));
+            } else if opt.endcard.is_some() {
+                // end the input itself, so there's a last frame to hold
+                args.extend(["-ss", &seek, "-t", &time, "-i", input]);
+            } else {
+                args.extend(["-ss", &seek, "-i", input]);
+            }
+            if let Some(ref pip) = pip_input {
+                args.extend(["-ss", &pip_seek, "-i", pip]);
+            }
+            if let Some(ref rest) = accurate_rest {
+                args.extend(["-ss", rest]);
+            }
+            if opt.endcard.is_none() && sequence_args.is_none() {
+                args.extend(["-t", &time]);
+            }
+        }
+        let mut clip_filters: Vec<String> = vec![video_filter.clone()];
+        if let Some(hold) = opt.endcard {
+            clip_filters.push(filters::endcard(duration, hold, endcard_text.as_deref()));
+        }
+        clip_filters.retain(|f| !f.is_empty());
+        let video_filter = clip_filters.join(",");
+        let fitted: formats::Encoding;
+        let mut choice = formats::CodecChoice { ext, video_filter: &video_filter, mezzanine: mezzanine_args.as_deref(), reencode, encoding: &encoding, audio_input: audio_input_codec };
+        let estimate = size_estimates.as_ref().filter(|_| choice.copies()).and_then(|e| e.copy_size(duration));
+        if let Some((budget, estimate)) = opt.size_budget.zip(estimate).filter(|(budget, estimate)| estimate > budget) {
+            let over = format!("{} would be about {}, over the budget of {}", output_filename, human_size(estimate), human_size(budget));
+            match formats::fit_bitrate(budget, duration).filter(|_| opt.auto_fit) {
+                Some(bitrate) => {
+                    eprintln!("note: {}; re-encoding it at {} kb/s to fit", over, bitrate / 1000);
+                    fitted = formats::Encoding { bitrate: Some(bitrate), ..encoding.clone() };
+                    choice.reencode = true;
+                    choice.encoding = &fitted;
+                }
+                None if opt.auto_fit => eprintln!("warning: {}, and is too long to fit by re-encoding", over),
+                None => eprintln!("warning: {}; --auto-fit would re-encode it to fit", over),
+            }
+        }
+        let mut codec_args = choice.args();
+        if pip_input.is_some() {
+            filters::to_complex(&mut codec_args);
+        }
+        // copies that start early can't simply be re-encoded instead
+        let fallback_args = (choice.copies() && lead.is_none() && !opt.single_pass && opt.copy_fallback == formats::CopyFallback::Reencode).then(|| {
+            let mut args = formats::CodecChoice { reencode: true, ..choice }.args();
+            if pip_input.is_some() {
+                filters::to_complex(&mut args);
+            }
+            args
+        });
+        let mut shrink_steps: Vec<Vec<String>> = Vec::new();
+        if opt.max_size.is_some() && animation.args(ext).is_some() {
+            let mut smaller = animation.smaller();
+            while let Some(step) = smaller {
+                let encoding = formats::Encoding { animation: step.clone(), ..choice.encoding.clone() };
+                let mut step_args = formats::CodecChoice { encoding: &encoding, ..choice }.args();
+                if pip_input.is_some() {
+                    filters::to_complex(&mut step_args);
+                }
+                shrink_steps.push(step_args);
+                smaller = step.smaller();
+            }
+        }
+        let codec_start = args.len();
+        args.extend(codec_args.iter().map(String::as_str));
+        let codec_range = codec_start..args.len();
+        let (video, audio) = formats::keeps_streams(ext);
+        let mut tag_args: Vec<String> = Vec::new();
+        if audio {
+            // audio formats hold a single track, so they get the first
+            let tracks = if video { audio_tags.len() } else { audio_tags.len().min(1) };
+            if all_audio && video && pip_input.is_none() {
+                tag_args.extend(["-map", "0:v?", "-map", "0:a"].map(String::from));
+            } else if all_audio && !video {
+                tag_args.extend(["-map", "0:a:0"].map(String::from));
+            }
+            for (n, tags) in audio_tags.iter().take(tracks).enumerate() {
+                if let Some(ref language) = tags.language {
+                    tag_args.extend([format!("-metadata:s:a:{}", n), format!("language={}", language)]);
+                }
+                if let Some(ref title) = tags.title {
+                    tag_args.extend([format!("-metadata:s:a:{}", n), format!("title={}", title)]);
+                    if formats::titles_by_handler(ext) {
+                        tag_args.extend([format!("-metadata:s:a:{}", n), format!("handler_name={}", title)]);
+                    }
+                }
+            }
+        }
+        args.extend(tag_args.iter().map(String::as_str));
+        args.extend(mux_args.iter().map(String::as_str));
+        let audio_filter = audio_filters.join(",");
+        if !audio_filter.is_empty() {
+            args.extend(["-af", &audio_filter]);
+        }
+        args.extend(extra_args.iter().map(String::as_str));
+        args.extend(passthrough_args.iter().map(String::as_str));
+        args.push(&output_filename);
+        let fallback = fallback_args.map(|codec_args| {
+            let (before, after) = (&args[..codec_range.start], &args[codec_range.end..]);
+            before.iter().map(|a| a.to_string()).chain(codec_args).chain(after.iter().map(|a| a.to_string())).collect()
+        });
+        let expected_streams = input_streams.iter().filter(|t| (video && *t == "video") || (audio && *t == "audio")).cloned().collect();
+        jobs.push(Job {
+            args: args.into_iter().map(String::from).collect(),
+            output: output_filename,
+            span: *span,
+            watch_errors: opt.ignore_errors,
+            expected_streams,
+            segment: segments[i].as_ref().map(|(_, segment)| segment.clone()),
+            poster: opt.poster.filter(|_| formats::embeds_poster(ext)),
+            bumpers: bumpers.clone().filter(|_| formats::keeps_streams(ext).0 && !opt.concat),
+            shrink: opt.max_size.filter(|_| !shrink_steps.is_empty()).map(|max_size| Shrink { max_size, codec_args: codec_range, steps: shrink_steps }),
+            report: opt.keep_going.then(|| {
+                let path = join::temp_path("ffmpeg.log");
+                cache::remove_at_exit(path.clone());
+                path
+            }),
+            fallback,
+        });
+        planned.push(entry);
+    }
+    // only the last of the clips would be left in the file
+    if let Some((first, second)) = shared_output(jobs.iter().map(|job| job.output.as_str())) {
+        let hint = if planned[first].label.is_some() && planned[second].label.is_some() { " (labels that differ only in case or punctuation name the same file)" } else { "" };
+        return Err(XclipsError::Usage(format!("{} and {} would both be written to {}{}", planned[first].origin, planned[second].origin, jobs[second].output, hint)));
+    }
+
+    if opt.diff_existing || opt.skip_existing {
+        let expectations = existing::Expectations::for_input(&source, opt.spans.duration);
+        let mut keep = Vec::new();
+        for (job, entry) in jobs.into_iter().zip(planned) {
+            let state = expectations.check(&job);
+            let complete = matches!(state, existing::State::Complete);
+            if opt.diff_existing {
+                let action = match state {
+                    existing::State::Missing => "create".to_string(),
+                    existing::State::Empty => "overwrite (existing file is empty)".to_string(),
+                    existing::State::DurationMismatch(d) => {
+                        format!("overwrite (existing file is {}.{:03}s long)", d.as_secs(), d.subsec_millis())
+                    }
+                    existing::State::SizeMismatch(size, estimate) => {
+                        format!("overwrite (existing file is {} bytes, expected about {})", size, estimate)
+                    }
+                    existing::State::Complete if opt.skip_existing => "skip (up to date)".to_string(),
+                    existing::State::Complete => "overwrite (up to date)".to_string(),
+                };
+                println!("{}: {} ({})", job.output, action, job.span);
+            }
+            if !(opt.skip_existing && complete) {
+                keep.push((job, entry));
+            }
+        }
+        if opt.diff_existing {
+            return Ok(());
+        }
+        (jobs, planned) = keep.into_iter().unzip();
+    }
+
+    if opt.interactive {
+        let width = jobs.len().to_string().len();
+        let items: Vec<String> = jobs
+            .iter()
+            .zip(&planned)
+            .enumerate()
+            .map(|(i, (job, entry))| {
+                let d = job.span.duration();
+                let label = entry.label.as_deref().unwrap_or_default();
+                format!("{:>w$}  {}  {:>4}.{:03}s  {}", i + 1, job.span, d.as_secs(), d.subsec_millis(), label, w = width).trim_end().to_string()
+            })
+            .collect();
+        let checked = match pick::choose(&items)? {
+            Some(checked) => checked,
+            None => {
+                eprintln!("nothing cut");
+                return Ok(());
+            }
+        };
+        (jobs, planned) = jobs.into_iter().zip(planned).zip(checked).filter(|(_, checked)| *checked).map(|(pair, _)| pair).unzip();
+    }
+
+    // spans past the end of the input make empty or cut-short clips, so
+    // catch them before running anything
+    let input_end = input::input_duration(&opt.spans, &source).ok().map(|d| Timestamp::from_millis(d.as_millis() as u64));
+    match input_end {
+        Some(input_end) => {
+            let mut out_of_range = 0;
+            for (job, entry) in jobs.iter().zip(&planned) {
+                let past = if job.span.start >= input_end {
+                    "starts"
+                } else if job.span.end > input_end {
+                    "ends"
+                } else {
+                    continue;
+                };
+                let warning = if opt.allow_out_of_range { "warning: " } else { "" };
+                eprintln!("{}{}: span {} {} after the input ends at {}", warning, entry.origin, job.span, past, input_end);
+                out_of_range += 1;
+            }
+            if out_of_range > 0 && !opt.allow_out_of_range {
+                return Err(XclipsError::Usage(format!("{} span(s) run past the end of the input (--allow-out-of-range cuts them anyway)", out_of_range)));
+            }
+        }
+        None => eprintln!("warning: cannot find how long {} is, so the spans aren't checked against it", file.display()),
+    }
+
+    if opt.print_durations || opt.strict {
+        let input_end = input_end.ok_or_else(|| XclipsError::Probe { what: "duration", path: file.clone() })?;
+        let seconds = |d: Duration| format!("{}.{:03}s", d.as_secs(), d.subsec_millis());
+        let mut total = Duration::ZERO;
+        let mut empty = 0;
+        for job in &jobs {
+            let length = Duration::from_millis(job.span.end.min(input_end).as_millis().saturating_sub(job.span.start.as_millis()));
+            if length.is_zero() {
+                empty += 1;
+            }
+            total += length;
+            if opt.print_durations {
+                let note = if job.span.end > input_end { format!(", cut short by the input ending at {}", input_end) } else { String::new() };
+                println!("{}: {} ({}{})", job.output, seconds(length), job.span, note);
+            }
+        }
+        if opt.print_durations {
+            println!("total: {} in {} clip(s)", seconds(total), jobs.len());
+        }
+        if opt.strict && empty > 0 {
+            return Err(XclipsError::Usage(format!("{} clip(s) would be empty", empty)));
+        }
+    }
+
+    if opt.preflight {
+        let mut unreadable = 0;
+        for (job, entry) in jobs.iter().zip(&planned) {
+            if let Err(err) = probe::decodes(&source, job.span.start, PREFLIGHT_LENGTH.min(job.span.duration())) {
+                eprintln!("{}: cannot read {} at {}: {}", entry.origin, file.display(), job.span.start, err);
+                unreadable += 1;
+            }
+        }
+        if unreadable > 0 {
+            return Err(XclipsError::Failed(format!("the input can't be read where {} clip(s) start", unreadable)));
+        }
+    }
+
+    if opt.boundary_previews {
+        for job in &jobs {
+            let (base, _) = plan::split_output(&job.output).unwrap_or_else(|| (job.output.clone(), String::new()));
+            screenshot::boundaries(&input_file, &job.span, &base)?;
+        }
+        eprintln!("made first and last frame previews of {} clip(s)", jobs.len());
+    }
+
+    let mut input_args: Vec<String> = error_args.iter().map(|a| a.to_string()).collect();
+    match sequence {
+        Some(ref sequence) => input_args.extend(sequence.input_args()),
+        None => input_args.extend(["-i".to_string(), input_file.clone()]),
+    }
+    if opt.dry_run {
+        return dry_run(&jobs, &planned, &input_args, opt.single_pass, input_end);
+    }
+
+    // ffmpeg has no one to ask whether to replace an output, so only does
+    // when told to (a queue's own outputs are its to replace, though)
+    let overwrite = opt.overwrite || opt.skip_existing || opt.from_report.is_some();
+    let queued = match opt.queue {
+        Some(ref db) if !overwrite => queue::outputs(db)?,
+        _ => Vec::new(),
+    };
+    let existing: Vec<&str> =
+        jobs.iter().map(|job| job.output.as_str()).filter(|output| Path::new(output).exists() && !queued.iter().any(|q| q == output)).collect();
+    if let (Some(first), false) = (existing.first(), overwrite) {
+        return Err(XclipsError::Usage(format!(
+            "{} output(s) already exist, like {} (--overwrite replaces them, and --skip-existing skips the finished ones)",
+            existing.len(),
+            first
+        )));
+    }
+
+    if let Some(ref dir) = opt.output_dir {
+        fs::create_dir_all(dir).map_err(|err| XclipsError::io(format!("cannot create {}", dir.display()), err))?;
+    }
+    if let Some(ref dir) = run_dir {
+        fs::create_dir_all(dir).map_err(|err| XclipsError::io(format!("cannot create {}", dir.display()), err))?;
+        if opt.run_id.as_ref().is_some_and(Option::is_none) {
+            eprintln!("writing clips to {}", dir.display());
+        }
+    }
+
+    let ui = ui::Ui::new(opt.no_color);
+    let meter = ui::Meter::new(jobs.len());
+    let options = run_options(opt, &ui, overwrite, &meter);
+    if let Some(time) = opt.start_at {
+        schedule::wait_until(time)?;
+    }
+    if let Some(ref db) = opt.queue {
+        queue::add(db, &jobs)?;
+        let failed = queue::work(db, &options)?;
+        if failed > 0 {
+            return Err(XclipsError::Failed(format!("{} clip(s) failed; see `xclips queue status {}`", failed, db.display())));
+        }
+        return Ok(());
+    }
+
+    if opt.bundle {
+        for job in &jobs {
+            let dir = bundle::dir(&job.output);
+            fs::create_dir_all(&dir).map_err(|err| XclipsError::io(format!("cannot create {}", dir.display()), err))?;
+        }
+    }
+
+    let mut archive = match opt.archive {
+        Some(ref path) => Some(Archive::create(path).map_err(|err| XclipsError::io(format!("cannot create {}", path.display()), err))?),
+        None => None,
+    };
+    let mut records: Vec<report::Record> =
+        planned.iter().zip(&jobs).map(|(entry, job)| report::Record { entry, output: &job.output, status: "pending", error: None, upload: None, upload_error: None, reencoded: false }).collect();
+    let mut unverified = 0;
+    let mut timed_out = 0;
+    let mut stalled = 0;
+    let mut not_uploaded = 0;
+    let mut failed = 0;
+    let batch_size = if opt.single_pass { SINGLE_PASS_OUTPUTS } else { 1 };
+    let batches: Vec<&[Job]> = jobs.chunks(batch_size).collect();
+    // pauses space batches out one after another, so they can't overlap
+    let workers = if opt.pause_between.is_some() { 1 } else { opt.jobs.unwrap_or_else(default_jobs).clamp(1, batches.len().max(1)) };
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let count = jobs.len();
+    let cut = thread::scope(|scope| -> error::Result<()> {
+        let (done, finished) = mpsc::channel();
+        for _ in 0..workers {
+            let (done, next, stop, batches, input_args, options) = (done.clone(), &next, &stop, &batches, &input_args, &options);
+            scope.spawn(move || {
+                while !stop.load(Ordering::SeqCst) {
+                    let n = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(batch) = batches.get(n) else {
+                        break;
+                    };
+                    if let Some(pause) = opt.pause_between.filter(|_| n > 0) {
+                        schedule::pause(pause);
+                    }
+                    let options = options.for_batch(n * batch_size, batch, count);
+                    if done.send((n, run_batch(input_args, batch, opt.single_pass, &options))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(done);
+        // results come back as batches finish, which with several workers
+        // isn't necessarily the order they were planned in
+        let mut reported = 0;
+        for (n, results) in finished {
+            let batch = batches[n];
+            for (i, (job, result)) in batch.iter().zip(results).enumerate() {
+                let i = n * batch_size + i;
+                let status = |status, detail: Option<&str>| ui.clip(i, jobs.len(), status, &job.output, &job.span, detail);
+                let loose = if opt.bundle { bundle::dir(&job.output) } else { PathBuf::from(&job.output) };
+                let set_aside = || {
+                    if opt.keep_going {
+                        match quarantine(&loose, job.report.as_deref()) {
+                            Ok(Some(dir)) => eprintln!("moved {} to {}", loose.display(), dir.display()),
+                            Ok(None) => {}
+                            Err(err) => eprintln!("warning: cannot move {} out of the way: {}", loose.display(), err),
+                        }
+                    }
+                };
+                match result {
+                    Ok(reencoded) => {
+                        status(ui::Status::Ok, reencoded.then_some("re-encoded after the copy failed"));
+                        records[i].status = "ok";
+                        records[i].reencoded = reencoded;
+                        let has_video = Path::new(&job.output).extension().is_some_and(|e| formats::keeps_streams(&e.to_string_lossy()).0);
+                        let mut wrote_subtitles = false;
+                        if let Some(subtitles) = subtitles.as_ref().filter(|_| has_video) {
+                            let offset = job.bumpers.as_ref().and_then(|b| b.intro.as_ref()).map_or(0, |(_, d)| d.as_millis() as u64);
+                            let base = if opt.bundle { bundle::subtitles_base(&job.output) } else { job.output.rsplit_once('.').map_or(&*job.output, |(b, _)| b).to_string() };
+                            match subtitles.write_clip(&job.span, offset, &base) {
+                                Ok(()) => wrote_subtitles = true,
+                                Err(err) => eprintln!("warning: cannot write subtitles for {}: {}", job.output, err),
+                            }
+                        }
+                        if opt.bundle {
+                            if let Err(err) = bundle::finish(&job.output, &job.span, planned[i].label.as_deref(), &file, wrote_subtitles) {
+                                eprintln!("warning: cannot finish the bundle for {}: {}", job.output, err);
+                            }
+                        }
+                        if opt.sidecar {
+                            if let Err(err) = sidecar::write(job, planned[i].label.as_deref(), &file) {
+                                eprintln!("warning: cannot write the sidecar for {}: {}", job.output, err);
+                            }
+                        }
+                        if let Some(ref destination) = opt.upload {
+                            match destination.upload(&loose, opt.upload_retries) {
+                                Ok(()) => records[i].upload = Some("ok"),
+                                Err(err) => {
+                                    eprintln!("warning: cannot upload {} to {}: {}", loose.display(), destination, err);
+                                    records[i].upload = Some("failed");
+                                    records[i].upload_error = Some(err);
+                                    not_uploaded += 1;
+                                }
+                            }
+                        }
+                        if let Some(archive) = archive.as_mut().filter(|_| !opt.concat) {
+                            add_to_archive(archive, &loose, opt.remove_archived)?;
+                        }
+                        if opt.list_outputs && !opt.concat && !opt.remove_archived {
+                            println!("{}", job.output);
+                        }
+                    }
+                    Err(Failure::Verify(problem)) => {
+                        status(ui::Status::Failed, Some(&format!("output {}", problem)));
+                        records[i].status = "failed";
+                        records[i].error = Some(format!("output {}", problem));
+                        unverified += 1;
+                        set_aside();
+                    }
+                    Err(failure @ (Failure::Timeout | Failure::Stalled)) => {
+                        status(ui::Status::Failed, Some(&failure.to_string()));
+                        records[i].status = "failed";
+                        records[i].error = Some(failure.to_string());
+                        if let Failure::Timeout = failure {
+                            timed_out += 1;
+                        } else {
+                            stalled += 1;
+                        }
+                        set_aside();
+                    }
+                    Err(failure) => {
+                        status(ui::Status::Failed, Some(&failure.to_string()));
+                        records[i].status = "failed";
+                        records[i].error = Some(failure.to_string());
+                        if opt.keep_going {
+                            failed += 1;
+                            set_aside();
+                            continue;
+                        }
+                        if let Some(ref path) = opt.json {
+                            report::write(path, &file, &records)?;
+                        }
+                        if let Some(archive) = archive.take() {
+                            // keep what was made so far readable
+                            let _ = archive.finish();
+                        }
+                        // let the clips already being cut finish, but start no more
+                        stop.store(true, Ordering::SeqCst);
+                        return Err(XclipsError::Encode { output: job.output.clone(), failure });
+                    }
+                }
+            }
+            reported += batch.len();
+            if let Some(path) = opt.json.as_ref().filter(|_| reported >= REPORT_CHECKPOINT) {
+                report::write(path, &file, &records)?;
+                reported = 0;
+            }
+        }
+        Ok(())
+    });
+    history::note_clips(&file, &records);
+    cut?;
+    if opt.measure_accuracy {
+        measure_accuracy(&jobs, &records);
+    }
+    if let Some(ref path) = opt.json {
+        report::write(path, &file, &records)?;
+    }
+    if !opt.concat {
+        if let Some(archive) = archive.take() {
+            finish_archive(archive, opt.archive.as_ref().unwrap(), opt.list_outputs)?;
+        }
+    }
+    if opt.keep_going {
+        let width = records.iter().map(|r| r.output.len()).max().unwrap_or(0);
+        eprintln!();
+        for record in &records {
+            let error = record.error.as_deref().map_or(String::new(), |e| format!("  {}", e));
+            eprintln!("{:<7} {:<width$}  {}{}", record.status, record.output, record.entry.span, error, width = width);
+        }
+    }
+    let mut problems: Vec<String> = Vec::new();
+    if failed > 0 {
+        problems.push(format!("{} clip(s) failed", failed));
+    }
+    if unverified > 0 {
+        problems.push(format!("{} clip(s) failed verification", unverified));
+    }
+    if timed_out > 0 {
+        problems.push(format!("{} clip(s) timed out", timed_out));
+    }
+    if stalled > 0 {
+        problems.push(format!("{} clip(s) stalled", stalled));
+    }
+    if not_uploaded > 0 {
+        problems.push(format!("{} clip(s) failed to upload", not_uploaded));
+    }
+    if !problems.is_empty() {
+        return Err(XclipsError::Failed(problems.join(", ")));
+    }
+
+    if opt.concat && !jobs.is_empty() {
+        let paths: Vec<String> = jobs.iter().map(|job| job.output.clone()).collect();
+        let whole = |job: &Job| Span { start: Timestamp::from_millis(0), end: Timestamp::from_millis(job.span.duration().as_millis() as u64) };
+        let pieces: Vec<join::Piece> = paths.iter().zip(&jobs).map(|(input, job)| join::Piece { input, span: whole(job) }).collect();
+        let starts = join::audio_starts(&pieces, audio_crossfade, gap);
+        let length = Duration::from_millis(starts[starts.len() - 1].as_millis()) + pieces[pieces.len() - 1].span.duration();
+
+        // a chapter for each clip, titled with its label and where it came from
+        let intro = bumpers.as_ref().and_then(|b| b.intro.as_ref()).map_or(0, |(_, length)| length.as_millis() as u64);
+        let chapters: Vec<join::Chapter> = planned
+            .iter()
+            .zip(&pieces)
+            .zip(&starts)
+            .map(|((entry, piece), start)| join::Chapter {
+                title: entry.label.as_ref().map_or_else(|| entry.span.to_string(), |label| format!("{} ({})", label, entry.span)),
+                start: Timestamp::from_millis(intro + start.as_millis()),
+                end: Timestamp::from_millis(intro + start.as_millis() + piece.span.duration().as_millis() as u64),
+            })
+            .collect();
+        let metadata = join::temp_path("chapters.txt");
+        cache::remove_at_exit(metadata.clone());
+        fs::write(&metadata, join::chapters_metadata(&chapters)).map_err(|err| XclipsError::io(format!("cannot write {}", metadata.display()), err))?;
+        let metadata = metadata.to_string_lossy();
+        // with bumpers, the chapters go in when they're attached
+        let join_metadata = Some(&*metadata).filter(|_| bumpers.is_none());
+
+        let mut args: Vec<String> = ["-v", "error", "-y"].map(String::from).to_vec();
+        if audio_crossfade.is_zero() && gap.is_zero() {
+            let list = join::temp_path("concat.txt");
+            cache::remove_at_exit(list.clone());
+            fs::write(&list, join::concat_list(&paths)).map_err(|err| XclipsError::io(format!("cannot write {}", list.display()), err))?;
+            args.extend(join::demuxer_args(&list.to_string_lossy(), join_metadata, &joined));
+        } else {
+            args.extend(join::audio_args(&pieces, audio_crossfade, gap, join_metadata, &joined));
+        }
+        let status = ffmpeg().args(&args).status().map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+        if !status.success() {
+            return Err(XclipsError::Encode { output: joined, failure: Failure::Ffmpeg });
+        }
+        if let Some(ref bumpers) = bumpers {
+            job::attach_bumpers(&joined, length, bumpers, Some(&metadata), &options).map_err(|failure| XclipsError::Encode { output: joined.clone(), failure })?;
+        }
+        eprintln!("joined {} clip(s) into {}", jobs.len(), joined);
+        if let Some(mut archive) = archive {
+            add_to_archive(&mut archive, Path::new(&joined), opt.remove_archived)?;
+            finish_archive(archive, opt.archive.as_ref().unwrap(), opt.list_outputs)?;
+        }
+        if opt.list_outputs && !opt.remove_archived {
+            println!("{}", joined);
+        }
+    }
+    Ok(())
+}
+
+/// Prints how much earlier than asked each clip that was cut starts, taking
+/// whatever it runs over its span's length to be lead-in.
+fn measure_accuracy(jobs: &[Job], records: &[report::Record]) {
+    let seconds = |d: Duration| format!("{}.{:03}s", d.as_secs(), d.subsec_millis());
+    let mut drifts = Vec::new();
+    for (job, _) in jobs.iter().zip(records).filter(|(_, r)| r.status == "ok") {
+        let Some(length) = probe::duration(Path::new(&job.output)) else {
+            eprintln!("warning: cannot measure {}", job.output);
+            continue;
+        };
+        let early = length.saturating_sub(job.span.duration());
+        println!("{}: starts {} before {}", job.output, seconds(early), job.span.start);
+        drifts.push(early);
+    }
+    if let Some(&most) = drifts.iter().max() {
+        let mean = drifts.iter().sum::<Duration>() / drifts.len() as u32;
+        println!("start drift in {} clip(s): {} on average, {} at most", drifts.len(), seconds(mean), seconds(most));
+    }
+}
+
+/// Moves what's left of a clip that failed into a `failed/` directory beside
+/// it, with ffmpeg's log of cutting it, so only good clips are left where
+/// they were. Returns the directory, if there was anything to move.
+fn quarantine(output: &Path, report: Option<&Path>) -> io::Result<Option<PathBuf>> {
+    let report = report.filter(|r| r.exists());
+    if !output.exists() && report.is_none() {
+        return Ok(None);
+    }
+    let dir = output.parent().unwrap_or(Path::new("")).join("failed");
+    fs::create_dir_all(&dir)?;
+    let name = output.file_name().unwrap_or_default();
+    if output.exists() {
+        fs::rename(output, dir.join(name))?;
+    }
+    if let Some(report) = report {
+        let mut log = name.to_os_string();
+        log.push(".log");
+        // the log is in the temporary directory, which may be another file system
+        fs::copy(report, dir.join(log))?;
+        let _ = fs::remove_file(report);
+    }
+    Ok(Some(dir))
+}
+
+/// The first two of `outputs` that are the same, if any are.
+fn shared_output<'a>(outputs: impl IntoIterator<Item = &'a str>) -> Option<(usize, usize)> {
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    for (i, output) in outputs.into_iter().enumerate() {
+        if let Some(&first) = seen.get(output) {
+            return Some((first, i));
+        }
+        seen.insert(output, i);
+    }
+    None
+}
+
+/// Finds the entries whose spans overlap (or duplicate) an earlier one,
+/// reporting each, and with `merge` folds them into the span they overlap.
+/// Returns the entries, sorted, and how many overlaps were left unmerged.
+fn resolve_overlaps(mut entries: Vec<Entry>, merge: bool) -> (Vec<Entry>, usize) {
+    entries.sort_by_key(|e| e.span);
+    let mut kept: Vec<Entry> = Vec::new();
+    // the kept entry that extends furthest so far
+    let mut furthest: Option<usize> = None;
+    let mut overlaps = 0;
+    for entry in entries {
+        let Some(f) = furthest.filter(|&f| entry.span.start < kept[f].span.end || entry.span == kept[f].span) else {
+            furthest = Some(kept.len());
+            kept.push(entry);
+            continue;
+        };
+        let prev = &mut kept[f];
+        let what = if entry.span == prev.span { "duplicates" } else { "overlaps" };
+        if merge {
+            eprintln!("note: merging {} ({}) into {} ({}), which it {}", entry.origin, entry.span, prev.origin, prev.span, what);
+            prev.span.end = prev.span.end.max(entry.span.end);
+            continue;
+        }
+        eprintln!("{}: span {} {} {} from {}", entry.origin, entry.span, what, prev.span, prev.origin);
+        overlaps += 1;
+        if entry.span.end > prev.span.end {
+            furthest = Some(kept.len());
+        }
+        kept.push(entry);
+    }
+    (kept, overlaps)
+}
+
+/// Prints the ffmpeg commands a run would make, shell-quoted, each after a
+/// comment naming its output, and checks that no span is empty or starts
+/// after the input (when its length is known) ends.
+fn dry_run(jobs: &[Job], planned: &[&Entry], input_args: &[String], single_pass: bool, input_end: Option<Timestamp>) -> error::Result<()> {
+    let command = |args: &[String]| format!("{} {}", shell_quote(&ffmpeg_path().to_string_lossy()), args.iter().map(|a| shell_quote(a)).collect::<Vec<_>>().join(" "));
+    let mut segments: Vec<&Arc<Segment>> = Vec::new();
+    let batch_size = if single_pass { SINGLE_PASS_OUTPUTS } else { 1 };
+    for batch in jobs.chunks(batch_size) {
+        for job in batch {
+            if let Some(segment) = job.segment.as_ref().filter(|s| !segments.iter().any(|seen| Arc::ptr_eq(seen, s))) {
+                println!("# {} (shared by the clips that overlap it)", segment.output);
+                println!("{}", command(&segment.args));
+                segments.push(segment);
+            }
+            println!("# {} ({})", job.output, job.span);
+        }
+        if single_pass {
+            let args: Vec<String> = input_args.iter().chain(batch.iter().flat_map(|job| &job.args)).cloned().collect();
+            println!("{}", command(&args));
+        } else {
+            println!("{}", command(&batch[0].args));
+        }
+        for job in batch.iter().filter(|job| job.bumpers.is_some() || job.poster.is_some() || job.shrink.is_some()) {
+            let then: Vec<&str> = [
+                (job.shrink.is_some(), "cut again smaller if over --max-size"),
+                (job.bumpers.is_some(), "add the intro and outro"),
+                (job.poster.is_some(), "embed the poster frame"),
+            ]
+            .into_iter()
+            .filter_map(|(applies, step)| applies.then_some(step))
+            .collect();
+            println!("# then {}: {}", job.output, then.join(", then "));
+        }
+    }
+
+    let mut problems = 0;
+    for (job, entry) in jobs.iter().zip(planned) {
+        let problem = if job.span.start == job.span.end {
+            Some("has zero length".to_string())
+        } else {
+            input_end.filter(|&end| job.span.start >= end).map(|end| format!("starts after the input ends at {}", end))
+        };
+        if let Some(problem) = problem {
+            eprintln!("{}: span {} {}", entry.origin, job.span, problem);
+            problems += 1;
+        }
+    }
+    eprintln!("{} clip(s) planned; nothing cut (--dry-run)", jobs.len());
+    if problems > 0 {
+        return Err(XclipsError::Failed(format!("{} problem(s) found", problems)));
+    }
+    Ok(())
+}
+
+/// Cuts one batch of clips: a single clip, or with `--single-pass` several
+/// from one ffmpeg run.
+fn run_batch(input_args: &[String], batch: &[Job], single_pass: bool, options: &RunOptions) -> Vec<Result<bool, Failure>> {
+    if single_pass {
+        match job::execute_together(input_args, batch, options) {
+            Ok(()) => batch.iter().map(|job| job.finish(options).map(|()| false)).collect(),
+            Err(failure) => batch.iter().map(|_| Err(failure.clone())).collect(),
+        }
+    } else {
+        batch.iter().map(|job| job.execute(options)).collect()
+    }
+}
+
+/// A run ID for `--run-id` without one: the local time and the process ID,
+/// which no other run can have at the same time.
+fn generated_run_id() -> String {
+    let time = template::strftime("%Y%m%d-%H%M%S").unwrap_or_else(|_| {
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+        now.as_secs().to_string()
+    });
+    format!("{}-{}", time, process::id())
+}
+
+/// How many clips to cut at once without `--jobs`: one per CPU.
+/// How clips are run, given the options and where the status lines go.
+fn run_options<'a>(opt: &Opt, ui: &ui::Ui, overwrite: bool, meter: &'a ui::Meter) -> RunOptions<'a> {
+    // ffmpeg logs from several clips at once would be interleaved beyond
+    // reading, and so would a bar for each, so they share one
+    let parallel = opt.pause_between.is_none() && opt.jobs.unwrap_or_else(default_jobs) > 1;
+    let progress = ui.progress_bars();
+    RunOptions {
+        timeout: opt.clip_timeout,
+        quiet: ui.quiet_ffmpeg() || parallel || progress || opt.quiet,
+        stall: opt.stall_timeout,
+        stall_retries: opt.stall_retries,
+        progress,
+        silent: opt.quiet,
+        overwrite,
+        meter: Some(meter).filter(|_| parallel),
+        ..RunOptions::default()
+    }
+}
+
+fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Adds a finished clip (or bundle directory) to the archive under its file
+/// name, deleting it afterwards if asked to.
+fn add_to_archive(archive: &mut Archive, loose: &Path, remove: bool) -> error::Result<()> {
+    let name = loose.file_name().unwrap_or_default().to_string_lossy();
+    archive.add(loose, &name).map_err(|err| XclipsError::io(format!("cannot add {} to the archive", loose.display()), err))?;
+    if remove {
+        let removed = if loose.is_dir() { fs::remove_dir_all(loose) } else { fs::remove_file(loose) };
+        removed.map_err(|err| XclipsError::io(format!("cannot remove {}", loose.display()), err))?;
+    }
+    Ok(())
+}
+
+/// `path` moved into `dir`, keeping its file name.
+fn in_dir(dir: &Path, path: &str) -> String {
+    dir.join(Path::new(path).file_name().unwrap_or_default()).to_string_lossy().into_owned()
+}
+
+/// Finishes writing the archive, listing it with the outputs if asked to.
+fn finish_archive(archive: Archive, path: &Path, list_outputs: bool) -> error::Result<()> {
+    archive.finish().map_err(|err| XclipsError::io(format!("cannot finish {}", path.display()), err))?;
+    if list_outputs {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subcommands_are_all_in_the_help() {
+        let listed: Vec<&str> = SUBCOMMANDS_HELP.lines().skip(1).take_while(|l| !l.is_empty()).filter_map(|l| l.split_whitespace().next()).collect();
+        assert_eq!(listed, SUBCOMMANDS);
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("500k"), Ok(500_000));
+        assert_eq!(parse_size("25MB"), Ok(25_000_000));
+        assert_eq!(parse_size("1.5 GiB"), Ok(1_610_612_736));
+        assert_eq!(parse_size("8mib"), Ok(8 << 20));
+        assert!(parse_size("25 TB").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    #[test]
+    fn overlaps() {
+        let entries = |spans: &[&str]| spans.iter().enumerate().map(|(n, s)| Entry::new(format!("clip #{}", n + 1), s.parse().unwrap())).collect::<Vec<_>>();
+        let spans = |entries: &[Entry]| entries.iter().map(|e| e.span.to_string()).collect::<Vec<_>>();
+        let list = entries(&["0-100", "10-20", "30-40", "100-110", "100-110"]);
+        let (kept, overlaps) = resolve_overlaps(list.clone(), false);
+        assert_eq!((kept.len(), overlaps), (5, 3));
+        let (merged, overlaps) = resolve_overlaps(list, true);
+        assert_eq!((spans(&merged), overlaps), (vec!["0.000-100.000".to_string(), "100.000-110.000".to_string()], 0));
+        let (merged, _) = resolve_overlaps(entries(&["5-8", "0-6"]), true);
+        assert_eq!((spans(&merged), merged[0].origin.as_str()), (vec!["0.000-8.000".to_string()], "clip #2"));
+    }
+
+    #[test]
+    fn progress_on_a_terminal() {
+        let (ui, meter) = (ui::Ui::terminal(), ui::Meter::new(2));
+        let options = |args: &[&str]| run_options(&Opt::from_iter(["xclips", "talk.mp4"].iter().chain(args)), &ui, false, &meter);
+        // by default, clips are cut one per CPU
+        let default = options(&[]);
+        assert_eq!(default.meter.is_some(), default_jobs() > 1);
+        assert!(default.for_batch(0, &[], 2).position.is_some());
+        let parallel = options(&["--jobs", "4"]);
+        assert!(parallel.meter.is_some() && parallel.quiet && parallel.for_batch(1, &[], 2).position.is_some());
+        let one_at_a_time = options(&["--jobs", "1"]);
+        assert!(one_at_a_time.meter.is_none() && one_at_a_time.for_batch(0, &[], 2).position.is_some());
+    }
+
+    #[test]
+    fn labels_naming_one_file() {
+        let output = |index, label| {
+            let vars = template::Vars { base: "match", ext: "mp4", index, count: 3, input_stem: "match", span: "0-1".parse().unwrap(), label, stable_id: None, scheme: template::NumberScheme::Label };
+            template::render(template::DEFAULT, &vars).unwrap()
+        };
+        let outputs = [output(0, Some("Goal by Smith")), output(1, None), output(2, Some("goal by smith!"))];
+        assert_eq!(outputs[2], "match_clipgoal-by-smith.mp4");
+        assert_eq!(shared_output(outputs.iter().map(String::as_str)), Some((0, 2)));
+        assert_eq!(shared_output(outputs[..2].iter().map(String::as_str)), None);
+    }
+
+    #[test]
+    fn human_sizes() {
+        assert_eq!(human_size(512), "512 bytes");
+        assert_eq!(human_size(31_234_567), "31.2 MB");
+        assert_eq!(human_size(2_500_000_000), "2.50 GB");
+    }
+
+    #[test]
+    fn moved_into_dir() {
+        assert_eq!(in_dir(Path::new("clips"), "/home/me/recordings/talk"), "clips/talk");
+        assert_eq!(in_dir(Path::new("/tmp/out"), "talk_joined.mp4"), "/tmp/out/talk_joined.mp4");
+    }
+}
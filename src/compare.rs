@@ -0,0 +1,112 @@
+use std::fs;
+use std::path::PathBuf;
+
+use regex::Regex;
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::input::{read_spans, Entry, SpanSource};
+use crate::job::Failure;
+use crate::{ffmpeg, filters, formats, join, template};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "compare", bin_name = "xclips compare", setting = AppSettings::AllowNegativeNumbers)]
+pub struct CompareOpt {
+    #[structopt(flatten)]
+    spans: SpanSource,
+
+    #[structopt(short = "o", long = "output")]
+    output: Option<PathBuf>,
+
+    /// Output filename template (see xclips --help for placeholders)
+    #[structopt(long = "name-template", default_value = "{base}_compare{index}.{ext}")]
+    name_template: String,
+
+    /// Stack the files top to bottom instead of side by side
+    #[structopt(long = "vertical")]
+    vertical: bool,
+
+    /// Text burned into each side, given once per file (e.g. --label before --label after)
+    #[structopt(long = "label", number_of_values = 1, max_values = 2)]
+    label: Vec<String>,
+
+    #[structopt(name = "FILE_A", parse(from_os_str))]
+    a: PathBuf,
+
+    #[structopt(name = "FILE_B", parse(from_os_str))]
+    b: PathBuf,
+}
+
+/// Cuts the same spans out of two files and stacks them into one clip each,
+/// for looking at (say) two encodes of the same footage at once. The audio
+/// comes from the first file.
+pub fn run(opt: CompareOpt) -> Result<()> {
+    let entries = read_spans(&opt.spans, &opt.a)?;
+    let input_stem = opt.a.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let output = opt.output.clone().unwrap_or_else(|| opt.a.clone());
+    let output = output.to_string_lossy();
+    let captures = Regex::new(r"^(.*)\.(.*)$")
+        .unwrap()
+        .captures(&output)
+        .ok_or_else(|| XclipsError::Usage("output filename does not have a file extension".to_string()))?;
+    let (base, ext) = (&captures[1], &captures[2]);
+
+    // drawtext reads the labels from files, which sidesteps filtergraph escaping
+    let mut label_files: Vec<PathBuf> = Vec::new();
+    for label in &opt.label {
+        let path = join::temp_path("label.txt");
+        if let Err(err) = fs::write(&path, label) {
+            remove_all(&label_files);
+            return Err(XclipsError::io(format!("cannot write {}", path.display()), err));
+        }
+        label_files.push(path);
+    }
+    let result = cut(&opt, &entries, base, ext, &input_stem, &label_files);
+    remove_all(&label_files);
+    result
+}
+
+fn cut(opt: &CompareOpt, entries: &[Entry], base: &str, ext: &str, input_stem: &str, label_files: &[PathBuf]) -> Result<()> {
+    let input_a = opt.a.to_string_lossy();
+    let input_b = opt.b.to_string_lossy();
+    let mut codec_args = formats::filtered_codec_args(ext, &graph(opt.vertical, label_files), &formats::Encoding::default());
+    filters::to_complex(&mut codec_args);
+
+    for (i, entry) in entries.iter().enumerate() {
+        let vars = template::Vars { base, ext, index: i, count: entries.len(), input_stem, span: entry.span, label: entry.label.as_deref(), stable_id: None, scheme: template::NumberScheme::Digits };
+        let output_filename =
+            template::render(&opt.name_template, &vars).map_err(|err| XclipsError::Parse(format!("cannot use name template {}: {}", opt.name_template, err)))?;
+        let seek = entry.span.start.to_string();
+        let duration = entry.span.duration();
+        let time = format!("{}.{:03}", duration.as_secs(), duration.subsec_millis());
+        let status = ffmpeg()
+            .args(["-ss", &seek, "-i", &input_a, "-ss", &seek, "-i", &input_b, "-t", &time])
+            .args(&codec_args)
+            .arg(&output_filename)
+            .status()
+            .map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+        if !status.success() {
+            return Err(XclipsError::Encode { output: output_filename, failure: Failure::Ffmpeg });
+        }
+    }
+    Ok(())
+}
+
+fn remove_all(paths: &[PathBuf]) {
+    for path in paths {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// A filtergraph scaling the second input to match the first (in height, or
+/// with `vertical` in width), labelling each, and stacking them.
+fn graph(vertical: bool, label_files: &[PathBuf]) -> String {
+    let (size, stack) = if vertical { ("w=main_w:h=-2", "vstack") } else { ("w=-2:h=main_h", "hstack") };
+    let label = |i: usize| {
+        label_files.get(i).map_or(String::new(), |path: &PathBuf| {
+            format!(",drawtext=textfile={}:x=w/50:y=h/50:fontsize=h/20:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=8", filters::escape(&path.to_string_lossy()))
+        })
+    };
+    format!("[1:v][0:v]scale2ref={}[cmp_b][cmp_a];[cmp_a]setsar=1{}[cmp_a2];[cmp_b]setsar=1{}[cmp_b2];[cmp_a2][cmp_b2]{}", size, label(0), label(1), stack)
+}
@@ -0,0 +1,81 @@
+//! Per-project settings, read from a `.xclipsrc` file in the current
+//! directory or the nearest parent that has one (or from the file the
+//! `XCLIPS_CONFIG` environment variable names, if it's set; set it empty to
+//! read none). Each line is a `key = value` setting, and lines starting with
+//! `#` are comments:
+//!
+//! ```text
+//! # our logging tool writes MM.SS,fff
+//! timestamp-format = MM.SS,fff
+//! timestamp-regex = (?P<m>\d+)m(?P<s>\d+)s
+//! ```
+//!
+//! `timestamp-format` and `timestamp-regex` add custom timestamp notations
+//! (see `notation`) and may be given any number of times.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::{Result, XclipsError};
+use crate::notation::TimestampFormat;
+
+const FILE_NAME: &str = ".xclipsrc";
+
+/// Reads the project's settings, if it has any, and puts them into effect.
+pub fn load() -> Result<()> {
+    let Some(path) = find() else {
+        return Ok(());
+    };
+    let text = fs::read_to_string(&path).map_err(|err| XclipsError::io(format!("cannot read config file: {}", path.display()), err))?;
+    for format in parse(&text).map_err(|err| XclipsError::Parse(format!("{}:{}", path.display(), err)))? {
+        format.register();
+    }
+    Ok(())
+}
+
+fn find() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("XCLIPS_CONFIG") {
+        return Some(PathBuf::from(path)).filter(|p| !p.as_os_str().is_empty());
+    }
+    let dir = env::current_dir().ok()?;
+    dir.ancestors().map(|d| d.join(FILE_NAME)).find(|p| p.is_file())
+}
+
+/// Parses a config file into the timestamp notations it adds.
+fn parse(text: &str) -> std::result::Result<Vec<TimestampFormat>, String> {
+    let mut formats = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once('=').ok_or_else(|| format!("{}: expected KEY = VALUE", n + 1))?;
+        let value = value.trim();
+        let format = match key.trim() {
+            "timestamp-format" => TimestampFormat::pattern(value),
+            "timestamp-regex" => TimestampFormat::regex(value),
+            key => Err(format!("unknown setting {}", key)),
+        };
+        formats.push(format.map_err(|err| format!("{}: {}", n + 1, err))?);
+    }
+    Ok(formats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings() {
+        let formats = parse("# legacy\n\ntimestamp-format = MM.SS,fff\ntimestamp-regex=(?P<s>\\d+)s\n").unwrap();
+        assert_eq!(formats.len(), 2);
+    }
+
+    #[test]
+    fn bad_settings() {
+        assert_eq!(parse("a\n").unwrap_err(), "1: expected KEY = VALUE");
+        assert_eq!(parse("\ncolor = red\n").unwrap_err(), "2: unknown setting color");
+        assert!(parse("timestamp-format = MMM\n").unwrap_err().starts_with("1: "));
+    }
+}
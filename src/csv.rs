@@ -0,0 +1,87 @@
+//! CSV (and TSV) span lists, as spreadsheets and logging tools export them:
+//! a header row naming the columns, then a row per span.
+//!
+//! ```text
+//! Start,End,Note
+//! 00:01:02.5,00:01:05,"goal, by smith"
+//! ```
+//!
+//! Fields may be quoted, with `""` for a quote inside them, and quoted fields
+//! may span lines.
+
+/// Splits CSV text into rows of fields, with `delimiter` between fields.
+pub fn parse(text: &str, delimiter: char) -> Result<Vec<Vec<String>>, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            '\n' if quoted => {
+                line += 1;
+                field.push(c);
+            }
+            c if quoted => field.push(c),
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("line {}: unterminated quoted field", line));
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    // blank lines aren't rows
+    rows.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    Ok(rows)
+}
+
+/// Finds a column by its header (ignoring case) or its number, from 1.
+pub fn column(header: &[String], name: &str) -> Option<usize> {
+    if let Ok(n) = name.parse::<usize>() {
+        return (1..=header.len()).contains(&n).then(|| n - 1);
+    }
+    header.iter().position(|h| h.trim().eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let rows = parse("\u{feff}Start,End,Note\r\n1:02.5,1:05,\"goal, by \"\"smith\"\"\"\n\n3,4,\"two\nlines\"\n5,6,", ',').unwrap();
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1], ["1:02.5", "1:05", "goal, by \"smith\""]);
+        assert_eq!(rows[2][2], "two\nlines");
+        assert_eq!(rows[3], ["5", "6", ""]);
+        assert_eq!(parse("a\tb\n1\t2\n", '\t').unwrap()[1], ["1", "2"]);
+        assert_eq!(parse("a,\"b\n", ',').unwrap_err(), "line 2: unterminated quoted field");
+    }
+
+    #[test]
+    fn columns() {
+        let header: Vec<String> = ["Start", "End", "Note"].map(String::from).to_vec();
+        assert_eq!(column(&header, "end"), Some(1));
+        assert_eq!(column(&header, "3"), Some(2));
+        assert_eq!(column(&header, "4"), None);
+        assert_eq!(column(&header, "label"), None);
+    }
+}
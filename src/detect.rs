@@ -0,0 +1,72 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::error::{Result, XclipsError};
+use crate::ffmpeg;
+
+/// How `exec` hands detectors the audio: 16-bit mono WAV at this rate, which
+/// is plenty for the usual models (speech, applause, laughter).
+pub const EXEC_SAMPLE_RATE: u32 = 16000;
+
+/// Finds scene changes in the video of the file at `path`, returning their
+/// times in milliseconds. `threshold` is ffmpeg's scene score (0 to 1) above
+/// which a frame counts as a cut; around 0.4 works for most footage.
+///
+/// This decodes the whole video, so it's slow on long files.
+pub fn scenes(path: &Path, threshold: f64) -> Option<Vec<u64>> {
+    lazy_static! {
+        static ref PTS_RE: Regex = Regex::new(r"pts_time:\s*(\d+(?:\.\d+)?)").unwrap();
+    }
+    let output = ffmpeg()
+        .args(["-hide_banner", "-nostats", "-i"])
+        .arg(path)
+        .args(["-an", "-filter:v", &format!("select='gt(scene,{})',showinfo", threshold), "-f", "null", "-"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let log = String::from_utf8_lossy(&output.stderr);
+    Some(
+        log.lines()
+            .filter(|line| line.contains("Parsed_showinfo"))
+            .filter_map(|line| PTS_RE.captures(line))
+            .filter_map(|c| c[1].parse::<f64>().ok())
+            .map(|s| (s * 1000.0).round() as u64)
+            .collect(),
+    )
+}
+
+/// Runs an external detector, the shell command `cmd`, with the audio of the
+/// file at `path` decoded to WAV on its standard input, and returns what it
+/// writes to standard output (a span list, for the caller to parse).
+pub fn exec(cmd: &str, path: &Path) -> Result<String> {
+    let mut decoder = ffmpeg()
+        .args(["-v", "error", "-nostdin", "-i"])
+        .arg(path)
+        .args(["-vn", "-ac", "1", "-ar", &EXEC_SAMPLE_RATE.to_string(), "-c:a", "pcm_s16le", "-f", "wav", "-"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+    let audio = decoder.stdout.take().unwrap();
+    let detector = Command::new("sh")
+        .args(["-c", cmd])
+        .stdin(audio)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|_| XclipsError::Spawn(format!("detector: {}", cmd)));
+    // a detector that stops reading early leaves ffmpeg with nowhere to
+    // write, so only its own status says whether it worked
+    let decoded = decoder.wait().map_err(|err| XclipsError::io("failed to wait for ffmpeg", err))?;
+    let detector = detector?;
+    if !detector.status.success() {
+        return Err(XclipsError::Command(format!("detector returned non-zero exit status: {}", cmd)));
+    }
+    if !decoded.success() {
+        eprintln!("warning: ffmpeg didn't decode all of {} for the detector", path.display());
+    }
+    Ok(String::from_utf8_lossy(&detector.stdout).into_owned())
+}
@@ -0,0 +1,87 @@
+//! The error type for everything that stops xclips. Errors are passed up to
+//! `main`, which reports them (and whatever caused them) in one place, and
+//! exits with a status that says which kind of error it was:
+//!
+//! ```text
+//! 1   some of the work failed (each failure is reported as it happens),
+//!     or the command line couldn't be parsed
+//! 2   options or inputs that don't make sense together
+//! 3   a span, span list, template, or report that can't be understood
+//! 4   a file that can't be read or written
+//! 5   ffprobe couldn't tell something about a file
+//! 6   a program (ffmpeg, ffprobe, a span command) couldn't be started
+//! 7   ffmpeg failed to make an output
+//! 8   another command (a span command, a hook, sqlite3) failed
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::job::Failure;
+
+#[derive(Debug)]
+pub enum XclipsError {
+    /// Something given to xclips (a span, a span list, a template, a
+    /// report) that can't be understood.
+    Parse(String),
+    /// ffprobe couldn't tell what was needed about a file.
+    Probe { what: &'static str, path: PathBuf },
+    /// A program xclips relies on couldn't be started.
+    Spawn(String),
+    /// ffmpeg failed to produce an output.
+    Encode { output: String, failure: Failure },
+    /// An external command (a span command, a hook, sqlite3) failed.
+    Command(String),
+    Io { context: String, source: io::Error },
+    /// Options or inputs that don't make sense together.
+    Usage(String),
+    /// Some of the work failed; each failure has already been reported, and
+    /// this sums them up.
+    Failed(String),
+}
+
+pub type Result<T> = std::result::Result<T, XclipsError>;
+
+impl XclipsError {
+    pub fn io(context: impl Into<String>, source: io::Error) -> XclipsError {
+        XclipsError::Io { context: context.into(), source }
+    }
+
+    /// The status to exit with (see the table above).
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            XclipsError::Failed(_) => 1,
+            XclipsError::Usage(_) => 2,
+            XclipsError::Parse(_) => 3,
+            XclipsError::Io { .. } => 4,
+            XclipsError::Probe { .. } => 5,
+            XclipsError::Spawn(_) => 6,
+            XclipsError::Encode { .. } => 7,
+            XclipsError::Command(_) => 8,
+        }
+    }
+}
+
+impl fmt::Display for XclipsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            XclipsError::Parse(msg) | XclipsError::Command(msg) | XclipsError::Usage(msg) | XclipsError::Failed(msg) => f.write_str(msg),
+            XclipsError::Probe { what, path } => write!(f, "cannot determine {} of {}", what, path.display()),
+            XclipsError::Spawn(program) => write!(f, "failed to spawn {}", program),
+            XclipsError::Encode { output, .. } => write!(f, "cannot make {}", output),
+            XclipsError::Io { context, .. } => f.write_str(context),
+        }
+    }
+}
+
+impl Error for XclipsError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            XclipsError::Encode { failure, .. } => Some(failure),
+            XclipsError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
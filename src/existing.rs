@@ -0,0 +1,68 @@
+//! Comparing planned outputs with files already on disk.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::job::Job;
+use crate::{formats, probe};
+
+/// What's on disk where a planned clip will be written.
+pub enum State {
+    Missing,
+    Empty,
+    /// Duration of the existing file is off from what's planned.
+    DurationMismatch(Duration),
+    /// Size of the existing file is far from the estimate.
+    SizeMismatch(u64, u64),
+    Complete,
+}
+
+/// Estimates of what a clip's output should look like, from the input.
+pub struct Expectations {
+    /// Input bytes per millisecond of media, for estimating clip sizes;
+    /// only meaningful for stream copies.
+    bytes_per_ms: Option<f64>,
+}
+
+impl Expectations {
+    pub fn for_input(input: &Path, duration: Option<Duration>) -> Expectations {
+        let size = fs::metadata(input).map(|m| m.len()).ok();
+        let duration = duration.or_else(|| probe::duration(input));
+        let bytes_per_ms = match (size, duration) {
+            (Some(size), Some(d)) if !d.is_zero() => Some(size as f64 / d.as_millis() as f64),
+            _ => None,
+        };
+        Expectations { bytes_per_ms }
+    }
+
+    /// About how many bytes a stream copy of `length` of the input takes.
+    pub fn copy_size(&self, length: Duration) -> Option<u64> {
+        self.bytes_per_ms.map(|rate| (rate * length.as_millis() as f64) as u64)
+    }
+
+    pub fn check(&self, job: &Job) -> State {
+        let path = Path::new(&job.output);
+        let size = match fs::metadata(path) {
+            Err(_) => return State::Missing,
+            Ok(m) => m.len(),
+        };
+        if size == 0 {
+            return State::Empty;
+        }
+        let expected = job.span.duration();
+        // stream copies start at a keyframe, so allow some slack
+        let tolerance = (expected / 10).max(Duration::from_secs(2));
+        match probe::duration(path) {
+            Some(actual) if actual + tolerance < expected || actual > expected + tolerance => return State::DurationMismatch(actual),
+            None => return State::DurationMismatch(Duration::ZERO),
+            _ => (),
+        }
+        if let (Some(estimate), true) = (self.copy_size(expected), formats::is_copy(&job.args)) {
+            if size < estimate / 2 || size > estimate.saturating_mul(2) {
+                return State::SizeMismatch(size, estimate);
+            }
+        }
+        State::Complete
+    }
+}
@@ -0,0 +1,183 @@
+//! Filters applied to clips as they're cut, for the options that clean them
+//! up without users having to know ffmpeg's filter syntax.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ParseErr;
+
+/// Strength of `--denoise`.
+#[derive(Clone, Copy, Debug)]
+pub enum Denoise {
+    Light,
+    Medium,
+    Heavy,
+}
+
+impl FromStr for Denoise {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "light" => Ok(Denoise::Light),
+            "medium" => Ok(Denoise::Medium),
+            "heavy" => Ok(Denoise::Heavy),
+            _ => Err(ParseErr("expected light, medium, or heavy")),
+        }
+    }
+}
+
+impl Denoise {
+    pub fn filter(self) -> &'static str {
+        match self {
+            Denoise::Light => "hqdn3d=2:1.5:3:3",
+            Denoise::Medium => "hqdn3d=4:3:6:4.5",
+            // much slower, but keeps detail that hqdn3d would smear at this strength
+            Denoise::Heavy => "nlmeans=s=3:p=7:r=15",
+        }
+    }
+}
+
+/// A video filter for `--sharpen`, mild enough not to bring back the noise
+/// `--denoise` removed.
+pub const SHARPEN: &str = "unsharp=5:5:0.8:3:3:0";
+
+/// An audio filter removing silences longer than `min` from inside a clip,
+/// leaving a short pause in their place so speech doesn't run together.
+pub fn compact_silence(min: Duration) -> String {
+    format!("silenceremove=stop_periods=-1:stop_duration={}:stop_threshold=-50dB:stop_silence=0.25", seconds(min))
+}
+
+/// Output dimensions for `--fit`, written like `1920x1080`.
+#[derive(Clone, Copy, Debug)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Canvas {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = ParseErr("expected dimensions like 1920x1080");
+        let (width, height) = s.split_once('x').ok_or(err.clone())?;
+        let width: u32 = width.parse().map_err(|_| err.clone())?;
+        let height: u32 = height.parse().map_err(|_| err.clone())?;
+        if width == 0 || height == 0 || !width.is_multiple_of(2) || !height.is_multiple_of(2) {
+            // most encoders need even dimensions
+            return Err(ParseErr("expected nonzero even dimensions"));
+        }
+        Ok(Canvas { width, height })
+    }
+}
+
+/// What fills the canvas around a clip that doesn't match its shape.
+#[derive(Clone, Copy, Debug)]
+pub enum Background {
+    Black,
+    /// A blurred copy of the clip, scaled up to cover the canvas.
+    Blur,
+}
+
+impl FromStr for Background {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "black" => Ok(Background::Black),
+            "blur" => Ok(Background::Blur),
+            _ => Err(ParseErr("expected black or blur")),
+        }
+    }
+}
+
+/// A video filter scaling a clip to fit inside `canvas`, keeping its aspect
+/// ratio, and filling the rest with `background`.
+pub fn fit(canvas: Canvas, background: Background) -> String {
+    let (w, h) = (canvas.width, canvas.height);
+    match background {
+        Background::Black => format!("scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2:black,setsar=1"),
+        Background::Blur => format!(
+            // labels that won't clash with the filters of animated outputs
+            "split[fit_a][fit_b];[fit_a]scale={w}:{h}:force_original_aspect_ratio=increase,crop={w}:{h},boxblur=20[fit_bg];\
+             [fit_b]scale={w}:{h}:force_original_aspect_ratio=decrease[fit_fg];[fit_bg][fit_fg]overlay=(W-w)/2:(H-h)/2,setsar=1"
+        ),
+    }
+}
+
+/// Where `--pip` puts the overlay.
+#[derive(Clone, Copy, Debug)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl FromStr for Corner {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tl" => Ok(Corner::TopLeft),
+            "tr" => Ok(Corner::TopRight),
+            "bl" => Ok(Corner::BottomLeft),
+            "br" => Ok(Corner::BottomRight),
+            _ => Err(ParseErr("expected tl, tr, bl, or br")),
+        }
+    }
+}
+
+/// A filter overlaying the second input on the first, scaled to `scale`
+/// times the first's width. Only works in a complex filtergraph; see
+/// `to_complex`.
+pub fn pip(corner: Corner, scale: f64) -> String {
+    const MARGIN: &str = "main_w/40";
+    let (x, y) = match corner {
+        Corner::TopLeft => (MARGIN.to_string(), MARGIN.to_string()),
+        Corner::TopRight => (format!("main_w-overlay_w-{}", MARGIN), MARGIN.to_string()),
+        Corner::BottomLeft => (MARGIN.to_string(), format!("main_h-overlay_h-{}", MARGIN)),
+        Corner::BottomRight => (format!("main_w-overlay_w-{}", MARGIN), format!("main_h-overlay_h-{}", MARGIN)),
+    };
+    format!("[1:v][0:v]scale2ref=w=trunc(main_w*{}/2)*2:h=trunc(ow/mdar/2)*2[pip_fg][pip_bg];[pip_bg][pip_fg]overlay={}:{}", scale, x, y)
+}
+
+/// Turns a `-vf GRAPH` in codec arguments into a complex filtergraph, which
+/// can read more than one input, keeping the first input's audio.
+pub fn to_complex(args: &mut Vec<String>) {
+    if let Some(i) = args.iter().position(|a| a == "-vf") {
+        args[i] = "-filter_complex".to_string();
+        args[i + 1].push_str("[xclips_v]");
+        args.extend(["-map", "[xclips_v]", "-map", "0:a?"].map(String::from));
+    }
+}
+
+/// A video filter holding the last frame of a clip for `hold` (with the text
+/// in `text_file` over it, if given), where the clip is `length` long.
+pub fn endcard(length: Duration, hold: Duration, text_file: Option<&str>) -> String {
+    let mut filter = format!("tpad=stop_mode=clone:stop_duration={}", seconds(hold));
+    if let Some(path) = text_file {
+        filter.push_str(&format!(
+            ",drawtext=textfile={}:enable=gte(t\\,{}):x=(w-text_w)/2:y=(h-text_h)/2:fontsize=h/12:fontcolor=white:box=1:boxcolor=black@0.5:boxborderw=16",
+            escape(path),
+            seconds(length)
+        ));
+    }
+    filter
+}
+
+/// Escapes a value (like a path) for use as a filter option.
+pub fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        if "\\':,;[]".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn seconds(d: Duration) -> String {
+    format!("{}.{:03}", d.as_secs(), d.subsec_millis())
+}
@@ -0,0 +1,513 @@
+//! What each kind of output container needs from ffmpeg.
+//!
+//! Most outputs are stream copies of the input, but some containers can't
+//! hold the input's streams as they are: animations (GIF, WebP) have to be
+//! re-encoded from the video, and audio formats need just the audio.
+//! `--mezzanine` re-encodes everything to an intermediate codec instead, and
+//! `--reencode` re-encodes with ordinary delivery codecs, for clips that start
+//! exactly where asked rather than at the keyframe before. An input with
+//! nothing but audio is copied into audio formats that can hold its codec.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::ParseErr;
+
+const AUDIO_EXTS: &[&str] = &["mp3", "m4a", "aac", "wav", "flac", "ogg", "opus"];
+
+/// Codec arguments for an output with the given extension, with the
+/// default animation settings for GIF and WebP.
+pub fn codec_args(ext: &str) -> Vec<&'static str> {
+    match ext.to_ascii_lowercase().as_str() {
+        "gif" => vec!["-an", "-vf", "fps=15,scale='min(480,iw)':-1:flags=lanczos,split[a][b];[a]palettegen[p];[b][p]paletteuse", "-loop", "0"],
+        "webp" => vec!["-an", "-vf", "fps=15,scale='min(480,iw)':-1:flags=lanczos", "-c:v", "libwebp", "-loop", "0"],
+        "mp3" => vec!["-vn", "-c:a", "libmp3lame", "-q:a", "2"],
+        "m4a" | "aac" => vec!["-vn", "-c:a", "aac", "-b:a", "192k"],
+        "wav" => vec!["-vn", "-c:a", "pcm_s16le"],
+        "flac" => vec!["-vn", "-c:a", "flac"],
+        "ogg" => vec!["-vn", "-c:a", "libvorbis", "-q:a", "5"],
+        "opus" => vec!["-vn", "-c:a", "libopus", "-b:a", "128k"],
+        _ => vec!["-c", "copy"],
+    }
+}
+
+/// Codec settings for re-encoded video. Unset fields fall back to defaults
+/// for the container.
+#[derive(Clone, Debug, Default)]
+pub struct Encoding {
+    pub vcodec: Option<String>,
+    pub acodec: Option<String>,
+    pub crf: Option<u32>,
+    pub preset: Option<String>,
+    /// Target video bitrate in bits per second (from `--auto-fit`), instead
+    /// of constant quality.
+    pub bitrate: Option<u64>,
+    pub animation: Animation,
+}
+
+/// How GIF and WebP outputs are made.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animation {
+    pub fps: f64,
+    /// Width to scale wider videos down to, keeping the aspect ratio.
+    pub max_width: u32,
+    /// The palette's dithering algorithm (for GIFs), as ffmpeg's paletteuse
+    /// names it; its default (sierra2_4a) if unset.
+    pub dither: Option<String>,
+    /// How many times it plays; 0 for forever.
+    pub plays: u32,
+}
+
+impl Default for Animation {
+    fn default() -> Animation {
+        Animation { fps: 15.0, max_width: 480, dither: None, plays: 0 }
+    }
+}
+
+/// The dithering algorithms ffmpeg's paletteuse offers.
+pub const DITHERS: &[&str] = &["bayer", "heckbert", "floyd_steinberg", "sierra2", "sierra2_4a", "sierra3", "burkes", "atkinson", "none"];
+
+impl Animation {
+    /// Codec arguments for an animation with the given extension, or `None`
+    /// if it isn't one.
+    pub fn args(&self, ext: &str) -> Option<Vec<String>> {
+        let scale = format!("fps={},scale='min({},iw)':-1:flags=lanczos", self.fps, self.max_width);
+        match ext.to_ascii_lowercase().as_str() {
+            "gif" => {
+                let dither = self.dither.as_ref().map_or(String::new(), |d| format!("=dither={}", d));
+                // the gif muxer counts repeats after the first play, with -1
+                // for none
+                let repeats = match self.plays {
+                    0 => 0,
+                    1 => -1,
+                    n => n as i64 - 1,
+                };
+                let graph = format!("{},split[a][b];[a]palettegen[p];[b][p]paletteuse{}", scale, dither);
+                Some(["-an", "-vf", &graph, "-loop", &repeats.to_string()].map(String::from).to_vec())
+            }
+            "webp" => Some(["-an", "-vf", &scale, "-c:v", "libwebp", "-loop", &self.plays.to_string()].map(String::from).to_vec()),
+            _ => None,
+        }
+    }
+
+    /// A step down in frame rate and width, for `--max-size`, or `None`
+    /// once it gets too choppy or small to bother.
+    pub fn smaller(&self) -> Option<Animation> {
+        let fps = (self.fps * 0.8).max(5.0).round();
+        let max_width = (self.max_width as f64 * 0.8).round() as u32 / 2 * 2;
+        if max_width < 120 {
+            return None;
+        }
+        Some(Animation { fps, max_width, ..self.clone() })
+    }
+}
+
+/// Codec arguments for an output with the given extension, with the
+/// animation settings from `encoding`.
+pub fn output_args(ext: &str, encoding: &Encoding) -> Vec<String> {
+    encoding.animation.args(ext).unwrap_or_else(|| codec_args(ext).into_iter().map(String::from).collect())
+}
+
+/// Audio bitrate of clips re-encoded to fit a size budget, in bits per
+/// second.
+pub const FIT_AUDIO_BITRATE: u64 = 128_000;
+
+/// Everything that decides whether an output's streams are copied or
+/// re-encoded, and how.
+pub struct CodecChoice<'a> {
+    pub ext: &'a str,
+    /// Filters the video goes through (empty for none), which rule out
+    /// copying it.
+    pub video_filter: &'a str,
+    /// Codec arguments for a mezzanine output, which win over the rest.
+    pub mezzanine: Option<&'a [String]>,
+    /// Re-encode even without filters (`--reencode`, or a clip over its
+    /// size budget with `--auto-fit`).
+    pub reencode: bool,
+    pub encoding: &'a Encoding,
+    /// The audio codec of an input with no video (cover art aside), or `""`
+    /// if ffprobe can't tell; `None` for any other input.
+    pub audio_input: Option<&'a str>,
+}
+
+impl CodecChoice<'_> {
+    pub fn args(&self) -> Vec<String> {
+        match self.mezzanine {
+            Some(mezzanine) => {
+                let mut args = mezzanine.to_vec();
+                if !self.video_filter.is_empty() {
+                    args.extend(["-vf".to_string(), self.video_filter.to_string()]);
+                }
+                args
+            }
+            None if !self.video_filter.is_empty() && keeps_streams(self.ext).0 => filtered_codec_args(self.ext, self.video_filter, self.encoding),
+            None => match self.audio_input {
+                Some(codec) => audio_input_args(self.ext, codec, self.reencode, self.encoding),
+                None if self.reencode => reencoded_codec_args(self.ext, self.encoding),
+                None => output_args(self.ext, self.encoding),
+            },
+        }
+    }
+
+    /// Whether the output is a plain stream copy, whose size follows the
+    /// input's bitrate.
+    pub fn copies(&self) -> bool {
+        is_copy(&self.args())
+    }
+}
+
+/// Whether ffmpeg arguments copy every stream.
+pub fn is_copy(args: &[String]) -> bool {
+    args.windows(2).any(|w| w[0] == "-c" && w[1] == "copy")
+}
+
+/// The video bitrate that brings a clip of `duration` in under `budget`
+/// bytes, leaving room for the audio and the container, or `None` if that
+/// leaves too little for watchable video.
+pub fn fit_bitrate(budget: u64, duration: Duration) -> Option<u64> {
+    const MIN_VIDEO_BITRATE: u64 = 100_000;
+    let ms = duration.as_millis() as u64;
+    if ms == 0 {
+        return None;
+    }
+    // about 3% of a file goes to the container
+    let total = budget * 8 * 97 / 100 * 1000 / ms;
+    total.checked_sub(FIT_AUDIO_BITRATE).filter(|&video| video >= MIN_VIDEO_BITRATE)
+}
+
+/// Codec arguments for an output with the given extension that re-encode
+/// everything, where it would otherwise be a stream copy.
+pub fn reencoded_codec_args(ext: &str, encoding: &Encoding) -> Vec<String> {
+    let args = output_args(ext, encoding);
+    if is_copy(&args) {
+        let acodec = if ext.eq_ignore_ascii_case("webm") { "libopus" } else { "aac" };
+        reencode_args(ext, encoding, acodec)
+    } else {
+        args
+    }
+}
+
+/// Whether an audio format with the given extension can hold audio in
+/// `codec`, as ffprobe names it.
+fn holds_audio(ext: &str, codec: &str) -> bool {
+    match ext.to_ascii_lowercase().as_str() {
+        "mp3" => codec == "mp3",
+        "m4a" => codec == "aac" || codec == "alac",
+        "aac" => codec == "aac",
+        "wav" => codec.starts_with("pcm_"),
+        "flac" => codec == "flac",
+        "ogg" => matches!(codec, "vorbis" | "opus" | "flac"),
+        "opus" => codec == "opus",
+        _ => false,
+    }
+}
+
+/// Codec arguments for an output with the given extension of an input with
+/// only audio, in `codec`. The audio is copied where the output can hold it
+/// (any container that isn't an audio format is assumed to), and cover art
+/// is left behind.
+fn audio_input_args(ext: &str, codec: &str, reencode: bool, encoding: &Encoding) -> Vec<String> {
+    let audio_format = keeps_streams(ext) == (false, true);
+    if !reencode && (!audio_format || holds_audio(ext, codec)) {
+        return ["-vn", "-c", "copy"].map(String::from).to_vec();
+    }
+    if audio_format {
+        return codec_args(ext).into_iter().map(String::from).collect();
+    }
+    let acodec = encoding.acodec.as_deref().unwrap_or(if ext.eq_ignore_ascii_case("webm") { "libopus" } else { "aac" });
+    let mut args: Vec<String> = ["-vn", "-c:a", acodec].map(String::from).to_vec();
+    if encoding.bitrate.is_some() && acodec != "copy" {
+        args.extend(["-b:a".into(), FIT_AUDIO_BITRATE.to_string()]);
+    }
+    args
+}
+
+/// Codec arguments for an output with the given extension whose video goes
+/// through `video_filter`, which rules out copying the video stream.
+pub fn filtered_codec_args(ext: &str, video_filter: &str, encoding: &Encoding) -> Vec<String> {
+    let mut args = output_args(ext, encoding);
+    if is_copy(&args) {
+        args = reencode_args(ext, encoding, "copy");
+    }
+    match args.iter().position(|a| a == "-vf") {
+        Some(i) => args[i + 1] = format!("{},{}", video_filter, args[i + 1]),
+        None => args.extend(["-vf".to_string(), video_filter.to_string()]),
+    }
+    args
+}
+
+/// Codec arguments re-encoding the video of an output that would otherwise
+/// be a stream copy, with `acodec` for the audio unless `encoding` names one.
+/// The default codecs get their usual quality settings; any others only get
+/// what's asked for.
+fn reencode_args(ext: &str, encoding: &Encoding, acodec: &str) -> Vec<String> {
+    let (default, crf) = if ext.eq_ignore_ascii_case("webm") { ("libvpx-vp9", 32) } else { ("libx264", 18) };
+    let vcodec = encoding.vcodec.as_deref().unwrap_or(default);
+    let mut args: Vec<String> = vec!["-c:v".into(), vcodec.into()];
+    if let Some(bitrate) = encoding.bitrate {
+        args.extend(["-b:v".into(), bitrate.to_string(), "-maxrate".into(), bitrate.to_string(), "-bufsize".into(), (2 * bitrate).to_string()]);
+    } else {
+        if let Some(crf) = encoding.crf.or((vcodec == default).then_some(crf)) {
+            args.extend(["-crf".into(), crf.to_string()]);
+        }
+        if vcodec == "libvpx-vp9" {
+            // constant quality, rather than the default bitrate
+            args.extend(["-b:v".into(), "0".into()]);
+        }
+    }
+    if let Some(preset) = encoding.preset.as_deref().or((vcodec == "libx264").then_some("medium")) {
+        args.extend(["-preset".into(), preset.into()]);
+    }
+    let acodec = encoding.acodec.as_deref().unwrap_or(acodec);
+    args.extend(["-c:a".into(), acodec.into()]);
+    if encoding.bitrate.is_some() && acodec != "copy" {
+        args.extend(["-b:a".into(), FIT_AUDIO_BITRATE.to_string()]);
+    }
+    args
+}
+
+/// Whether an output with the given extension keeps the video and the audio
+/// of the input, respectively.
+pub fn keeps_streams(ext: &str) -> (bool, bool) {
+    let ext = ext.to_ascii_lowercase();
+    if ext == "gif" || ext == "webp" {
+        (true, false)
+    } else if AUDIO_EXTS.contains(&ext.as_str()) {
+        (false, true)
+    } else {
+        (true, true)
+    }
+}
+
+/// Whether an output with the given extension can carry a cover picture for
+/// `--poster`.
+pub fn embeds_poster(ext: &str) -> bool {
+    matches!(ext.to_ascii_lowercase().as_str(), "mp4" | "m4v" | "mov")
+}
+
+/// Whether an output with the given extension can have an edit list, which
+/// players use to skip the start of a clip's data (see `--copy-lead-in`).
+pub fn has_edit_lists(ext: &str) -> bool {
+    matches!(ext.to_ascii_lowercase().as_str(), "mp4" | "m4v" | "mov" | "m4a")
+}
+
+/// Whether an output with the given extension is written by ffmpeg's
+/// QuickTime muxer, which takes `-movflags`.
+pub fn takes_movflags(ext: &str) -> bool {
+    matches!(ext.to_ascii_lowercase().as_str(), "mp4" | "m4v" | "mov" | "m4a")
+}
+
+/// Whether players show the tracks of an output with the given extension
+/// under their handler name rather than their title, as QuickTime does.
+pub fn titles_by_handler(ext: &str) -> bool {
+    matches!(ext.to_ascii_lowercase().as_str(), "mp4" | "m4v" | "mov" | "m4a")
+}
+
+/// Codecs (as ffprobe names them) that containers can hold as stream copies.
+const MP4_CODECS: &[&str] = &["h264", "hevc", "av1", "mpeg4", "mpeg2video", "mjpeg", "aac", "mp3", "ac3", "eac3", "alac", "flac", "opus"];
+const MOV_CODECS: &[&str] = &["prores", "dnxhd", "qtrle", "pcm_s16le", "pcm_s16be", "pcm_s24le", "pcm_s24be", "pcm_f32le"];
+const WEBM_CODECS: &[&str] = &["vp8", "vp9", "av1", "opus", "vorbis"];
+const TS_CODECS: &[&str] = &["h264", "hevc", "mpeg1video", "mpeg2video", "aac", "mp2", "mp3", "ac3", "eac3", "opus"];
+
+/// A container that holds any codec, for clips that can't be copied into
+/// the one asked for.
+pub const ANY_CODEC_EXT: &str = "mkv";
+
+/// The first of `codecs` (the input's video and audio codecs, as ffprobe
+/// names them) that an output with the given extension can't hold without
+/// re-encoding, if any. Containers this doesn't know are taken to hold
+/// anything.
+pub fn unsupported_codec<'a>(ext: &str, codecs: &'a [String]) -> Option<&'a str> {
+    let ext = ext.to_ascii_lowercase();
+    let holds = |codec: &str| match ext.as_str() {
+        "mp4" | "m4v" => MP4_CODECS.contains(&codec),
+        "mov" => MP4_CODECS.contains(&codec) || MOV_CODECS.contains(&codec),
+        "webm" => WEBM_CODECS.contains(&codec),
+        "ts" | "m2ts" => TS_CODECS.contains(&codec),
+        _ => true,
+    };
+    codecs.iter().map(String::as_str).find(|codec| !holds(codec))
+}
+
+/// What to do with a stream-copied clip whose container can't hold the
+/// input's codecs (`--incompatible`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Incompatible {
+    /// Copy it into an `ANY_CODEC_EXT` file instead.
+    Remux,
+    Reencode,
+    Fail,
+}
+
+impl FromStr for Incompatible {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "remux" => Ok(Incompatible::Remux),
+            "reencode" => Ok(Incompatible::Reencode),
+            "fail" => Ok(Incompatible::Fail),
+            _ => Err(ParseErr("expected remux, reencode, or fail")),
+        }
+    }
+}
+
+/// What to do with a stream-copied clip when ffmpeg fails to copy it, or
+/// the copy is empty or broken (`--copy-fallback`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CopyFallback {
+    /// Cut it again, re-encoding it.
+    Reencode,
+    Fail,
+}
+
+impl FromStr for CopyFallback {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "reencode" => Ok(CopyFallback::Reencode),
+            "fail" => Ok(CopyFallback::Fail),
+            _ => Err(ParseErr("expected reencode or fail")),
+        }
+    }
+}
+
+/// Edit-friendly intermediate codecs for `--mezzanine`: every frame is a
+/// keyframe, so editors can scrub them smoothly.
+#[derive(Clone, Copy, Debug)]
+pub enum Mezzanine {
+    ProRes,
+    Dnxhd,
+}
+
+/// Mezzanine clips are QuickTime files, which both codecs are at home in.
+pub const MEZZANINE_EXT: &str = "mov";
+
+impl FromStr for Mezzanine {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "prores" => Ok(Mezzanine::ProRes),
+            "dnxhd" | "dnxhr" => Ok(Mezzanine::Dnxhd),
+            _ => Err(ParseErr("expected prores or dnxhd")),
+        }
+    }
+}
+
+impl Mezzanine {
+    /// Codec arguments, tagging the video with the input's color properties
+    /// (from `probe::color`) so editors don't have to guess them.
+    pub fn codec_args(self, color: &[Option<String>; 3]) -> Vec<String> {
+        let video: &[&str] = match self {
+            // ProRes 422 HQ
+            Mezzanine::ProRes => &["-c:v", "prores_ks", "-profile:v", "3", "-vendor", "apl0", "-pix_fmt", "yuv422p10le"],
+            // DNxHR HQ, which unlike DNxHD works at any resolution
+            Mezzanine::Dnxhd => &["-c:v", "dnxhd", "-profile:v", "dnxhr_hq", "-pix_fmt", "yuv422p"],
+        };
+        let mut args: Vec<String> = video.iter().map(|a| a.to_string()).collect();
+        for (flag, value) in ["-color_primaries", "-color_trc", "-colorspace"].into_iter().zip(color) {
+            if let Some(value) = value {
+                args.extend([flag.to_string(), value.clone()]);
+            }
+        }
+        args.extend(["-c:a", "pcm_s16le"].map(String::from));
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choice<'a>(ext: &'a str, video_filter: &'a str, reencode: bool, encoding: &'a Encoding) -> CodecChoice<'a> {
+        CodecChoice { ext, video_filter, mezzanine: None, reencode, encoding, audio_input: None }
+    }
+
+    #[test]
+    fn copies_unless_something_needs_encoding() {
+        let encoding = Encoding::default();
+        assert!(choice("mp4", "", false, &encoding).copies());
+        assert!(!choice("mp4", "", true, &encoding).copies());
+        assert!(!choice("mp4", "scale=640:-1", false, &encoding).copies());
+        assert!(!choice("mp3", "", false, &encoding).copies());
+        let mezzanine = ["-c:v".to_string(), "prores_ks".to_string()];
+        assert!(!CodecChoice { mezzanine: Some(&mezzanine), ..choice("mov", "", false, &encoding) }.copies());
+    }
+
+    #[test]
+    fn audio_inputs_copy_where_they_fit() {
+        let encoding = Encoding::default();
+        let audio = |ext, codec, reencode| CodecChoice { audio_input: Some(codec), ..choice(ext, "", reencode, &encoding) }.args().join(" ");
+        assert_eq!(audio("mp3", "mp3", false), "-vn -c copy");
+        assert_eq!(audio("m4a", "aac", false), "-vn -c copy");
+        assert_eq!(audio("mp3", "flac", false), "-vn -c:a libmp3lame -q:a 2");
+        assert_eq!(audio("flac", "", false), "-vn -c:a flac");
+        assert_eq!(audio("mp3", "mp3", true), "-vn -c:a libmp3lame -q:a 2");
+        assert_eq!(audio("mkv", "flac", false), "-vn -c copy");
+        assert_eq!(audio("mp4", "mp3", true), "-vn -c:a aac");
+    }
+
+    #[test]
+    fn containers_hold_some_codecs() {
+        let codecs = |list: &[&str]| list.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+        assert_eq!(unsupported_codec("mp4", &codecs(&["h264", "aac"])), None);
+        assert_eq!(unsupported_codec("MP4", &codecs(&["vp9", "opus"])), Some("vp9"));
+        assert_eq!(unsupported_codec("mp4", &codecs(&["h264", "pcm_s16le"])), Some("pcm_s16le"));
+        assert_eq!(unsupported_codec("mov", &codecs(&["h264", "pcm_s16le"])), None);
+        assert_eq!(unsupported_codec("webm", &codecs(&["h264"])), Some("h264"));
+        assert_eq!(unsupported_codec("mkv", &codecs(&["vp9", "pcm_s16le"])), None);
+    }
+
+    #[test]
+    fn target_bitrate_replaces_quality() {
+        let encoding = Encoding { bitrate: Some(2_000_000), ..Encoding::default() };
+        let args = choice("mp4", "", true, &encoding).args();
+        assert!(args.windows(2).any(|w| w == ["-b:v", "2000000"]));
+        assert!(args.windows(2).any(|w| w == ["-b:a", "128000"]));
+        assert!(!args.contains(&"-crf".to_string()));
+        let args = choice("webm", "", true, &encoding).args();
+        assert!(!args.windows(2).any(|w| w == ["-b:v", "0"]));
+    }
+
+    #[test]
+    fn fits_bitrate_to_budget() {
+        // 10 MB over 40 seconds is 2 Mb/s, less the container and the audio
+        assert_eq!(fit_bitrate(10_000_000, Duration::from_secs(40)), Some(1_812_000));
+        assert_eq!(fit_bitrate(1_000_000, Duration::from_secs(600)), None);
+        assert_eq!(fit_bitrate(1_000_000, Duration::ZERO), None);
+    }
+
+    #[test]
+    fn default_animation_matches_codec_args() {
+        for ext in ["gif", "webp"] {
+            let defaults: Vec<String> = codec_args(ext).into_iter().map(String::from).collect();
+            assert_eq!(Animation::default().args(ext), Some(defaults));
+        }
+        assert_eq!(Animation::default().args("mp4"), None);
+    }
+
+    #[test]
+    fn animation_settings() {
+        let animation = Animation { fps: 10.0, max_width: 320, dither: Some("bayer".to_string()), plays: 1 };
+        let args = animation.args("gif").unwrap();
+        assert_eq!(args[2], "fps=10,scale='min(320,iw)':-1:flags=lanczos,split[a][b];[a]palettegen[p];[b][p]paletteuse=dither=bayer");
+        assert_eq!(args[4], "-1");
+        assert_eq!(Animation { plays: 3, ..animation.clone() }.args("gif").unwrap()[4], "2");
+        assert_eq!(Animation { plays: 3, ..animation }.args("webp").unwrap()[6], "3");
+    }
+
+    #[test]
+    fn shrinking_stops() {
+        let mut animation = Animation::default();
+        let mut steps = 0;
+        while let Some(smaller) = animation.smaller() {
+            assert!(smaller.max_width < animation.max_width && smaller.fps <= animation.fps);
+            animation = smaller;
+            steps += 1;
+        }
+        assert!(steps > 0 && steps < 10);
+        assert!(animation.fps >= 5.0);
+    }
+}
@@ -0,0 +1,189 @@
+//! Positions given in frames rather than time, for frame-accurate review:
+//! `f1500` is frame 1500 (counting from 0), and `12:30.5f` is 5 frames past
+//! 12:30 (the digits after the point counting frames, as in a timecode).
+//! Frames are turned into time at the input's frame rate, as ffprobe reports
+//! it, or at the one given with `--frame-rate`.
+//!
+//! A frame becomes the millisecond it starts in, so a span from `f1500` to
+//! `f2250` has frame 1500 in it but not frame 2250.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{probe, ParseErr, Timestamp};
+
+thread_local! {
+    /// Where frames are counted from in what this thread is parsing, for
+    /// as long as `counting_in` says.
+    static RATE: RefCell<Rate> = const { RefCell::new(Rate::Unknown) };
+}
+
+/// A frame rate, as a fraction of frames per second.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct FrameRate {
+    frames: u64,
+    per: u64,
+}
+
+impl FromStr for FrameRate {
+    type Err = ParseErr;
+
+    /// Parses a rate written as a number (`24`, `29.97`) or as a fraction
+    /// the way ffprobe writes it (`30000/1001`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (frames, per) = match s.split_once('/') {
+            Some((frames, per)) => (frames.parse().map_err(|_| ParseErr("not a valid frame rate"))?, per.parse().map_err(|_| ParseErr("not a valid frame rate"))?),
+            None => {
+                let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+                if fraction.len() > 6 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+                    return Err(ParseErr("not a valid frame rate"));
+                }
+                let per = 10u64.pow(fraction.len() as u32);
+                let whole: u64 = whole.parse().map_err(|_| ParseErr("not a valid frame rate"))?;
+                (whole * per + fraction.parse::<u64>().unwrap_or(0), per)
+            }
+        };
+        if frames == 0 || per == 0 {
+            return Err(ParseErr("frame rate must be more than zero"));
+        }
+        Ok(FrameRate { frames, per })
+    }
+}
+
+impl fmt::Display for FrameRate {
+    /// Writes the rate the way ffmpeg takes it, as a whole number or a
+    /// fraction.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.per {
+            1 => write!(f, "{}", self.frames),
+            per => write!(f, "{}/{}", self.frames, per),
+        }
+    }
+}
+
+impl FrameRate {
+    /// When frame `n` starts, to the millisecond.
+    pub fn start_of(&self, n: u64) -> Option<Timestamp> {
+        Some(Timestamp::from_millis(n.checked_mul(1000 * self.per)? / self.frames))
+    }
+
+    /// The first frame that starts at or after `at`, so that a span from
+    /// `start_of(n)` has frame `n` in it.
+    pub fn frame_at(&self, at: Timestamp) -> u64 {
+        (at.as_millis() * self.frames).div_ceil(1000 * self.per)
+    }
+
+    /// How many frames start within each second, at most.
+    fn per_second(&self) -> u64 {
+        self.frames.div_ceil(self.per)
+    }
+}
+
+/// Where frames are counted from.
+enum Rate {
+    Unknown,
+    Given(FrameRate),
+    /// The rate of this input, once it's needed.
+    Input(PathBuf),
+    Probed(Option<FrameRate>),
+}
+
+/// Runs `read`, counting frames in the spans it parses at `given`, or else
+/// at the rate of `input` (probed the first time a frame position comes up).
+pub fn counting_in<T>(given: Option<FrameRate>, input: &Path, read: impl FnOnce() -> T) -> T {
+    let rate = match given {
+        Some(rate) => Rate::Given(rate),
+        None => Rate::Input(input.to_path_buf()),
+    };
+    let outer = RATE.with(|r| r.replace(rate));
+    let result = read();
+    RATE.with(|r| r.replace(outer));
+    result
+}
+
+fn rate() -> Option<FrameRate> {
+    RATE.with(|rate| {
+        let mut rate = rate.borrow_mut();
+        if let Rate::Input(ref input) = *rate {
+            let probed = probe::video_format(input).and_then(|(_, _, fps)| fps.parse().ok());
+            if probed.is_none() {
+                eprintln!("warning: cannot find the frame rate of {}, to count frames in; give one with --frame-rate", input.display());
+            }
+            *rate = Rate::Probed(probed);
+        }
+        match *rate {
+            Rate::Given(rate) | Rate::Probed(Some(rate)) => Some(rate),
+            _ => None,
+        }
+    })
+}
+
+/// Parses `s` as a frame position, if it's written as one.
+pub(crate) fn parse(s: &str) -> Option<Result<Timestamp, ParseErr>> {
+    lazy_static! {
+        static ref RE_FRAME: Regex = Regex::new(r"^f(\d+)$").unwrap();
+        static ref RE_TIME_FRAMES: Regex = Regex::new(r"^(\d[\d:]*)\.(\d+)f$").unwrap();
+    }
+    if let Some(captures) = RE_FRAME.captures(s) {
+        let Some(rate) = rate() else {
+            return Some(Err(ParseErr("frame positions need a frame rate")));
+        };
+        return Some(captures[1].parse().ok().and_then(|n| rate.start_of(n)).ok_or(ParseErr("frame number is too large")));
+    }
+    let captures = RE_TIME_FRAMES.captures(s)?;
+    let Some(rate) = rate() else {
+        return Some(Err(ParseErr("frame positions need a frame rate")));
+    };
+    let time: Timestamp = match captures[1].parse() {
+        Ok(time) => time,
+        Err(err) => return Some(Err(err)),
+    };
+    let frame: u64 = match captures[2].parse() {
+        Ok(frame) if frame < rate.per_second() => frame,
+        _ => return Some(Err(ParseErr("more frames than there are in a second"))),
+    };
+    let offset = rate.start_of(frame).unwrap();
+    Some(Ok(Timestamp::from_millis(time.as_millis() + offset.as_millis())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frame_rates() {
+        assert_eq!("24".parse(), Ok(FrameRate { frames: 24, per: 1 }));
+        assert_eq!("29.97".parse(), Ok(FrameRate { frames: 2997, per: 100 }));
+        assert_eq!("30000/1001".parse(), Ok(FrameRate { frames: 30000, per: 1001 }));
+        assert!("0/0".parse::<FrameRate>().is_err());
+        assert!("fast".parse::<FrameRate>().is_err());
+        assert_eq!("30000/1001".parse::<FrameRate>().unwrap().to_string(), "30000/1001");
+        assert_eq!("24".parse::<FrameRate>().unwrap().to_string(), "24");
+    }
+
+    #[test]
+    fn frames() {
+        let ms = |s: &str| s.parse::<Timestamp>().map(|t| t.as_millis());
+        counting_in(Some("30000/1001".parse().unwrap()), Path::new("in.mp4"), || {
+            assert_eq!(ms("f1500"), Ok(50050));
+            assert_eq!(ms("f1"), Ok(33));
+            assert_eq!(ms("12:30.5f"), Ok(750166));
+            assert!(ms("12:30.30f").is_err());
+        });
+        counting_in(Some("25".parse().unwrap()), Path::new("in.mp4"), || {
+            assert_eq!(ms("1:00:00.24f"), Ok(3600960));
+            assert_eq!(ms("12.5"), Ok(12500));
+        });
+        assert!(ms("f1").is_err());
+        let ntsc: FrameRate = "30000/1001".parse().unwrap();
+        for n in [0, 1, 1500, 107892] {
+            assert_eq!(ntsc.frame_at(ntsc.start_of(n).unwrap()), n);
+        }
+        assert_eq!(ntsc.frame_at(Timestamp::from_millis(34)), 2);
+    }
+}
@@ -0,0 +1,109 @@
+//! `--glob`: finding input files by a shell-style pattern, for shells (or
+//! scripts) that don't expand it, or lists too long for the command line.
+//!
+//! `*` matches any run of characters within a path component, `?` any one
+//! character, and `[...]` any one of a set (`[abc]`, `[0-9]`, or not one of
+//! them, `[!abc]`). Hidden files only match a component that starts with `.`.
+
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// The files matching `pattern`, sorted.
+pub fn expand(pattern: &str) -> Result<Vec<PathBuf>, String> {
+    let mut paths = vec![PathBuf::new()];
+    for component in Path::new(pattern).components() {
+        let Component::Normal(part) = component else {
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        };
+        let part = part.to_string_lossy();
+        if !part.contains(['*', '?', '[']) {
+            for path in &mut paths {
+                path.push(&*part);
+            }
+            continue;
+        }
+        let pattern: Vec<char> = part.chars().collect();
+        let mut matched = Vec::new();
+        for dir in &paths {
+            let read_from = if dir.as_os_str().is_empty() { Path::new(".") } else { dir.as_path() };
+            let Ok(children) = fs::read_dir(read_from) else {
+                continue;
+            };
+            for child in children.flatten() {
+                let name = child.file_name().to_string_lossy().into_owned();
+                if name.starts_with('.') && !part.starts_with('.') {
+                    continue;
+                }
+                if matches(&pattern, &name.chars().collect::<Vec<_>>()) {
+                    matched.push(dir.join(&name));
+                }
+            }
+        }
+        paths = matched;
+    }
+    let mut files: Vec<PathBuf> = paths.into_iter().filter(|p| p.is_file()).collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no files match {}", pattern));
+    }
+    Ok(files)
+}
+
+/// Whether all of `name` matches `pattern`.
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+        Some('[') => {
+            let Some(close) = pattern.iter().skip(2).position(|&c| c == ']').map(|i| i + 2) else {
+                // an unclosed [ is just a [
+                return name.first() == Some(&'[') && matches(&pattern[1..], &name[1..]);
+            };
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            let (negated, set) = match &pattern[1..close] {
+                ['!', set @ ..] => (true, set),
+                set => (false, set),
+            };
+            let mut in_set = false;
+            let mut i = 0;
+            while i < set.len() {
+                if i + 2 < set.len() && set[i + 1] == '-' {
+                    in_set |= (set[i]..=set[i + 2]).contains(&c);
+                    i += 3;
+                } else {
+                    in_set |= set[i] == c;
+                    i += 1;
+                }
+            }
+            in_set != negated && matches(&pattern[close + 1..], &name[1..])
+        }
+        Some(&p) => name.first() == Some(&p) && matches(&pattern[1..], &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glob_match(pattern: &str, name: &str) -> bool {
+        matches(&pattern.chars().collect::<Vec<_>>(), &name.chars().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(glob_match("*.mp4", "week12.mp4"));
+        assert!(!glob_match("*.mp4", "week12.mkv"));
+        assert!(glob_match("week??.*", "week12.mp4"));
+        assert!(!glob_match("week?.*", "week12.mp4"));
+        assert!(glob_match("week[0-9][0-9].mp4", "week12.mp4"));
+        assert!(!glob_match("week[!1]*", "week12.mp4"));
+        assert!(glob_match("[ab]*", "b"));
+        assert!(glob_match("a[b", "a[b"));
+    }
+}
@@ -0,0 +1,242 @@
+//! A journal of past runs, so yesterday's batch can be looked over or done
+//! again (`xclips history`, `xclips rerun`) without digging through shell
+//! history. Each run is a line of JSON in `$XDG_DATA_HOME/xclips/history.jsonl`
+//! (`~/.local/share/xclips/history.jsonl` by default), or in the file the
+//! `XCLIPS_HISTORY` environment variable names (set it empty to keep none):
+//!
+//! ```text
+//! {"id": 12, "time": "2026-10-14 09:30:02", "dir": "/home/me/talks",
+//!  "args": ["xclips", "talk.mp4", "-f", "spans.txt"], "result": "ok",
+//!  "clips": [{"input": "talk.mp4", "start": 1.5, "end": 3.0, "label": null,
+//!             "output": "talk_clip0.mp4", "status": "ok"}, ...]}
+//! ```
+//!
+//! Dry runs aren't recorded, since they don't make anything.
+
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::json::{self, Value};
+use crate::report::Record;
+use crate::{shell_quote, template};
+
+lazy_static! {
+    /// The clips of the run in progress, as they'll be recorded.
+    static ref CLIPS: Mutex<Vec<Value>> = Mutex::new(Vec::new());
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "history", bin_name = "xclips history")]
+pub struct HistoryOpt {
+    /// Show this run's spans and outputs, rather than listing runs
+    #[structopt(name = "ID")]
+    id: Option<String>,
+
+    /// How many of the latest runs to list
+    #[structopt(short = "n", long = "last", default_value = "20")]
+    last: usize,
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "rerun", bin_name = "xclips rerun")]
+pub struct RerunOpt {
+    /// The run to repeat, by its number in `xclips history`, or LAST
+    #[structopt(name = "ID", default_value = "LAST")]
+    id: String,
+}
+
+/// A run read back from the journal.
+struct Run {
+    id: u64,
+    time: String,
+    dir: PathBuf,
+    args: Vec<String>,
+    result: String,
+    clips: Vec<Value>,
+}
+
+impl Run {
+    /// The command line, as it could be typed again.
+    fn command(&self) -> String {
+        let program = self.args.first().map(|a| Path::new(a).file_name().map_or(a.clone(), |n| n.to_string_lossy().into_owned()));
+        program.into_iter().chain(self.args.iter().skip(1).map(|a| shell_quote(a))).collect::<Vec<_>>().join(" ")
+    }
+}
+
+pub fn run(opt: HistoryOpt) -> Result<()> {
+    let runs = read()?;
+    let Some(ref id) = opt.id else {
+        for run in &runs[runs.len().saturating_sub(opt.last)..] {
+            println!("{:>4}  {}  {:<6}  {}", run.id, run.time, if run.result == "ok" { "ok" } else { "failed" }, run.command());
+        }
+        return Ok(());
+    };
+    let run = find(&runs, id)?;
+    println!("run {}, {} in {}", run.id, run.time, run.dir.display());
+    println!("{}", run.command());
+    println!("result: {}", run.result);
+    for clip in &run.clips {
+        let field = |key| clip.get(key).and_then(Value::as_str).unwrap_or("?");
+        let time = |key| match clip.get(key) {
+            Some(Value::Number(n)) => format!("{:.3}", n),
+            _ => "?".to_string(),
+        };
+        println!("  {} ({}-{}, from {}): {}", field("output"), time("start"), time("end"), field("input"), field("status"));
+    }
+    Ok(())
+}
+
+/// Repeats a past run, with the same arguments in the same directory.
+pub fn rerun(opt: RerunOpt) -> Result<()> {
+    let runs = read()?;
+    let run = find(&runs, &opt.id)?;
+    eprintln!("rerunning {} (in {})", run.command(), run.dir.display());
+    let exe = env::current_exe().map_err(|err| XclipsError::io("cannot find the xclips executable", err))?;
+    let status = Command::new(exe)
+        .args(&run.args[1..])
+        .current_dir(&run.dir)
+        .status()
+        .map_err(|_| XclipsError::Spawn("xclips".to_string()))?;
+    if !status.success() {
+        return Err(XclipsError::Failed(format!("rerun of run {} failed", run.id)));
+    }
+    Ok(())
+}
+
+fn find<'a>(runs: &'a [Run], id: &str) -> Result<&'a Run> {
+    let found = match id {
+        "LAST" | "last" => runs.last(),
+        id => {
+            let id: u64 = id.parse().map_err(|_| XclipsError::Usage(format!("expected a run number or LAST, not {}", id)))?;
+            runs.iter().find(|r| r.id == id)
+        }
+    };
+    found.ok_or_else(|| XclipsError::Usage(format!("no run {} in the history", id)))
+}
+
+/// Notes the outcome of the clips cut from `input`, for the run's journal
+/// entry.
+pub fn note_clips(input: &Path, records: &[Record]) {
+    let mut clips = CLIPS.lock().unwrap();
+    for r in records {
+        clips.push(json::object([
+            ("input", input.to_string_lossy().into_owned().into()),
+            ("start", (r.entry.span.start.as_millis() as f64 / 1000.0).into()),
+            ("end", (r.entry.span.end.as_millis() as f64 / 1000.0).into()),
+            ("label", r.entry.label.clone().into()),
+            ("output", r.output.into()),
+            ("status", r.status.into()),
+        ]));
+    }
+}
+
+/// Adds the run that just finished to the journal. A journal that can't be
+/// written only gets a warning, since the run itself is done.
+pub fn record(args: &[OsString], result: &Result<()>) {
+    let Some(path) = path() else {
+        return;
+    };
+    let id = match read_from(&path) {
+        Ok(runs) => runs.last().map_or(1, |r| r.id + 1),
+        Err(_) => 1,
+    };
+    let dir = env::current_dir().map(|d| d.to_string_lossy().into_owned()).unwrap_or_default();
+    let entry = json::object([
+        ("id", id.into()),
+        ("time", template::strftime("%Y-%m-%d %H:%M:%S").unwrap_or_default().into()),
+        ("dir", dir.into()),
+        ("args", args.iter().map(|a| a.to_string_lossy().into_owned()).collect::<Vec<_>>().into()),
+        ("result", result.as_ref().map_or_else(|err| err.to_string(), |()| "ok".to_string()).into()),
+        ("clips", Value::Array(CLIPS.lock().unwrap().clone())),
+    ]);
+    let written = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|()| {
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "{}", entry)
+    });
+    if let Err(err) = written {
+        eprintln!("warning: cannot write history {}: {}", path.display(), err);
+    }
+}
+
+fn path() -> Option<PathBuf> {
+    if let Some(path) = env::var_os("XCLIPS_HISTORY") {
+        return Some(PathBuf::from(path)).filter(|p| !p.as_os_str().is_empty());
+    }
+    let data = env::var_os("XDG_DATA_HOME").map(PathBuf::from).or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")))?;
+    Some(data.join("xclips").join("history.jsonl"))
+}
+
+fn read() -> Result<Vec<Run>> {
+    let path = path().ok_or_else(|| XclipsError::Usage("no history is kept (XCLIPS_HISTORY is empty)".to_string()))?;
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    read_from(&path)
+}
+
+fn read_from(path: &Path) -> Result<Vec<Run>> {
+    let text = fs::read_to_string(path).map_err(|err| XclipsError::io(format!("cannot read history {}", path.display()), err))?;
+    parse(&text).map_err(|err| XclipsError::Parse(format!("{}:{}", path.display(), err)))
+}
+
+fn parse(text: &str) -> std::result::Result<Vec<Run>, String> {
+    let mut runs = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fail = |msg: &str| format!("{}: {}", n + 1, msg);
+        let doc = json::parse(line).map_err(|err| fail(&err))?;
+        let text = |key| doc.get(key).and_then(Value::as_str).map(String::from).ok_or_else(|| fail(&format!("missing {}", key)));
+        let id = match doc.get("id") {
+            Some(Value::Number(n)) => *n as u64,
+            _ => return Err(fail("missing id")),
+        };
+        let args = doc.get("args").and_then(Value::as_array).ok_or_else(|| fail("missing args"))?;
+        let args: Vec<String> = args.iter().map(|a| a.as_str().map(String::from)).collect::<Option<_>>().ok_or_else(|| fail("args aren't all strings"))?;
+        if args.is_empty() {
+            return Err(fail("missing args"));
+        }
+        let clips = doc.get("clips").and_then(Value::as_array).unwrap_or_default().to_vec();
+        runs.push(Run { id, time: text("time")?, dir: PathBuf::from(text("dir")?), args, result: text("result")?, clips });
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs() {
+        let text = concat!(
+            r#"{"id":1,"time":"2026-10-13 17:02:11","dir":"/talks","args":["/usr/bin/xclips","talk.mp4","-c","1-2"],"result":"ok","clips":[]}"#,
+            "\n",
+            r#"{"id":2,"time":"2026-10-14 09:30:02","dir":"/talks","args":["xclips","my talk.mp4"],"result":"cannot make x","clips":[{"output":"x"}]}"#,
+            "\n",
+        );
+        let runs = parse(text).unwrap();
+        assert_eq!(runs[0].command(), "xclips talk.mp4 -c 1-2");
+        assert_eq!(runs[1].command(), "xclips 'my talk.mp4'");
+        assert_eq!(runs[1].clips.len(), 1);
+        assert_eq!(find(&runs, "LAST").unwrap().id, 2);
+        assert_eq!(find(&runs, "1").unwrap().id, 1);
+        assert!(find(&runs, "3").is_err());
+        assert!(find(&[], "LAST").is_err());
+    }
+
+    #[test]
+    fn bad_runs() {
+        assert_eq!(parse("{\"id\":1}\n").err().unwrap(), "1: missing args");
+        assert!(parse("\nnot json\n").err().unwrap().starts_with("2: "));
+    }
+}
@@ -0,0 +1,74 @@
+//! Per-clip hooks for naming and encode options that are too involved for
+//! command-line flags.
+//!
+//! With `--hook CMD`, CMD is run through the shell once per planned clip,
+//! with the clip described in environment variables:
+//!
+//! ```text
+//! XCLIPS_INPUT      input file
+//! XCLIPS_INDEX      position of the clip in the batch, from 0
+//! XCLIPS_COUNT      number of clips in the batch
+//! XCLIPS_START      start, in seconds
+//! XCLIPS_END        end, in seconds
+//! XCLIPS_DURATION   length, in seconds
+//! XCLIPS_OUTPUT     the output filename xclips would use
+//! ```
+//!
+//! and it can print lines to change what happens to that clip:
+//!
+//! ```text
+//! name=PATH         write the clip to PATH instead
+//! arg=ARG           pass ARG to ffmpeg as an output option (repeatable)
+//! skip              don't cut this clip
+//! ```
+//!
+//! The hook can be written in any language; xclips doesn't embed a
+//! scripting language of its own, which is why it's a hook rather than a
+//! script.
+
+use std::process::{Command, Stdio};
+
+use crate::error::{Result, XclipsError};
+use crate::Span;
+
+/// What a hook decided about one clip.
+#[derive(Default)]
+pub struct Decision {
+    pub name: Option<String>,
+    pub args: Vec<String>,
+    pub skip: bool,
+}
+
+pub fn decide(hook: &str, input: &str, index: usize, count: usize, span: &Span, output: &str) -> Result<Decision> {
+    let duration = span.duration();
+    let result = Command::new("sh")
+        .args(["-c", hook])
+        .env("XCLIPS_INPUT", input)
+        .env("XCLIPS_INDEX", index.to_string())
+        .env("XCLIPS_COUNT", count.to_string())
+        .env("XCLIPS_START", span.start.to_string())
+        .env("XCLIPS_END", span.end.to_string())
+        .env("XCLIPS_DURATION", format!("{}.{:03}", duration.as_secs(), duration.subsec_millis()))
+        .env("XCLIPS_OUTPUT", output)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|_| XclipsError::Spawn(format!("hook: {}", hook)))?;
+    if !result.status.success() {
+        return Err(XclipsError::Command(format!("hook returned non-zero exit status for clip {} ({})", index, span)));
+    }
+    let mut decision = Decision::default();
+    for line in String::from_utf8_lossy(&result.stdout).lines() {
+        let line = line.trim_end_matches('\r');
+        if let Some(name) = line.strip_prefix("name=") {
+            decision.name = Some(name.to_string());
+        } else if let Some(arg) = line.strip_prefix("arg=") {
+            decision.args.push(arg.to_string());
+        } else if line == "skip" {
+            decision.skip = true;
+        } else if !line.trim().is_empty() {
+            eprintln!("warning: ignoring unrecognized hook output: {}", line);
+        }
+    }
+    Ok(decision)
+}
@@ -0,0 +1,652 @@
+//! Reading spans from the places they can come from: a timestamps file, the
+//! command line, the input's chapters, subtitles, or the output of an
+//! external command (which may be a detector, given the input's audio).
+//!
+//! Span lists are either text, one `START-END` (or `START+LENGTH`) span per
+//! line, each optionally followed by a label (`12:30-13:45 goal by smith`),
+//! or JSON: an array (optionally under a top-level `"spans"` key) whose
+//! items are either span strings or objects with `"start"` and `"end"`
+//! fields, given as timestamp strings or as numbers of seconds. Either end
+//! of a span may be left off: `1:23:45-` runs to the end of the input
+//! (which is probed for its length), and `-30` is the first 30 seconds.
+//! Either end may also be counted back from the end of the input, written in
+//! parentheses with a minus sign: `10:00-(-0:30)` stops 30 seconds before
+//! the end, and `(-1:00)-` is the last minute (in JSON, a negative number of
+//! seconds does the same).
+//! Objects may also have a `"label"`, and an `"ext"` (or `"format"`) field
+//! choosing a different output container for that span, such as `"gif"` or
+//! `"mp3"`. With `--csv`, the list is a spreadsheet's rows instead, with
+//! columns chosen by `--start-column` and the like.
+//!
+//! Files may be UTF-8 or UTF-16, with or without a byte order mark, and have
+//! Unix or Windows line endings.
+//!
+//! Unless `--locale-strict` is given, timestamps may use `,` as the decimal
+//! separator (`1:02,5`), and spaces around them and around the dash are
+//! ignored. Timestamps may also be in any custom notation the project's
+//! `.xclipsrc` sets up, or in frames (`f1500`, or `12:30.5f` for 5 frames
+//! past 12:30).
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::json::{self, Value};
+use crate::frames::{self, FrameRate};
+use crate::sequence::{self, Sequence};
+use crate::{csv, detect, parse_duration, probe, sample, shell_quote, srt, vars, Span, Timestamp};
+
+lazy_static! {
+    /// The dash (or plus) between a span's timestamps and the spaces around
+    /// it, which aren't part of them.
+    static ref SEPARATOR: Regex = Regex::new(r"\s*([-+])\s*").unwrap();
+}
+
+#[derive(StructOpt, Debug)]
+pub struct SpanSource {
+    /// File with one span per line (or a JSON list of spans), or - for standard input
+    #[structopt(short = "f", long = "timestamps-file", parse(from_os_str))]
+    pub timestamps_file: Option<PathBuf>,
+
+    /// Spans to cut, as START-END or START+LENGTH; either end may be left off, as in 1:23:45- (to the end of the input) or -30 (the first 30
+    /// seconds; write -c=-1:30 for ends with a colon), or counted back from the end, as in 10:00-(-0:30)
+    #[structopt(short = "c", long = "clip")]
+    pub clip: Vec<String>,
+
+    /// Read the -f file as CSV with a header row (or TSV, if its name ends in .tsv)
+    #[structopt(long = "csv", requires = "timestamps-file")]
+    pub csv: bool,
+
+    /// The --csv column spans start at, by header or number from 1
+    #[structopt(long = "start-column", default_value = "start")]
+    pub start_column: String,
+
+    /// The --csv column spans end at, by header or number from 1
+    #[structopt(long = "end-column", default_value = "end")]
+    pub end_column: String,
+
+    /// The --csv column to take labels from, if any, by header or number from 1 (a column named label is used
+    /// anyway)
+    #[structopt(long = "label-column")]
+    pub label_column: Option<String>,
+
+    /// Take a span from each of the input's chapters, labeled with its title
+    #[structopt(long = "from-chapters")]
+    pub from_chapters: bool,
+
+    /// SubRip subtitles to take spans from, one per cue, labeled with its text (a -f file ending in .srt is read
+    /// this way too)
+    #[structopt(long = "srt", parse(from_os_str))]
+    pub srt: Option<PathBuf>,
+
+    /// Keep only the subtitle cues whose text matches this regular expression
+    #[structopt(long = "grep")]
+    pub grep: Option<Regex>,
+
+    /// File of NAME = VALUE lines giving the values of {{NAME}} variables in the spans (by default, the input's
+    /// name with a .vars extension)
+    #[structopt(long = "vars", parse(from_os_str))]
+    pub vars: Option<PathBuf>,
+
+    /// Run this shell command and read spans from its output; {input} is replaced by the input path
+    #[structopt(long = "spans-from-cmd")]
+    pub spans_from_cmd: Option<String>,
+
+    /// Run this shell command with the input's audio on standard input (as 16 kHz mono WAV) and read spans from its
+    /// output, for detectors of applause, laughter, or anything else
+    #[structopt(long = "detect-exec")]
+    pub detect_exec: Option<String>,
+
+    /// Generate this many evenly spaced spans across the whole input
+    #[structopt(long = "sample", requires = "length")]
+    pub sample: Option<usize>,
+
+    /// Generate this many spans at random positions in the input
+    #[structopt(long = "sample-random", requires = "length")]
+    pub sample_random: Option<usize>,
+
+    /// Seed for --sample-random, to reproduce an earlier sample
+    #[structopt(long = "seed", requires = "sample-random")]
+    pub seed: Option<u64>,
+
+    /// Length of each generated span (e.g. 10s)
+    #[structopt(long = "length", parse(try_from_str = parse_duration))]
+    pub length: Option<Duration>,
+
+    /// Length of the input, for when ffprobe can't tell (or isn't installed, or the input is a pipe)
+    #[structopt(long = "duration", parse(try_from_str = parse_duration))]
+    pub duration: Option<Duration>,
+
+    /// Frame rate to count frame positions (f1500, 12:30.5f) at, instead of the input's, as 24, 29.97, or 30000/1001 (and
+    /// the rate an image sequence like frames_%05d.png plays at)
+    #[structopt(long = "frame-rate")]
+    pub frame_rate: Option<FrameRate>,
+
+    /// Only accept spans written exactly as START-END with `.` before fractions, rejecting spaces and commas
+    #[structopt(long = "locale-strict")]
+    pub locale_strict: bool,
+}
+
+/// A span along with where it came from (for diagnostics) and any per-span
+/// output settings.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub origin: String,
+    pub span: Span,
+    pub label: Option<String>,
+    /// Output extension to use instead of the batch's.
+    pub ext: Option<String>,
+    /// Output path to reuse, when redoing a clip from an earlier report.
+    pub output: Option<String>,
+    /// Which ends of the span were written relative to the end of the
+    /// input; until its length is known, `span` has zero in place of a
+    /// start, and ends where it starts.
+    pub from_end: FromEnd,
+    /// Whether the span was written out in full by hand, in a timestamps
+    /// file or with `-c`, rather than generated or running to (or counting
+    /// back from) the end.
+    pub written: bool,
+}
+
+impl Entry {
+    pub fn new(origin: String, span: Span) -> Entry {
+        Entry { origin, span, label: None, ext: None, output: None, from_end: FromEnd::default(), written: false }
+    }
+}
+
+/// How long before the end of the input a span starts or ends, for spans
+/// that count back from it: `1:23:45-` ends right at the end, and
+/// `10:00-(-0:30)` 30 seconds before it.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct FromEnd {
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+}
+
+impl FromEnd {
+    pub fn is_some(&self) -> bool {
+        self.start.is_some() || self.end.is_some()
+    }
+
+    /// Where `span` is in an input of the given length.
+    fn resolve(&self, span: Span, length: Duration) -> std::result::Result<Span, String> {
+        let end_of_input = Timestamp::from_millis(length.as_millis() as u64);
+        let before_end = |d: Duration| {
+            let at = end_of_input.as_millis().checked_sub(d.as_millis() as u64);
+            at.map(Timestamp::from_millis).ok_or_else(|| format!("counts back further than the start of the input, which ends at {}", end_of_input))
+        };
+        let start = self.start.map_or(Ok(span.start), before_end)?;
+        let end = self.end.map_or(Ok(span.end), before_end)?;
+        Span::new(start, end).map_err(|_| format!("starts at {}, after it ends at {} (the input ends at {})", start, end, end_of_input))
+    }
+}
+
+/// One end of a span: a timestamp, or how long before the end of the input.
+enum Position {
+    At(Timestamp),
+    BeforeEnd(Duration),
+}
+
+impl Position {
+    /// The span between two positions, if it doesn't end before it starts
+    /// (as far as can be told before the input's length is known).
+    fn span_to(self, end: Position) -> Option<(Span, FromEnd)> {
+        let (start, from_start) = match self {
+            Position::At(t) => (t, None),
+            Position::BeforeEnd(d) => (Timestamp::from_millis(0), Some(d)),
+        };
+        match end {
+            Position::At(end) => Some((Span::new(start, end).ok()?, FromEnd { start: from_start, end: None })),
+            Position::BeforeEnd(d) => Some((Span { start, end: start }, FromEnd { start: from_start, end: Some(d) })),
+        }
+    }
+}
+
+/// Reads spans from the timestamps file, the command line, and then the
+/// external command, in the order they were given.
+pub fn read_spans(source: &SpanSource, input: &Path) -> Result<Vec<Entry>> {
+    frames::counting_in(source.frame_rate, input, || read_all(source, input))
+}
+
+fn read_all(source: &SpanSource, input: &Path) -> Result<Vec<Entry>> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut values = None;
+    // the values file is only read when a span needs it
+    let mut substitute = |text: String, origin: &str| -> Result<String> {
+        if !vars::used(&text) {
+            return Ok(text);
+        }
+        if values.is_none() {
+            let path = vars::path(source.vars.as_deref(), input);
+            if source.vars.is_none() && !path.exists() {
+                return Err(XclipsError::Usage(format!("{} has {{{{variables}}}}, but there's no {} to take their values from; name one with --vars", origin, path.display())));
+            }
+            values = Some(vars::read(&path)?);
+        }
+        vars::substitute(&text, values.as_ref().unwrap()).map_err(|err| XclipsError::Parse(format!("{}: {}", origin, err)))
+    };
+    // get all clips from the file
+    let mut subtitles = source.srt.iter().collect::<Vec<_>>();
+    if let Some(ref path) = source.timestamps_file {
+        if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("srt")) {
+            subtitles.push(path);
+        } else {
+            let (text, encoding) = read_text(path)?;
+            let text = substitute(text, &path.display().to_string())?;
+            let written = if source.csv {
+                csv_spans(source, path, &text).map_err(|err| in_encoding(err, encoding))?
+            } else {
+                parse_spans(&text, &path.display().to_string(), source.locale_strict).map_err(|err| in_encoding(err, encoding))?
+            };
+            entries.extend(written.into_iter().map(|entry| Entry { written: !entry.from_end.is_some(), ..entry }));
+        }
+    }
+    // get all clips from the input's chapters
+    if source.from_chapters {
+        let chapters = probe::chapters(input).ok_or_else(|| XclipsError::Probe { what: "chapters", path: input.to_path_buf() })?;
+        if chapters.is_empty() {
+            eprintln!("warning: {} has no chapters", input.display());
+        }
+        for (n, (span, title)) in chapters.into_iter().enumerate() {
+            entries.push(Entry { label: title, ..Entry::new(format!("chapter #{}", n + 1), span) });
+        }
+    }
+    // get all clips from subtitles
+    if source.grep.is_some() && subtitles.is_empty() {
+        return Err(XclipsError::Usage("--grep picks subtitle cues, so it needs --srt (or a .srt file for -f)".to_string()));
+    }
+    for path in subtitles {
+        entries.extend(srt_spans(path, source.grep.as_ref())?);
+    }
+    // get all clips from command-line arguments
+    for (n, clip) in source.clip.iter().enumerate() {
+        let clip = &substitute(clip.clone(), &format!("clip #{}", n + 1))?;
+        let (span, from_end) = parse_span(clip, source.locale_strict).ok_or_else(|| XclipsError::Parse(format!("cannot parse {} as a time span", clip)))?;
+        entries.push(Entry { from_end, written: !from_end.is_some(), ..Entry::new(format!("clip #{}", n + 1), span) })
+    }
+    // get all clips from the external command
+    if let Some(ref cmd) = source.spans_from_cmd {
+        entries.extend(spans_from_cmd(cmd, input, source.locale_strict)?);
+    }
+    // get all clips from the detector
+    if let Some(ref cmd) = source.detect_exec {
+        let text = detect::exec(cmd, input)?;
+        entries.extend(parse_spans(&text, "detector", source.locale_strict).map_err(XclipsError::Parse)?);
+    }
+    // generate clips spread over the input
+    if let (Some(n), Some(length)) = (source.sample, source.length) {
+        let duration = input_duration(source, input)?;
+        for (k, span) in sample::even(duration, n, length).into_iter().enumerate() {
+            entries.push(Entry::new(format!("sample #{}", k + 1), span));
+        }
+    }
+    if let (Some(n), Some(length)) = (source.sample_random, source.length) {
+        let duration = input_duration(source, input)?;
+        let seed = source.seed.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            let seed = now.as_nanos() as u64;
+            eprintln!("using --seed {}", seed);
+            seed
+        });
+        for (k, span) in sample::random(duration, n, length, seed).into_iter().enumerate() {
+            entries.push(Entry::new(format!("random sample #{}", k + 1), span));
+        }
+    }
+    // place the spans counted from the end of the input (or running to it)
+    if entries.iter().any(|e| e.from_end.is_some()) {
+        let length = input_duration(source, input)?;
+        for entry in entries.iter_mut().filter(|e| e.from_end.is_some()) {
+            entry.span = entry.from_end.resolve(entry.span, length).map_err(|err| XclipsError::Parse(format!("{}: {}", entry.origin, err)))?;
+            entry.from_end = FromEnd::default();
+        }
+    }
+    Ok(entries)
+}
+
+/// The length of the input: as given with `--duration`, or else as probed
+/// (or as counted, for an image sequence).
+pub fn input_duration(source: &SpanSource, input: &Path) -> Result<Duration> {
+    if source.duration.is_none() && sequence::is_sequence(input) {
+        return Ok(Sequence::open(input, source.frame_rate)?.duration());
+    }
+    source.duration.or_else(|| probe::duration(input)).ok_or_else(|| XclipsError::Probe { what: "duration", path: input.to_path_buf() })
+}
+
+/// Reads all lines of a file, where `-` means standard input.
+pub fn read_lines(path: &Path) -> Result<Vec<String>> {
+    let (text, _) = read_text(path)?;
+    Ok(text.lines().map(String::from).collect())
+}
+
+/// Reads a text file (or standard input, for `-`) in whichever encoding
+/// `decode` finds it in, returning the text and the encoding's name.
+pub fn read_text(path: &Path) -> Result<(String, &'static str)> {
+    let mut bytes = Vec::new();
+    let read = if path == Path::new("-") {
+        io::stdin().lock().read_to_end(&mut bytes)
+    } else {
+        let mut file = File::open(path).map_err(|err| XclipsError::io(format!("cannot open file: {}", path.display()), err))?;
+        file.read_to_end(&mut bytes)
+    };
+    read.map_err(|err| XclipsError::io(format!("error reading file: {}", path.display()), err))?;
+    decode(&bytes).map_err(|err| XclipsError::Parse(format!("cannot read {}: {}", path.display(), err)))
+}
+
+/// Decodes text that's UTF-8 (with or without a byte order mark) or UTF-16
+/// (as Windows tools like Notepad and Excel may save it, usually with a byte
+/// order mark), giving back the encoding's name too.
+pub fn decode(bytes: &[u8]) -> std::result::Result<(String, &'static str), String> {
+    if let Some(rest) = bytes.strip_prefix(b"\xef\xbb\xbf") {
+        return String::from_utf8(rest.to_vec()).map(|s| (s, "UTF-8 with a byte order mark")).map_err(|err| not_utf8(&err));
+    }
+    let utf16 = match bytes {
+        [0xff, 0xfe, rest @ ..] => Some((rest, true)),
+        [0xfe, 0xff, rest @ ..] => Some((rest, false)),
+        // without a byte order mark, UTF-16 text that's mostly ASCII (like
+        // timestamps) has a zero in every other byte
+        [a, 0, ..] if *a != 0 && bytes.len().is_multiple_of(2) && bytes.iter().skip(1).step_by(2).all(|&b| b == 0) => Some((bytes, true)),
+        [0, b, ..] if *b != 0 && bytes.len().is_multiple_of(2) && bytes.iter().step_by(2).all(|&b| b == 0) => Some((bytes, false)),
+        _ => None,
+    };
+    let Some((body, little_endian)) = utf16 else {
+        return String::from_utf8(bytes.to_vec()).map(|s| (s, "UTF-8")).map_err(|err| not_utf8(&err));
+    };
+    let name = if little_endian { "UTF-16LE" } else { "UTF-16BE" };
+    if !body.len().is_multiple_of(2) {
+        return Err(format!("the text looks like {} but has an odd number of bytes", name));
+    }
+    let units = body.chunks(2).map(|pair| if little_endian { u16::from_le_bytes([pair[0], pair[1]]) } else { u16::from_be_bytes([pair[0], pair[1]]) });
+    let text: String = char::decode_utf16(units).collect::<std::result::Result<_, _>>().map_err(|_| format!("the text looks like {} but isn't valid {}", name, name))?;
+    // a byte order mark may still be there if it was written twice
+    Ok((text.strip_prefix('\u{feff}').map(String::from).unwrap_or(text), name))
+}
+
+fn not_utf8(err: &std::string::FromUtf8Error) -> String {
+    format!("the text isn't UTF-8 or UTF-16 (at byte {}); save it as UTF-8", err.utf8_error().valid_up_to())
+}
+
+fn srt_spans(path: &Path, grep: Option<&Regex>) -> Result<Vec<Entry>> {
+    let (text, encoding) = read_text(path)?;
+    let cues = srt::parse(&text).map_err(|err| in_encoding(format!("cannot read subtitles {}: {}", path.display(), err), encoding))?;
+    let entries = cues
+        .into_iter()
+        .enumerate()
+        .filter(|(_, cue)| grep.is_none_or(|re| re.is_match(&cue.text)))
+        .map(|(n, cue)| {
+            let mut entry = Entry::new(format!("{} cue #{}", path.display(), n + 1), cue.span);
+            entry.label = Some(cue.text).filter(|t| !t.is_empty());
+            entry
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// Reads spans from the rows of a CSV (or TSV) file, taking them from the
+/// columns `source` names.
+fn csv_spans(source: &SpanSource, path: &Path, text: &str) -> std::result::Result<Vec<Entry>, String> {
+    let delimiter = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("tsv")) { '\t' } else { ',' };
+    let rows = csv::parse(text, delimiter).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let Some((header, rows)) = rows.split_first() else {
+        return Ok(Vec::new());
+    };
+    let column = |name: &str| csv::column(header, name).ok_or_else(|| format!("{} has no column {}", path.display(), name));
+    let (start, end) = (column(&source.start_column)?, column(&source.end_column)?);
+    let label = match source.label_column {
+        Some(ref name) => Some(column(name)?),
+        None => csv::column(header, "label"),
+    };
+    let mut entries = Vec::new();
+    for (n, row) in rows.iter().enumerate() {
+        let origin = format!("{} row {}", path.display(), n + 2);
+        let field = |i: usize| row.get(i).map_or("", |f| f.trim());
+        let span = format!("{}-{}", field(start), field(end));
+        let (span, from_end) = parse_span(&span, source.locale_strict).ok_or_else(|| format!("{}: cannot parse {} as a time span", origin, span))?;
+        let mut entry = Entry { from_end, ..Entry::new(origin, span) };
+        entry.label = label.map(field).filter(|l| !l.is_empty()).map(String::from);
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// A parse error in a file, noting the encoding it was read in if that's
+/// anything but plain UTF-8, in case it was the wrong guess.
+fn in_encoding(err: String, encoding: &str) -> XclipsError {
+    if encoding == "UTF-8" {
+        XclipsError::Parse(err)
+    } else {
+        XclipsError::Parse(format!("{} (read as {})", err, encoding))
+    }
+}
+
+fn spans_from_cmd(cmd: &str, input: &Path, strict: bool) -> Result<Vec<Entry>> {
+    let cmd = cmd.replace("{input}", &shell_quote(&input.to_string_lossy()));
+    let output = Command::new("sh")
+        .args(["-c", &cmd])
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|_| XclipsError::Spawn(format!("span command: {}", cmd)))?;
+    if !output.status.success() {
+        return Err(XclipsError::Command(format!("span command returned non-zero exit status: {}", cmd)));
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    parse_spans(&text, "span command", strict).map_err(XclipsError::Parse)
+}
+
+/// Parses a span list in either the text or the JSON format.
+pub fn parse_spans(text: &str, origin: &str, strict: bool) -> std::result::Result<Vec<Entry>, String> {
+    let trimmed = text.trim_start();
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        return parse_spans_json(trimmed, origin, strict);
+    }
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            let ((span, from_end), label) = parse_labeled_span(line.trim(), strict).ok_or_else(|| format!("cannot parse {} as a time span", line))?;
+            Ok(Entry { from_end, label, ..Entry::new(format!("{}:{}", origin, n + 1), span) })
+        })
+        .collect()
+}
+
+fn parse_spans_json(text: &str, origin: &str, strict: bool) -> std::result::Result<Vec<Entry>, String> {
+    let doc = json::parse(text).map_err(|err| format!("cannot read spans from {}: {}", origin, err))?;
+    let items = doc.get("spans").unwrap_or(&doc).as_array().ok_or_else(|| format!("cannot read spans from {}: expected an array", origin))?;
+    items
+        .iter()
+        .enumerate()
+        .map(|(n, item)| {
+            let (span, from_end) = json_span(item, strict).ok_or_else(|| format!("cannot parse {} as a time span", item))?;
+            let mut entry = Entry { from_end, ..Entry::new(format!("{} #{}", origin, n + 1), span) };
+            entry.label = item.get("label").and_then(Value::as_str).map(String::from);
+            entry.ext = item.get("ext").or_else(|| item.get("format")).and_then(Value::as_str).map(String::from);
+            Ok(entry)
+        })
+        .collect()
+}
+
+/// A span from a JSON span list, and which of its ends count back from the
+/// end of the input; objects may leave out either `"start"` or `"end"`, as
+/// text spans may leave off either end.
+fn json_span(item: &Value, strict: bool) -> Option<(Span, FromEnd)> {
+    if let Some(s) = item.as_str() {
+        return parse_span(s, strict);
+    }
+    let (start, end) = (item.get("start"), item.get("end"));
+    if start.is_none() && end.is_none() {
+        return None;
+    }
+    let start = start.map_or(Some(Position::At(Timestamp::from_millis(0))), |t| json_position(t, strict))?;
+    start.span_to(end.map_or(Some(Position::BeforeEnd(Duration::ZERO)), |t| json_position(t, strict))?)
+}
+
+fn json_position(value: &Value, strict: bool) -> Option<Position> {
+    match value {
+        Value::String(s) => parse_position(s, strict),
+        Value::Number(n) if *n >= 0.0 => Some(Position::At(Timestamp::from_millis((n * 1000.0).round() as u64))),
+        Value::Number(n) if n.is_finite() => Some(Position::BeforeEnd(Duration::from_millis((-n * 1000.0).round() as u64))),
+        _ => None,
+    }
+}
+
+/// Parses one end of a span: a timestamp, or `(-0:30)` for 30 seconds
+/// before the end of the input.
+fn parse_position(s: &str, strict: bool) -> Option<Position> {
+    let s = if strict { s } else { s.trim() };
+    match s.strip_prefix("(-").and_then(|s| s.strip_suffix(')')) {
+        Some(before) => parse_duration(if strict { before } else { before.trim() }).ok().map(Position::BeforeEnd),
+        None => parse_localized(s, strict).map(Position::At),
+    }
+}
+
+/// Parses a span, and which of its ends count back from the end of the
+/// input: `START-` runs to the end, `-END` starts at the beginning, and
+/// either end may be `(-LENGTH)` before the end.
+fn parse_span(s: &str, strict: bool) -> Option<(Span, FromEnd)> {
+    lazy_static! {
+        /// A span with a `(-LENGTH)` end, split at the dash between its ends.
+        static ref FROM_END: Regex = Regex::new(r"^(\(-[^()]*\)|[^()]*?)\s*-\s*(\(-[^()]*\)|[^()]*)$").unwrap();
+    }
+    if let Some(span) = parse_localized(s, strict) {
+        return Some((span, FromEnd::default()));
+    }
+    let s = if strict { s } else { s.trim() };
+    if s.contains("(-") {
+        let captures = FROM_END.captures(s).filter(|_| !strict || !s.contains(char::is_whitespace))?;
+        let start = if captures[1].is_empty() { Position::At(Timestamp::from_millis(0)) } else { parse_position(&captures[1], strict)? };
+        let end = if captures[2].is_empty() { Position::BeforeEnd(Duration::ZERO) } else { parse_position(&captures[2], strict)? };
+        return start.span_to(end);
+    }
+    if let Some(start) = s.strip_suffix('-').filter(|start| !start.is_empty()) {
+        let start = parse_localized(start, strict)?;
+        return Some((Span { start, end: start }, FromEnd { start: None, end: Some(Duration::ZERO) }));
+    }
+    let end = parse_localized(s.strip_prefix('-')?, strict)?;
+    Some((Span { start: Timestamp::from_millis(0), end }, FromEnd::default()))
+}
+
+/// Parses a line of a text span list: a span, optionally followed by a
+/// label after some whitespace (`12:30-13:45 goal by smith`).
+fn parse_labeled_span(line: &str, strict: bool) -> Option<((Span, FromEnd), Option<String>)> {
+    if let Some(span) = parse_span(line, strict) {
+        return Some((span, None));
+    }
+    // the span is the shortest run of words that reads as one, taking
+    // spaces around the dash as part of it (unless that's strict)
+    let lines = if strict { vec![line.to_string()] } else { vec![SEPARATOR.replace_all(line, "$1").into_owned(), line.to_string()] };
+    lines.iter().find_map(|line| {
+        line.char_indices().filter(|&(_, c)| c.is_whitespace()).find_map(|(i, _)| {
+            let span = parse_span(&line[..i], strict)?;
+            Some((span, Some(line[i..].trim().to_string())))
+        })
+    })
+}
+
+/// Parses a span or timestamp as written (but for spaces around the dash),
+/// or else localized; custom timestamp notations may have commas or spaces
+/// of their own.
+fn parse_localized<T: FromStr>(s: &str, strict: bool) -> Option<T> {
+    if strict {
+        return s.parse().ok();
+    }
+    SEPARATOR.replace_all(s.trim(), "$1").parse().ok().or_else(|| localize(s, strict).parse().ok())
+}
+
+/// Rewrites a span or timestamp written the European way (`1:02,5 - 1:10`)
+/// into the form they're parsed in, unless `strict`.
+fn localize(s: &str, strict: bool) -> String {
+    if strict {
+        return s.to_string();
+    }
+    SEPARATOR.replace_all(s.trim(), "$1").replace(',', ".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, bom: bool, little_endian: bool) -> Vec<u8> {
+        let units = bom.then_some(0xfeff).into_iter().chain(text.encode_utf16());
+        units.flat_map(|u| if little_endian { u.to_le_bytes() } else { u.to_be_bytes() }).collect()
+    }
+
+    #[test]
+    fn decodes_span_files() {
+        assert_eq!(decode(b"1-2\r\n3-4\r\n").unwrap(), ("1-2\r\n3-4\r\n".to_string(), "UTF-8"));
+        assert_eq!(decode(b"\xef\xbb\xbf1-2\n").unwrap(), ("1-2\n".to_string(), "UTF-8 with a byte order mark"));
+        assert_eq!(decode(&utf16("1-2\r\n", true, true)).unwrap(), ("1-2\r\n".to_string(), "UTF-16LE"));
+        assert_eq!(decode(&utf16("1-2\r\n", true, false)).unwrap(), ("1-2\r\n".to_string(), "UTF-16BE"));
+        assert_eq!(decode(&utf16("1-2\r\n", false, true)).unwrap(), ("1-2\r\n".to_string(), "UTF-16LE"));
+        assert_eq!(decode(b"").unwrap(), (String::new(), "UTF-8"));
+    }
+
+    #[test]
+    fn bad_encodings() {
+        assert!(decode(b"1-2 \xe9t\xe9\n").unwrap_err().contains("at byte 4"));
+        assert!(decode(b"\xff\xfe1").unwrap_err().contains("UTF-16LE"));
+    }
+
+    #[test]
+    fn labels() {
+        let entries = parse_spans("12:30-13:45 goal_by_smith\n1:00 - 1:02,5  second half \n1-2 3\n5-\t ending\n7-8\n", "spans", false).unwrap();
+        let read: Vec<(String, Option<&str>)> = entries.iter().map(|e| (e.span.to_string(), e.label.as_deref())).collect();
+        assert_eq!(read[0], ("750.000-825.000".to_string(), Some("goal_by_smith")));
+        assert_eq!(read[1], ("60.000-62.500".to_string(), Some("second half")));
+        assert_eq!(read[2], ("1.000-2.000".to_string(), Some("3")));
+        assert_eq!((read[3].1, entries[3].from_end.end), (Some("ending"), Some(Duration::ZERO)));
+        assert_eq!(read[4], ("7.000-8.000".to_string(), None));
+    }
+
+    #[test]
+    fn spans_from_end() {
+        let length = Duration::from_secs(3600);
+        let place = |s: &str| {
+            let (span, from_end) = parse_span(s, false).unwrap();
+            from_end.resolve(span, length).map(|span| span.to_string())
+        };
+        assert_eq!(place("10:00-(-0:30)").unwrap(), "600.000-3570.000");
+        assert_eq!(place("(-1:00)-").unwrap(), "3540.000-3600.000");
+        assert_eq!(place("(-1:00) - (-30s)").unwrap(), "3540.000-3570.000");
+        assert_eq!(place("-(-59:00)").unwrap(), "0.000-60.000");
+        assert!(place("59:50-(-0:30)").is_err());
+        assert!(place("(-2:00:00)-").is_err());
+        assert!(parse_span("10:00-(-0:30)x", false).is_none());
+        let entries = parse_spans(r#"[{"start": 600, "end": -30}]"#, "spans", false).unwrap();
+        assert_eq!(entries[0].from_end, FromEnd { start: None, end: Some(Duration::from_secs(30)) });
+    }
+
+    #[test]
+    fn written_spans() {
+        let source = SpanSource::from_iter(["xclips", "-c", "1-5", "-c", "10-", "--sample", "2", "--length", "5s", "--duration", "60"]);
+        let entries = read_spans(&source, Path::new("talk.mp4")).unwrap();
+        let written: Vec<(&str, bool)> = entries.iter().map(|e| (e.origin.as_str(), e.written)).collect();
+        assert_eq!(written, [("clip #1", true), ("clip #2", false), ("sample #1", false), ("sample #2", false)]);
+        assert_eq!(entries[1].span, "10-60".parse().unwrap());
+    }
+
+    #[test]
+    fn crlf_span_lists() {
+        let (text, _) = decode(&utf16("1-2\r\n\r\n3-4\r\n", true, true)).unwrap();
+        let spans: Vec<Span> = parse_spans(&text, "spans", false).unwrap().into_iter().map(|e| e.span).collect();
+        assert_eq!(spans, ["1-2".parse().unwrap(), "3-4".parse().unwrap()]);
+    }
+
+    #[test]
+    fn csv_columns() {
+        let source = SpanSource::from_iter(["xclips", "-f", "log.csv", "--csv", "--start-column", "In", "--end-column", "2"]);
+        let text = "In,Out,Label\n0:01,0:03,\"goal, smith\"\n1:05,,\n";
+        let entries = csv_spans(&source, Path::new("log.csv"), text).unwrap();
+        assert_eq!((entries[0].span, entries[0].label.as_deref()), ("1-3".parse().unwrap(), Some("goal, smith")));
+        assert!(entries[1].from_end.is_some());
+        assert_eq!(entries[1].origin, "log.csv row 3");
+        let source = SpanSource::from_iter(["xclips", "-f", "log.csv", "--csv"]);
+        assert_eq!(csv_spans(&source, Path::new("log.csv"), text).unwrap_err(), "log.csv has no column start");
+    }
+}
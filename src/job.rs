@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::join;
+use crate::probe;
+use crate::ui;
+use crate::{ffmpeg, Span, Timestamp};
+
+lazy_static! {
+    /// Messages ffmpeg logs when it runs into damaged input.
+    static ref DECODE_ERROR_RE: Regex =
+        Regex::new(r"(?i)error while decoding|corrupt (input|packet|decoded frame)|invalid data found|concealing \d+ (dc|ac|mv) errors").unwrap();
+}
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How long to wait before retrying a stalled ffmpeg, doubled each retry.
+const STALL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Keys in ffmpeg's `-progress` output whose changing shows it's moving.
+const PROGRESS_KEYS: [&str; 3] = ["frame", "out_time_us", "total_size"];
+
+/// A single planned ffmpeg invocation producing one output file.
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub output: String,
+    pub span: Span,
+    pub args: Vec<String>,
+    /// Watch ffmpeg's log for decode errors (used with `--ignore-errors`).
+    pub watch_errors: bool,
+    /// Kinds of streams the output must contain (empty to skip verification).
+    pub expected_streams: Vec<String>,
+    /// Shared copy of the input to make first, which `args` cut from.
+    pub segment: Option<Arc<Segment>>,
+    /// Offset into the clip of a frame to embed as its cover picture.
+    pub poster: Option<Duration>,
+    /// Files to put before and after the clip.
+    pub bumpers: Option<Arc<Bumpers>>,
+    /// Cheaper encodings to fall back on if the output is too big.
+    pub shrink: Option<Shrink>,
+    /// Where to have ffmpeg write a log of cutting the clip, to keep if it
+    /// fails (with `--keep-going`).
+    pub report: Option<PathBuf>,
+    /// Arguments that re-encode the clip, to cut it with instead if copying
+    /// its streams fails or makes an empty or broken file.
+    pub fallback: Option<Vec<String>>,
+}
+
+/// For `--max-size`: other codec arguments to try, each making a smaller
+/// output than the last, until the output fits in `max_size` bytes.
+#[derive(Clone, Debug)]
+pub struct Shrink {
+    pub max_size: u64,
+    /// Where in the job's arguments the codec arguments are.
+    pub codec_args: Range<usize>,
+    pub steps: Vec<Vec<String>>,
+}
+
+/// An intro and outro for `--intro` and `--outro`, with their durations.
+#[derive(Debug)]
+pub struct Bumpers {
+    pub intro: Option<(String, Duration)>,
+    pub outro: Option<(String, Duration)>,
+}
+
+#[derive(Clone, Debug)]
+pub enum Failure {
+    Spawn,
+    Ffmpeg,
+    Timeout,
+    Stalled,
+    Verify(String),
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Failure::Spawn => f.write_str("failed to spawn ffmpeg"),
+            Failure::Ffmpeg => f.write_str("ffmpeg command returned non-zero exit status"),
+            Failure::Timeout => f.write_str("ffmpeg took too long and was killed"),
+            Failure::Stalled => f.write_str("ffmpeg stopped making progress and was killed"),
+            Failure::Verify(problem) => write!(f, "output {}", problem),
+        }
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// How jobs run, beyond what's planned in the jobs themselves.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RunOptions<'a> {
+    /// Kill ffmpeg if the clip takes longer than this.
+    pub timeout: Option<Duration>,
+    /// Hold ffmpeg's log back, showing it only if ffmpeg fails.
+    pub quiet: bool,
+    /// Kill ffmpeg if it makes no progress for this long.
+    pub stall: Option<Duration>,
+    /// How many times to retry ffmpeg after it stalls.
+    pub stall_retries: u32,
+    /// Draw a progress bar while each clip is cut (which ffmpeg's log would
+    /// garble, so have it held back too).
+    pub progress: bool,
+    /// Never show ffmpeg's log, even when it fails.
+    pub silent: bool,
+    /// Have ffmpeg replace outputs that already exist, rather than refuse
+    /// to (it's never left to ask, since its log may be hidden).
+    pub overwrite: bool,
+    /// Which clips are being cut, for the progress bar; set by
+    /// `for_batch`.
+    pub position: Option<Position>,
+    /// The bar for the whole run, drawn instead of one for each clip when
+    /// several are cut at once.
+    pub meter: Option<&'a ui::Meter>,
+}
+
+/// Where a batch of clips is in the whole run, and how long the longest of
+/// them is, which is how far ffmpeg's output has to get.
+#[derive(Clone, Copy, Debug)]
+pub struct Position {
+    first: usize,
+    clips: usize,
+    count: usize,
+    length: Duration,
+}
+
+impl<'a> RunOptions<'a> {
+    /// The options for cutting `batch`, which starts at clip `first` of
+    /// `count`.
+    pub fn for_batch(&self, first: usize, batch: &[Job], count: usize) -> RunOptions<'a> {
+        let length = batch.iter().map(|job| job.span.duration()).max().unwrap_or_default();
+        let position = Position { first, clips: batch.len(), count, length };
+        RunOptions { position: Some(position).filter(|_| self.progress), ..*self }
+    }
+
+    /// The options for running ffmpeg on something other than the clip
+    /// itself, which has no progress bar.
+    fn unmetered(&self) -> RunOptions<'a> {
+        RunOptions { position: None, ..*self }
+    }
+
+    /// The options for running ffmpeg onto a file this run made itself,
+    /// which it's free to replace.
+    fn overwriting(&self) -> RunOptions<'a> {
+        RunOptions { overwrite: true, ..*self }
+    }
+}
+
+impl Position {
+    fn label(&self) -> String {
+        if self.clips == 1 {
+            format!("clip {}/{}", self.first + 1, self.count)
+        } else {
+            format!("clips {}-{}/{}", self.first + 1, self.first + self.clips, self.count)
+        }
+    }
+}
+
+/// A stretch of the input copied out once and shared by several jobs, which
+/// then cut from the copy instead of reading the input again.
+#[derive(Debug)]
+pub struct Segment {
+    pub output: String,
+    /// ffmpeg arguments that make the copy.
+    pub args: Vec<String>,
+    made: Mutex<bool>,
+}
+
+impl Segment {
+    pub fn new(output: String, args: Vec<String>) -> Segment {
+        Segment { output, args, made: Mutex::new(false) }
+    }
+
+    /// Makes the copy unless an earlier job already did.
+    fn make(&self, watch_errors: bool, options: &RunOptions) -> Result<(), Failure> {
+        let mut made = self.made.lock().unwrap();
+        if !*made {
+            run_ffmpeg(&self.args, &[&self.output], watch_errors, options)?;
+            *made = true;
+        }
+        Ok(())
+    }
+}
+
+impl Job {
+    /// Cuts the clip, returning whether it had to fall back on re-encoding
+    /// it.
+    pub fn execute(&self, options: &RunOptions) -> Result<bool, Failure> {
+        if let Some(ref segment) = self.segment {
+            segment.make(self.watch_errors, &options.unmetered())?;
+        }
+        let modified = || fs::metadata(&self.output).and_then(|m| m.modified()).ok();
+        let before = modified();
+        let result = self.cut(&self.args, options);
+        let Some(ref fallback) = self.fallback else {
+            return result.map(|()| false);
+        };
+        match result {
+            // an existing output left as it was means ffmpeg was told not to
+            // overwrite it, which the re-encode shouldn't undo
+            Err(failure @ (Failure::Ffmpeg | Failure::Verify(_))) if before.is_none() || modified() != before => {
+                eprintln!("note: copying {} ({}) failed ({}); re-encoding it instead", self.output, self.span, failure);
+                self.cut(fallback, &options.overwriting()).map(|()| true)
+            }
+            result => result.map(|()| false),
+        }
+    }
+
+    fn cut(&self, args: &[String], options: &RunOptions) -> Result<(), Failure> {
+        if run_ffmpeg_reporting(args, &[&self.output], self.watch_errors, self.report.as_deref(), options)? {
+            eprintln!("warning: ignored input errors while cutting {} ({}); the clip may be damaged", self.output, self.span);
+        }
+        // a copy can "succeed" without writing any packets
+        if self.fallback.is_some() && fs::metadata(&self.output).map_or(true, |m| m.len() == 0) {
+            return Err(Failure::Verify("is empty".to_string()));
+        }
+        if let Some(ref shrink) = self.shrink {
+            self.shrink(shrink, options)?;
+        }
+        self.finish(options)
+    }
+
+    /// Cuts the clip again with each of `shrink`'s steps in turn, for as
+    /// long as it's still too big.
+    fn shrink(&self, shrink: &Shrink, options: &RunOptions) -> Result<(), Failure> {
+        let size = || fs::metadata(&self.output).map_or(0, |m| m.len());
+        let mut steps = shrink.steps.iter();
+        while size() > shrink.max_size {
+            let Some(step) = steps.next() else {
+                eprintln!("warning: {} is still {} bytes at the lowest quality tried, over --max-size", self.output, size());
+                break;
+            };
+            let mut args = self.args[..shrink.codec_args.start].to_vec();
+            args.extend(step.iter().cloned());
+            args.extend(self.args[shrink.codec_args.end..].iter().cloned());
+            // each step replaces the output the one before it wrote
+            run_ffmpeg(&args, &[&self.output], self.watch_errors, &options.overwriting())?;
+        }
+        Ok(())
+    }
+
+    /// Does what's left once ffmpeg has written the output: adding bumpers,
+    /// embedding the poster frame, and verifying the result.
+    pub fn finish(&self, options: &RunOptions) -> Result<(), Failure> {
+        let options = &options.unmetered();
+        if let Some(ref bumpers) = self.bumpers {
+            attach_bumpers(&self.output, self.span.duration(), bumpers, None, options)?;
+        }
+        if let Some(at) = self.poster {
+            embed_poster(&self.output, at, options)?;
+        }
+        if !self.expected_streams.is_empty() {
+            verify_output(Path::new(&self.output), &self.expected_streams).map_err(Failure::Verify)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs jobs whose `args` are only output options as one ffmpeg invocation
+/// after `input_args`, so the input is opened and read once for all of them.
+/// Outputs aren't finished; see `Job::finish`.
+pub fn execute_together(input_args: &[String], jobs: &[Job], options: &RunOptions) -> Result<(), Failure> {
+    let mut args = input_args.to_vec();
+    for job in jobs {
+        args.extend(job.args.iter().cloned());
+    }
+    // the timeout is per clip
+    let options = RunOptions { timeout: options.timeout.map(|t| t * jobs.len() as u32), ..*options };
+    let outputs: Vec<&str> = jobs.iter().map(|j| j.output.as_str()).collect();
+    if run_ffmpeg(&args, &outputs, jobs.iter().any(|j| j.watch_errors), &options)? {
+        eprintln!("warning: ignored input errors while cutting {}; some of these clips may be damaged", outputs.join(", "));
+    }
+    Ok(())
+}
+
+/// Runs ffmpeg to completion, writing `outputs`, returning whether it logged
+/// decode errors (only looked for with `watch_errors`).
+fn run_ffmpeg(args: &[String], outputs: &[&str], watch_errors: bool, options: &RunOptions) -> Result<bool, Failure> {
+    run_ffmpeg_reporting(args, outputs, watch_errors, None, options)
+}
+
+/// Runs ffmpeg as `run_ffmpeg` does, having it also write its log to
+/// `report`.
+fn run_ffmpeg_reporting(args: &[String], outputs: &[&str], watch_errors: bool, report: Option<&Path>, options: &RunOptions) -> Result<bool, Failure> {
+    let mut backoff = STALL_BACKOFF;
+    let mut retries = 0;
+    // what a stalled run started is removed for the retry to start over,
+    // but nothing that was there before it
+    let fresh: Vec<&str> = outputs.iter().copied().filter(|output| !Path::new(output).exists()).collect();
+    loop {
+        match run_ffmpeg_once(args, watch_errors, report, options) {
+            Err(Failure::Stalled) if retries < options.stall_retries => {
+                for output in &fresh {
+                    let _ = fs::remove_file(output);
+                }
+                eprintln!("warning: ffmpeg stopped making progress, retrying in {}s", backoff.as_secs());
+                thread::sleep(backoff);
+                backoff *= 2;
+                retries += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn run_ffmpeg_once(args: &[String], watch_errors: bool, report: Option<&Path>, options: &RunOptions) -> Result<bool, Failure> {
+    let mut command = ffmpeg();
+    if let Some(report) = report {
+        let path = report.to_string_lossy().replace('\\', "\\\\").replace(':', "\\:");
+        command.env("FFREPORT", format!("file={}:level=32", path));
+    }
+    if options.stall.is_some() || options.position.is_some() {
+        command.args(["-progress", "pipe:1"]).stdout(Stdio::piped());
+    }
+    let mut child = command
+        .args(["-nostdin", if options.overwrite { "-y" } else { "-n" }])
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(if watch_errors || options.quiet { Stdio::piped() } else { Stdio::inherit() })
+        .spawn()
+        .map_err(|_| Failure::Spawn)?;
+    // pass ffmpeg's log through (unless it's being kept quiet) while
+    // watching it for decode errors, so damaged clips can be pointed out
+    let quiet = options.quiet;
+    let watcher = child.stderr.take().map(|mut stderr| {
+        thread::spawn(move || {
+            let mut log = Vec::new();
+            let mut buf = [0; 4096];
+            while let Ok(n) = stderr.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                if !quiet {
+                    let _ = io::stderr().write_all(&buf[..n]);
+                }
+                log.extend_from_slice(&buf[..n]);
+            }
+            log
+        })
+    });
+    // ffmpeg reports progress every so often even when it's stuck, so what
+    // counts is whether the numbers in the reports change
+    let last_progress = Arc::new(Mutex::new(Instant::now()));
+    let position = options.position;
+    // how far along the clip is, for the bar drawn while waiting below
+    let done = Arc::new(Mutex::new(None));
+    let reader = child.stdout.take().map(|stdout| {
+        let (last_progress, done) = (last_progress.clone(), done.clone());
+        thread::spawn(move || {
+            let mut values: HashMap<String, String> = HashMap::new();
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let Some((key, value)) = line.split_once('=') else {
+                    continue;
+                };
+                if PROGRESS_KEYS.contains(&key) && values.get(key).is_none_or(|v| v != value) {
+                    values.insert(key.to_string(), value.to_string());
+                    *last_progress.lock().unwrap() = Instant::now();
+                }
+                // each report ends with a progress= line
+                if let (Some(position), "progress") = (position, key) {
+                    let out = values.get("out_time_us").and_then(|us| us.parse::<u64>().ok()).unwrap_or(0);
+                    *done.lock().unwrap() = Some(out as f64 / position.length.as_micros().max(1) as f64);
+                }
+            }
+        })
+    });
+    let started = Instant::now();
+    let mut status = None;
+    let mut stalled = false;
+    while status.is_none() {
+        if let (Some(position), Some(done)) = (position, done.lock().unwrap().take()) {
+            match options.meter {
+                Some(meter) => meter.update(position.first, position.clips, done),
+                None => eprint!("\r{}\x1b[K", ui::progress_bar(&position.label(), done)),
+            }
+        }
+        status = child.try_wait().map_err(|_| Failure::Spawn)?;
+        stalled = options.stall.is_some_and(|s| last_progress.lock().unwrap().elapsed() > s);
+        if status.is_none() && (stalled || options.timeout.is_some_and(|t| started.elapsed() > t)) {
+            let _ = child.kill();
+            let _ = child.wait();
+            break;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    let log = watcher.and_then(|w| w.join().ok()).unwrap_or_default();
+    if let Some(reader) = reader {
+        let _ = reader.join();
+    }
+    if position.is_some() {
+        eprint!("\r\x1b[K");
+    }
+    let succeeded = status.is_some_and(|s| s.success());
+    if quiet && !succeeded && !options.silent {
+        let _ = io::stderr().write_all(&log);
+    }
+    if let (Some(position), Some(meter)) = (position, options.meter) {
+        meter.update(position.first, position.clips, 1.0);
+    }
+    match status {
+        None if stalled => Err(Failure::Stalled),
+        None => Err(Failure::Timeout),
+        Some(_) if !succeeded => Err(Failure::Ffmpeg),
+        Some(_) => Ok(watch_errors && DECODE_ERROR_RE.is_match(&String::from_utf8_lossy(&log))),
+    }
+}
+
+/// Joins the intro, the clip, and the outro, converting the bumpers to the
+/// clip's size and frame rate. `metadata` is an ffmetadata file of chapters for
+/// the result.
+pub fn attach_bumpers(output: &str, length: Duration, bumpers: &Bumpers, metadata: Option<&str>, options: &RunOptions) -> Result<(), Failure> {
+    let path = Path::new(output);
+    let (width, height, fps) = probe::video_format(path).ok_or_else(|| Failure::Verify("cannot be probed for its video format".to_string()))?;
+    let has_audio = probe::stream_types(path).is_some_and(|types| types.iter().any(|t| t == "audio"));
+    // stream copies can run a little longer than the span
+    let length = probe::duration(path).unwrap_or(length);
+    let whole = |length: Duration| Span { start: Timestamp::from_millis(0), end: Timestamp::from_millis(length.as_millis() as u64) };
+    let mut pieces = Vec::new();
+    if let Some((ref intro, length)) = bumpers.intro {
+        pieces.push(join::Piece { input: intro, span: whole(length) });
+    }
+    pieces.push(join::Piece { input: output, span: whole(length) });
+    if let Some((ref outro, length)) = bumpers.outro {
+        pieces.push(join::Piece { input: outro, span: whole(length) });
+    }
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("mp4");
+    let with_bumpers = format!("{}.bumpers.{}", output, ext);
+    let conform = join::Conform { width, height, fps };
+    let mut args: Vec<String> = ["-v", "error"].map(String::from).to_vec();
+    args.extend(join::filter_args(&pieces, has_audio, Duration::ZERO, Some(&conform), metadata, &with_bumpers));
+    match run_ffmpeg(&args, &[&with_bumpers], false, &options.overwriting()) {
+        Ok(_) => fs::rename(&with_bumpers, output).map_err(|err| Failure::Verify(format!("could not be replaced by the copy with bumpers: {}", err))),
+        Err(failure) => {
+            let _ = fs::remove_file(&with_bumpers);
+            Err(failure)
+        }
+    }
+}
+
+/// Grabs the frame `at` into the clip and adds it to the clip as an attached
+/// picture, which file browsers and players show as its thumbnail.
+fn embed_poster(output: &str, at: Duration, options: &RunOptions) -> Result<(), Failure> {
+    let image = join::temp_path("poster.jpg").into_os_string().into_string().unwrap();
+    // next to the output, so it can be renamed over it
+    let ext = Path::new(output).extension().and_then(|e| e.to_str()).unwrap_or("mp4");
+    let with_poster = format!("{}.poster.{}", output, ext);
+    let seek = format!("{}.{:03}", at.as_secs(), at.subsec_millis());
+    let options = &options.overwriting();
+    let grab = ["-v", "error", "-ss", &seek, "-i", output, "-frames:v", "1", "-q:v", "2", &image];
+    let result = run_ffmpeg(&grab.map(String::from), &[&image], false, options).and_then(|_| {
+        let mux = ["-v", "error", "-i", output, "-i", &image, "-map", "0", "-map", "1", "-c", "copy", "-disposition:v:1", "attached_pic", &with_poster];
+        run_ffmpeg(&mux.map(String::from), &[&with_poster], false, options)
+    });
+    let _ = fs::remove_file(&image);
+    match result {
+        Ok(_) => fs::rename(&with_poster, output).map_err(|err| Failure::Verify(format!("could not be replaced by the copy with a poster: {}", err))),
+        Err(failure) => {
+            let _ = fs::remove_file(&with_poster);
+            Err(failure)
+        }
+    }
+}
+
+/// Checks that a finished clip isn't empty and has all the expected kinds of
+/// streams, since `-c copy` can silently drop a stream or produce only a
+/// container header.
+fn verify_output(path: &Path, expected_streams: &[String]) -> Result<(), String> {
+    if fs::metadata(path).map_or(true, |m| m.len() == 0) {
+        return Err("is empty".to_string());
+    }
+    let types = probe::stream_types(path).ok_or("cannot be probed")?;
+    let missing: Vec<&str> = expected_streams.iter().map(String::as_str).filter(|t| !types.iter().any(|s| s == t)).collect();
+    if !missing.is_empty() {
+        return Err(format!("is missing {} stream(s)", missing.join(" and ")));
+    }
+    if probe::duration(path).is_none_or(|d| d.is_zero()) {
+        return Err("has zero duration".to_string());
+    }
+    Ok(())
+}
@@ -0,0 +1,288 @@
+//! Combining several pieces of media into one output file.
+//!
+//! Pieces that share codecs and parameters are joined with ffmpeg's concat
+//! demuxer, which copies streams; anything else (or anything with fades) goes
+//! through the concat or xfade filters and is re-encoded.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::{Span, Timestamp};
+
+/// A piece of a joined output: a span of some input file.
+pub struct Piece<'a> {
+    pub input: &'a str,
+    pub span: Span,
+}
+
+/// A chapter marker for the joined output.
+pub struct Chapter {
+    pub title: String,
+    pub start: Timestamp,
+    pub end: Timestamp,
+}
+
+/// Video parameters to convert every piece to, for pieces that may not match
+/// (differently sized bumpers, say).
+#[derive(Clone, Debug)]
+pub struct Conform {
+    pub width: u32,
+    pub height: u32,
+    /// Frame rate, as ffprobe reports it (`30000/1001`).
+    pub fps: String,
+}
+
+/// Builds ffmpeg arguments that join whole files with the concat demuxer,
+/// copying streams. `list` is a file written by [`concat_list`].
+pub fn demuxer_args(list: &str, metadata: Option<&str>, output: &str) -> Vec<String> {
+    let mut args: Vec<String> = ["-f", "concat", "-safe", "0", "-i", list].iter().map(|s| s.to_string()).collect();
+    if let Some(metadata) = metadata {
+        args.extend(["-i", metadata, "-map", "0", "-map_chapters", "1"].iter().map(|s| s.to_string()));
+    }
+    args.extend(["-c", "copy", output].iter().map(|s| s.to_string()));
+    args
+}
+
+/// Contents of a concat demuxer list file naming the given paths.
+pub fn concat_list(paths: &[String]) -> String {
+    let mut list = String::from("ffconcat version 1.0\n");
+    for path in paths {
+        let absolute = fs::canonicalize(path).map_or_else(|_| path.clone(), |p| p.to_string_lossy().into_owned());
+        list.push_str(&format!("file '{}'\n", absolute.replace('\'', "'\\''")));
+    }
+    list
+}
+
+/// Contents of an ffmetadata file with the given chapters.
+pub fn chapters_metadata(chapters: &[Chapter]) -> String {
+    let mut meta = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        let title: String = chapter.title.chars().flat_map(|c| if "=;#\\\n".contains(c) { vec!['\\', c] } else { vec![c] }).collect();
+        meta.push_str(&format!("[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n", chapter.start.as_millis(), chapter.end.as_millis(), title));
+    }
+    meta
+}
+
+fn input_args(pieces: &[Piece], metadata: Option<&str>) -> Vec<String> {
+    let mut args: Vec<String> = Vec::new();
+    for piece in pieces {
+        args.extend(["-ss".to_string(), piece.span.start.to_string(), "-t".to_string(), seconds(piece.span.duration()), "-i".to_string(), piece.input.to_string()]);
+    }
+    if let Some(metadata) = metadata {
+        args.extend(["-i".to_string(), metadata.to_string()]);
+    }
+    args
+}
+
+fn output_args(args: &mut Vec<String>, graph: String, has_audio: bool, chapters_input: Option<usize>, output: &str) {
+    args.extend(["-filter_complex".to_string(), graph, "-map".to_string(), "[v]".to_string()]);
+    if has_audio {
+        args.extend(["-map".to_string(), "[a]".to_string()]);
+    }
+    if let Some(i) = chapters_input {
+        args.extend(["-map_chapters".to_string(), i.to_string()]);
+    }
+    args.push(output.to_string());
+}
+
+/// Builds ffmpeg arguments that cut each piece and join them with the concat
+/// filter, re-encoding the result. With a non-zero `fade`, each piece fades
+/// in from and out to black (and silence). With `conform`, each piece is
+/// first scaled (and padded) to the same size and frame rate, and its audio
+/// resampled to the same format.
+pub fn filter_args(pieces: &[Piece], has_audio: bool, fade: Duration, conform: Option<&Conform>, metadata: Option<&str>, output: &str) -> Vec<String> {
+    let mut args = input_args(pieces, metadata);
+    let mut graph = String::new();
+    let mut streams = String::new();
+    let (conform_video, conform_audio) = match conform {
+        Some(c) => (
+            format!("scale={w}:{h}:force_original_aspect_ratio=decrease,pad={w}:{h}:(ow-iw)/2:(oh-ih)/2,setsar=1,fps={},", c.fps, w = c.width, h = c.height),
+            "aresample=48000,aformat=channel_layouts=stereo,".to_string(),
+        ),
+        None => (String::new(), String::new()),
+    };
+    for (i, piece) in pieces.iter().enumerate() {
+        let length = piece.span.duration();
+        // keep the fades from overlapping on very short pieces
+        let fade = fade.min(length / 2);
+        let out_start = seconds(length.saturating_sub(fade));
+        if fade.is_zero() {
+            graph.push_str(&format!("[{}:v]{}setpts=PTS-STARTPTS[v{}];", i, conform_video, i));
+        } else {
+            graph.push_str(&format!(
+                "[{i}:v]{c}setpts=PTS-STARTPTS,fade=t=in:st=0:d={d},fade=t=out:st={s}:d={d}[v{i}];",
+                i = i,
+                c = conform_video,
+                d = seconds(fade),
+                s = out_start
+            ));
+        }
+        streams.push_str(&format!("[v{}]", i));
+        if has_audio {
+            if fade.is_zero() {
+                graph.push_str(&format!("[{}:a]{}asetpts=PTS-STARTPTS[a{}];", i, conform_audio, i));
+            } else {
+                graph.push_str(&format!(
+                    "[{i}:a]{c}asetpts=PTS-STARTPTS,afade=t=in:st=0:d={d},afade=t=out:st={s}:d={d}[a{i}];",
+                    i = i,
+                    c = conform_audio,
+                    d = seconds(fade),
+                    s = out_start
+                ));
+            }
+            streams.push_str(&format!("[a{}]", i));
+        }
+    }
+    graph.push_str(&format!("{}concat=n={}:v=1:a={}[v]", streams, pieces.len(), has_audio as u8));
+    if has_audio {
+        graph.push_str("[a]");
+    }
+    output_args(&mut args, graph, has_audio, metadata.map(|_| pieces.len()), output);
+    args
+}
+
+/// Builds ffmpeg arguments that join the pieces with crossfades of the given
+/// length between consecutive pieces (xfade and acrossfade filters). The
+/// pieces' videos must share a resolution and frame rate.
+pub fn crossfade_args(pieces: &[Piece], has_audio: bool, crossfade: Duration, metadata: Option<&str>, output: &str) -> Vec<String> {
+    let mut args = input_args(pieces, metadata);
+    let mut graph = String::new();
+    for i in 0..pieces.len() {
+        graph.push_str(&format!("[{i}:v]setpts=PTS-STARTPTS[v{i}];", i = i));
+        if has_audio {
+            graph.push_str(&format!("[{i}:a]asetpts=PTS-STARTPTS[a{i}];", i = i));
+        }
+    }
+    let starts: Vec<String> = crossfade_starts(pieces, crossfade).iter().map(Timestamp::to_string).collect();
+    let (mut video, mut audio) = ("v0".to_string(), "a0".to_string());
+    for (i, piece) in pieces.iter().enumerate().skip(1) {
+        let d = crossfade.min(piece.span.duration() / 2).min(pieces[i - 1].span.duration() / 2);
+        let last = i == pieces.len() - 1;
+        let (v, a) = if last { ("v".to_string(), "a".to_string()) } else { (format!("xv{}", i), format!("xa{}", i)) };
+        graph.push_str(&format!("[{}][v{}]xfade=transition=fade:duration={}:offset={}[{}];", video, i, seconds(d), starts[i], v));
+        if has_audio {
+            graph.push_str(&format!("[{}][a{}]acrossfade=d={}[{}];", audio, i, seconds(d), a));
+        }
+        video = v;
+        audio = a;
+    }
+    if pieces.len() == 1 {
+        graph.push_str("[v0]null[v];");
+        if has_audio {
+            graph.push_str("[a0]anull[a];");
+        }
+    }
+    let graph = graph.trim_end_matches(';').to_string();
+    output_args(&mut args, graph, has_audio, metadata.map(|_| pieces.len()), output);
+    args
+}
+
+/// How long the fades at either side of a gap last, to keep the joins from
+/// clicking.
+const DECLICK: Duration = Duration::from_millis(10);
+
+/// Builds ffmpeg arguments that join the audio of the pieces, leaving out any
+/// video: with crossfades of the given length between consecutive pieces
+/// (acrossfade), or else with `gap` of silence between them, each piece
+/// fading in and out very briefly on either side of it. Every piece is
+/// resampled to the same format first, as the silence is.
+pub fn audio_args(pieces: &[Piece], crossfade: Duration, gap: Duration, metadata: Option<&str>, output: &str) -> Vec<String> {
+    let mut args = input_args(pieces, metadata);
+    let mut graph = String::new();
+    for (i, piece) in pieces.iter().enumerate() {
+        graph.push_str(&format!("[{}:a]aresample=48000,aformat=channel_layouts=stereo,asetpts=PTS-STARTPTS", i));
+        if !gap.is_zero() && crossfade.is_zero() {
+            let length = piece.span.duration();
+            let fade = DECLICK.min(length / 2);
+            graph.push_str(&format!(",afade=t=in:st=0:d={d},afade=t=out:st={s}:d={d}", d = seconds(fade), s = seconds(length.saturating_sub(fade))));
+        }
+        graph.push_str(&format!("[a{}];", i));
+    }
+    if !crossfade.is_zero() && pieces.len() > 1 {
+        let mut audio = "a0".to_string();
+        for (i, piece) in pieces.iter().enumerate().skip(1) {
+            let d = crossfade.min(piece.span.duration() / 2).min(pieces[i - 1].span.duration() / 2);
+            let a = if i == pieces.len() - 1 { "a".to_string() } else { format!("xa{}", i) };
+            graph.push_str(&format!("[{}][a{}]acrossfade=d={}[{}];", audio, i, seconds(d), a));
+            audio = a;
+        }
+    } else {
+        let mut streams = String::new();
+        for i in 0..pieces.len() {
+            if i > 0 && !gap.is_zero() {
+                graph.push_str(&format!("anullsrc=r=48000:cl=stereo,atrim=duration={}[g{}];", seconds(gap), i));
+                streams.push_str(&format!("[g{}]", i));
+            }
+            streams.push_str(&format!("[a{}]", i));
+        }
+        let n = if gap.is_zero() { pieces.len() } else { 2 * pieces.len() - 1 };
+        graph.push_str(&format!("{}concat=n={}:v=0:a=1[a]", streams, n));
+    }
+    let graph = graph.trim_end_matches(';').to_string();
+    args.extend(["-filter_complex".to_string(), graph, "-map".to_string(), "[a]".to_string()]);
+    if metadata.is_some() {
+        args.extend(["-map_chapters".to_string(), pieces.len().to_string()]);
+    }
+    args.push(output.to_string());
+    args
+}
+
+/// Where each piece starts in an output from [`audio_args`].
+pub fn audio_starts(pieces: &[Piece], crossfade: Duration, gap: Duration) -> Vec<Timestamp> {
+    let gap = if crossfade.is_zero() { gap.as_millis() as u64 } else { 0 };
+    crossfade_starts(pieces, crossfade).iter().enumerate().map(|(i, start)| Timestamp::from_millis(start.as_millis() + i as u64 * gap)).collect()
+}
+
+/// Where each piece starts in a crossfaded output.
+pub fn crossfade_starts(pieces: &[Piece], crossfade: Duration) -> Vec<Timestamp> {
+    let mut starts = Vec::new();
+    let mut t: u64 = 0;
+    for (i, piece) in pieces.iter().enumerate() {
+        if i > 0 {
+            let d = crossfade.min(piece.span.duration() / 2).min(pieces[i - 1].span.duration() / 2);
+            t = t.saturating_sub(d.as_millis() as u64);
+        }
+        starts.push(Timestamp::from_millis(t));
+        t += piece.span.duration().as_millis() as u64;
+    }
+    starts
+}
+
+/// A fresh path in the temporary directory for intermediate files.
+pub fn temp_path(name: &str) -> PathBuf {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNTER.fetch_add(1, Ordering::SeqCst);
+    env::temp_dir().join(format!("xclips-{}-{}-{}", process::id(), n, name))
+}
+
+fn seconds(d: Duration) -> String {
+    format!("{}.{:03}", d.as_secs(), d.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn piece(input: &str, start: u64, end: u64) -> Piece<'_> {
+        Piece { input, span: Span { start: Timestamp::from_millis(start), end: Timestamp::from_millis(end) } }
+    }
+
+    #[test]
+    fn audio_joins() {
+        let pieces = [piece("a.mp3", 0, 2000), piece("b.mp3", 1000, 4000)];
+        let graph = |args: Vec<String>| args[args.iter().position(|a| a == "-filter_complex").unwrap() + 1].clone();
+        let gapped = graph(audio_args(&pieces, Duration::ZERO, Duration::from_millis(500), None, "out.mp3"));
+        assert!(gapped.contains("afade=t=out:st=1.990:d=0.010[a0]"));
+        assert!(gapped.ends_with("anullsrc=r=48000:cl=stereo,atrim=duration=0.500[g1];[a0][g1][a1]concat=n=3:v=0:a=1[a]"));
+        let faded = graph(audio_args(&pieces, Duration::from_secs(1), Duration::ZERO, None, "out.mp3"));
+        assert!(faded.ends_with("[a0][a1]acrossfade=d=1.000[a]"));
+        assert!(!faded.contains("afade"));
+        let starts = |crossfade, gap| audio_starts(&pieces, crossfade, gap).iter().map(|t| t.as_millis()).collect::<Vec<_>>();
+        assert_eq!(starts(Duration::ZERO, Duration::from_millis(500)), [0, 2500]);
+        assert_eq!(starts(Duration::from_secs(1), Duration::ZERO), [0, 1000]);
+    }
+}
@@ -0,0 +1,113 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::join::{self, Chapter, Piece};
+use crate::job::Failure;
+use crate::{ffmpeg, parse_duration, probe, Span, Timestamp};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "join-files", bin_name = "xclips join-files")]
+pub struct JoinFilesOpt {
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: PathBuf,
+
+    /// Add a chapter for each file, titled with its name
+    #[structopt(long = "chapters")]
+    chapters: bool,
+
+    /// Fade each file in and out by this much (re-encodes)
+    #[structopt(long = "fade", default_value = "0s", parse(try_from_str = parse_duration))]
+    fade: Duration,
+
+    /// Crossfade consecutive files by this much (re-encodes)
+    #[structopt(long = "crossfade", default_value = "0s", parse(try_from_str = parse_duration))]
+    crossfade: Duration,
+
+    /// Put this much silence between files that are only audio (re-encodes)
+    #[structopt(long = "gap", default_value = "0s", parse(try_from_str = parse_duration), conflicts_with = "crossfade")]
+    gap: Duration,
+
+    /// Re-encode even when the files could be joined by copying streams
+    #[structopt(long = "reencode")]
+    reencode: bool,
+
+    #[structopt(name = "FILES", parse(from_os_str), required = true)]
+    files: Vec<PathBuf>,
+}
+
+/// Joins existing files into one, copying streams when they're compatible.
+pub fn run(opt: JoinFilesOpt) -> Result<()> {
+    let paths: Vec<String> = opt.files.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+    let durations: Vec<Duration> = opt
+        .files
+        .iter()
+        .map(|f| probe::duration(f).ok_or_else(|| XclipsError::Probe { what: "duration", path: f.clone() }))
+        .collect::<Result<_>>()?;
+    let pieces: Vec<Piece> = paths
+        .iter()
+        .zip(&durations)
+        .map(|(input, d)| Piece { input, span: Span { start: Timestamp::from_millis(0), end: Timestamp::from_millis(d.as_millis() as u64) } })
+        .collect();
+    let signatures: Vec<Option<Vec<String>>> = opt.files.iter().map(|f| probe::stream_signature(f)).collect();
+    let compatible = signatures[0].is_some() && signatures.iter().all(|s| *s == signatures[0]);
+    let has_audio = signatures.iter().all(|s| s.as_ref().is_some_and(|s| s.iter().any(|l| l.starts_with("audio") || l.contains(",audio"))));
+    let audio_only = has_audio && signatures.iter().all(|s| s.as_ref().is_some_and(|s| !s.iter().any(|l| l.starts_with("video") || l.contains(",video"))));
+    if !opt.gap.is_zero() && !audio_only {
+        return Err(XclipsError::Usage("--gap only joins files that are just audio".to_string()));
+    }
+    if !compatible && !opt.reencode && opt.fade.is_zero() && opt.crossfade.is_zero() {
+        eprintln!("files have different codecs or parameters, re-encoding to join them");
+    }
+
+    let mut temp_files: Vec<PathBuf> = Vec::new();
+    let metadata = if opt.chapters {
+        let starts = join::audio_starts(&pieces, opt.crossfade, opt.gap);
+        let chapters: Vec<Chapter> = pieces
+            .iter()
+            .zip(&starts)
+            .zip(&opt.files)
+            .map(|((piece, start), file)| Chapter {
+                title: file.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                start: *start,
+                end: Timestamp::from_millis(start.as_millis() + piece.span.duration().as_millis() as u64),
+            })
+            .collect();
+        let path = join::temp_path("chapters.txt");
+        write_temp(&path, &join::chapters_metadata(&chapters))?;
+        temp_files.push(path.clone());
+        Some(path.to_string_lossy().into_owned())
+    } else {
+        None
+    };
+
+    let output = opt.output.to_string_lossy();
+    let args = if audio_only && (!opt.crossfade.is_zero() || !opt.gap.is_zero()) {
+        join::audio_args(&pieces, opt.crossfade, opt.gap, metadata.as_deref(), &output)
+    } else if !opt.crossfade.is_zero() {
+        join::crossfade_args(&pieces, has_audio, opt.crossfade, metadata.as_deref(), &output)
+    } else if !compatible || opt.reencode || !opt.fade.is_zero() {
+        join::filter_args(&pieces, has_audio, opt.fade, None, metadata.as_deref(), &output)
+    } else {
+        let list = join::temp_path("concat.txt");
+        write_temp(&list, &join::concat_list(&paths))?;
+        temp_files.push(list.clone());
+        join::demuxer_args(&list.to_string_lossy(), metadata.as_deref(), &output)
+    };
+    let status = ffmpeg().args(&args).status();
+    for file in &temp_files {
+        let _ = fs::remove_file(file);
+    }
+    let status = status.map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+    if !status.success() {
+        return Err(XclipsError::Encode { output: output.into_owned(), failure: Failure::Ffmpeg });
+    }
+    Ok(())
+}
+
+fn write_temp(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).map_err(|err| XclipsError::io(format!("cannot write {}", path.display()), err))
+}
@@ -0,0 +1,84 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::probe;
+
+/// Returns the keyframe times (in milliseconds) of the file at `path`, using
+/// the on-disk cache when the file's size and modification time haven't
+/// changed since it was last indexed.
+pub fn index(path: &Path) -> Option<Vec<u64>> {
+    let key = cache_key(path);
+    let cache_file = key.as_ref().and_then(|key| Some(cache_dir()?.join(format!("{:016x}", fnv1a(key.as_bytes())))));
+    if let (Some(key), Some(cache_file)) = (&key, &cache_file) {
+        if let Some(times) = read_cache(cache_file, key) {
+            return Some(times);
+        }
+    }
+    let times = probe::keyframes(path)?;
+    if let (Some(key), Some(cache_file)) = (&key, &cache_file) {
+        if let Err(err) = write_cache(cache_file, key, &times) {
+            eprintln!("warning: cannot write keyframe cache {}: {}", cache_file.display(), err);
+        }
+    }
+    Some(times)
+}
+
+/// Returns the last keyframe at or before `ms`, if any.
+pub fn snap_back(keyframes: &[u64], ms: u64) -> Option<u64> {
+    match keyframes.binary_search(&ms) {
+        Ok(i) => Some(keyframes[i]),
+        Err(0) => None,
+        Err(i) => Some(keyframes[i - 1]),
+    }
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os("XDG_CACHE_HOME") {
+        return Some(PathBuf::from(dir).join("xclips"));
+    }
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache").join("xclips"))
+}
+
+/// Identifies a particular version of a file: its absolute path, size, and
+/// modification time.
+fn cache_key(path: &Path) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    let meta = fs::metadata(&path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("{} {} {}", meta.len(), mtime.as_nanos(), path.display()))
+}
+
+fn read_cache(cache_file: &Path, key: &str) -> Option<Vec<u64>> {
+    let contents = fs::read_to_string(cache_file).ok()?;
+    let mut lines = contents.lines();
+    if lines.next()? != key {
+        return None;
+    }
+    lines.map(|l| l.parse().ok()).collect()
+}
+
+fn write_cache(cache_file: &Path, key: &str, times: &[u64]) -> std::io::Result<()> {
+    if let Some(dir) = cache_file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut contents = String::from(key);
+    contents.push('\n');
+    for t in times {
+        contents.push_str(&t.to_string());
+        contents.push('\n');
+    }
+    fs::write(cache_file, contents)
+}
+
+/// 64-bit FNV-1a, used because it's stable across builds (unlike `std`'s
+/// default hasher), which matters for names that persist on disk.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
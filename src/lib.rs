@@ -0,0 +1,430 @@
+//! Cutting clips out of a video with ffmpeg, given a list of time spans.
+//!
+//! This is the library behind the `xclips` command. Besides the command
+//! itself, it offers the parts other tools may want without shelling out:
+//! parsing timestamps and spans ([`Timestamp`], [`Span`], [`parse_spans`],
+//! and [`TimestampFormat`] for custom notations),
+//! and planning the ffmpeg commands that cut them ([`ClipPlan`]).
+
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+mod archive;
+mod bundle;
+mod cache;
+#[doc(hidden)]
+pub mod cli;
+mod compare;
+mod config;
+mod csv;
+mod detect;
+mod error;
+mod existing;
+mod filters;
+mod formats;
+mod frames;
+mod glob;
+mod history;
+mod hook;
+mod input;
+mod job;
+mod join;
+mod join_files;
+mod json;
+mod keyframes;
+mod lint;
+mod notation;
+mod pick;
+mod plan;
+mod probe;
+mod queue;
+mod report;
+mod sample;
+mod screenshot;
+mod schedule;
+mod sequence;
+mod server;
+mod sha256;
+mod sidecar;
+mod srt;
+mod subs;
+mod template;
+mod timeline;
+mod trailer;
+mod transform;
+mod ui;
+mod upload;
+mod vars;
+#[cfg(feature = "web")]
+mod web;
+
+pub use crate::notation::TimestampFormat;
+pub use crate::plan::{ClipJob, ClipPlan};
+
+lazy_static! {
+    /// The ffmpeg to run, when it isn't the one on the PATH.
+    static ref FFMPEG: RwLock<Option<PathBuf>> = RwLock::new(None);
+}
+
+/// Why a timestamp, span, or other argument couldn't be parsed.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ParseErr(&'static str);
+
+impl fmt::Display for ParseErr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// A point in a video, to the millisecond, written like `1:02.5`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct Timestamp {
+    seconds: u32,
+    milliseconds: u32,
+}
+
+impl FromStr for Timestamp {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE_S_MS: Regex = Regex::new(r"^(\d+)\.?(\d{1,3})?$").unwrap();
+            static ref RE_M_S_MS: Regex = Regex::new(r"^(\d+):(\d{2})\.?(\d{1,3})?$").unwrap();
+            static ref RE_H_M_S_MS: Regex = Regex::new(r"^(\d+):(\d{2}):(\d{2})\.?(\d{1,3})?$").unwrap();
+        }
+        fn parse_ms<'a>(ms: Option<regex::Match<'a>>) -> u32 {
+            ms.map_or(0, |v| {
+                let n: u32  = v.as_str().parse().unwrap();
+                n * (10_u32.pow(3-v.range().len() as u32))
+            })
+        }
+        if let Some(t) = notation::parse(s) {
+            Ok(t)
+        } else if let Some(t) = frames::parse(s) {
+            t
+        } else if let Some(captures) = RE_S_MS.captures(s) {
+            Ok(Timestamp {
+                seconds: captures[1].parse().unwrap(),
+                milliseconds: parse_ms(captures.get(2)),
+            })
+        } else if let Some(captures) = RE_M_S_MS.captures(s) {
+            let m: u32 = captures[1].parse().unwrap();
+            let s: u32 = captures[2].parse().unwrap();
+            Ok(Timestamp {
+                seconds: 60 * m + s,
+                milliseconds: parse_ms(captures.get(3)),
+            })
+        } else if let Some(captures) = RE_H_M_S_MS.captures(s) {
+            let h: u32 = captures[1].parse().unwrap();
+            let m: u32 = captures[2].parse().unwrap();
+            let s: u32 = captures[3].parse().unwrap();
+            Ok(Timestamp {
+                seconds: 60*60*h + 60*m + s,
+                milliseconds: parse_ms(captures.get(4)),
+            })
+        } else {
+            Err(ParseErr("not a valid timestamp"))
+        }
+    }
+}
+
+impl Timestamp {
+    pub fn from_millis(ms: u64) -> Timestamp {
+        Timestamp {
+            seconds: (ms / 1000) as u32,
+            milliseconds: (ms % 1000) as u32,
+        }
+    }
+
+    pub fn as_millis(&self) -> u64 {
+        (self.seconds as u64) * 1000 + (self.milliseconds as u64)
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{:03}", self.seconds, self.milliseconds)
+    }
+}
+
+/// A stretch of a video, written like `1:02.5-1:10`, or as a start and a
+/// length like `1:02.5+7.5` (or `1:02.5+30s`); it never ends before it
+/// starts.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct Span {
+    start: Timestamp,
+    end: Timestamp,
+}
+
+impl FromStr for Span {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^(.*)-(.*)$").unwrap();
+        }
+        if let Some((start, length)) = s.rsplit_once('+') {
+            let start: Timestamp = start.parse()?;
+            let length = parse_duration(length).map_err(|_| ParseErr("not a valid length"))?;
+            return Ok(Span { start, end: Timestamp::from_millis(start.as_millis() + length.as_millis() as u64) });
+        }
+        let captures = match RE.captures(s) {
+            None => { return Err(ParseErr("doesn't contain a dash")) }
+            Some(c) => c
+        };
+        let start: Timestamp = captures[1].parse()?;
+        let end: Timestamp = captures[2].parse()?;
+        if start > end {
+            return Err(ParseErr("end is before start"))
+        }
+        Ok(Span{start, end})
+    }
+}
+
+impl Span {
+    pub fn new(start: Timestamp, end: Timestamp) -> Result<Span, ParseErr> {
+        if start > end {
+            return Err(ParseErr("end is before start"));
+        }
+        Ok(Span { start, end })
+    }
+
+    pub fn start(&self) -> Timestamp {
+        self.start
+    }
+
+    pub fn end(&self) -> Timestamp {
+        self.end
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.end.as_millis() - self.start.as_millis())
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Parses a duration given either as a timestamp (`1:30`, `0.5`) or as a
+/// number with a unit suffix (`500ms`, `0.5s`, `10m`, `2h`).
+pub fn parse_duration(s: &str) -> Result<Duration, ParseErr> {
+    lazy_static! {
+        static ref RE_UNIT: Regex = Regex::new(r"^(\d+(?:\.\d+)?)(ms|s|m|h)$").unwrap();
+    }
+    if let Some(captures) = RE_UNIT.captures(s) {
+        let n: f64 = captures[1].parse().unwrap();
+        let scale = match &captures[2] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            _ => 3600.0,
+        };
+        return Ok(Duration::from_millis((n * scale * 1000.0).round() as u64));
+    }
+    let t: Timestamp = s.parse().map_err(|_| ParseErr("not a valid duration"))?;
+    Ok(Duration::from_millis(t.as_millis()))
+}
+
+/// Parses a list of spans: one per line (blank lines are skipped), or a
+/// JSON array of spans, as `xclips -f` reads them. European-style decimal
+/// commas (`1:02,5`) are accepted. Spans without an end (`1:00-`) or
+/// counting back from it (`1:00-(-0:30)`) aren't, since where they end
+/// depends on the video.
+pub fn parse_spans(text: &str) -> Result<Vec<Span>, String> {
+    input::parse_spans(text, "spans", false)?
+        .into_iter()
+        .map(|e| match e.from_end {
+            from_end if from_end.end == Some(Duration::ZERO) => Err(format!("{}: the span has no end", e.origin)),
+            from_end if from_end.is_some() => Err(format!("{}: the span counts back from the end of the video", e.origin)),
+            _ => Ok(e.span),
+        })
+        .collect()
+}
+
+/// Quotes a string for safe use as a single word in a POSIX shell command.
+pub(crate) fn shell_quote(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c)) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+/// Splits a command line into words the way a POSIX shell would, minus
+/// expansions: whitespace separates words, quotes group them, and a backslash
+/// escapes the next character (outside single quotes).
+pub(crate) fn shell_words(s: &str) -> Result<Vec<String>, ParseErr> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(ParseErr("unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some(c) => word.extend(['\\', c]),
+                            None => return Err(ParseErr("unterminated double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(ParseErr("unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).push(chars.next().ok_or(ParseErr("nothing after a backslash"))?),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Runs the ffmpeg at `path` from now on, rather than the one on the PATH.
+pub(crate) fn use_ffmpeg(path: PathBuf) {
+    *FFMPEG.write().unwrap() = Some(path);
+}
+
+/// The ffmpeg that's run.
+pub(crate) fn ffmpeg_path() -> PathBuf {
+    FFMPEG.read().unwrap().clone().unwrap_or_else(|| PathBuf::from("ffmpeg"))
+}
+
+/// A command running ffmpeg (the one `--ffmpeg-path` names, if given).
+pub(crate) fn ffmpeg() -> Command {
+    Command::new(ffmpeg_path())
+}
+
+pub(crate) fn log10_ceil(mut n: usize) -> usize {
+    let mut digits = 1;
+    while n > 10 {
+        n /= 10;
+        digits += 1;
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(ms: u64) -> Timestamp {
+        Timestamp::from_millis(ms)
+    }
+
+    #[test]
+    fn timestamps() {
+        assert_eq!("5".parse(), Ok(ts(5000)));
+        assert_eq!("1.5".parse(), Ok(ts(1500)));
+        assert_eq!("1.05".parse(), Ok(ts(1050)));
+        assert_eq!("0.005".parse(), Ok(ts(5)));
+        assert_eq!("1:02.5".parse(), Ok(ts(62500)));
+        assert_eq!("1:02:03".parse(), Ok(ts(3723000)));
+        assert_eq!("90:00".parse(), Ok(ts(5400000)));
+    }
+
+    #[test]
+    fn bad_timestamps() {
+        for s in ["", "1:2", "1.2345", "a", "1:02:03:04", "-1"] {
+            assert_eq!(s.parse::<Timestamp>(), Err(ParseErr("not a valid timestamp")), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn timestamp_display() {
+        assert_eq!(ts(62500).to_string(), "62.500");
+        assert_eq!(ts(7).to_string(), "0.007");
+    }
+
+    #[test]
+    fn spans() {
+        let span: Span = "1:00-1:30.25".parse().unwrap();
+        assert_eq!((span.start(), span.end()), (ts(60000), ts(90250)));
+        assert_eq!(span.duration(), Duration::from_millis(30250));
+        assert_eq!(span.to_string(), "60.000-90.250");
+        assert_eq!("3-3".parse::<Span>().map(|s| s.duration()), Ok(Duration::ZERO));
+    }
+
+    #[test]
+    fn spans_with_lengths() {
+        assert_eq!("1:30+20".parse(), Span::new(ts(90000), ts(110000)));
+        assert_eq!("1:30+1:00.5".parse(), Span::new(ts(90000), ts(150500)));
+        assert_eq!("5+500ms".parse(), Span::new(ts(5000), ts(5500)));
+        assert_eq!(parse_spans("1:02,5 + 10").unwrap(), [Span::new(ts(62500), ts(72500)).unwrap()]);
+    }
+
+    #[test]
+    fn bad_spans() {
+        assert_eq!("1:00".parse::<Span>(), Err(ParseErr("doesn't contain a dash")));
+        assert_eq!("1:00+x".parse::<Span>(), Err(ParseErr("not a valid length")));
+        assert_eq!("5-4".parse::<Span>(), Err(ParseErr("end is before start")));
+        assert_eq!("x-4".parse::<Span>(), Err(ParseErr("not a valid timestamp")));
+        assert_eq!(Span::new(ts(2), ts(1)), Err(ParseErr("end is before start")));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("0.5s"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("10m"), Ok(Duration::from_secs(600)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+        assert_eq!(parse_duration("1:30"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("10 s"), Err(ParseErr("not a valid duration")));
+    }
+
+    #[test]
+    fn span_lists() {
+        let spans = parse_spans("0:01-0:02\n\n 1:00 - 1:02,5 \n").unwrap();
+        assert_eq!(spans, vec![Span::new(ts(1000), ts(2000)).unwrap(), Span::new(ts(60000), ts(62500)).unwrap()]);
+        assert!(parse_spans("0:01-0:02\nnope\n").unwrap_err().contains("nope"));
+    }
+
+    #[test]
+    fn open_spans() {
+        assert_eq!(parse_spans("-30\n- 1:00,5\n").unwrap(), [Span::new(ts(0), ts(30000)).unwrap(), Span::new(ts(0), ts(60500)).unwrap()]);
+        assert_eq!(parse_spans("1:00-").unwrap_err(), "spans:1: the span has no end");
+        assert!(parse_spans("-").is_err());
+        assert_eq!(parse_spans(r#"[{"end": 5}]"#).unwrap(), [Span::new(ts(0), ts(5000)).unwrap()]);
+    }
+
+    #[test]
+    fn json_span_lists() {
+        let spans = parse_spans(r#"["1-2", {"start": 3, "end": "0:04.5"}]"#).unwrap();
+        assert_eq!(spans, vec![Span::new(ts(1000), ts(2000)).unwrap(), Span::new(ts(3000), ts(4500)).unwrap()]);
+        let spans = parse_spans(r#"{"spans": [{"start": 1.25, "end": 2}]}"#).unwrap();
+        assert_eq!(spans, vec![Span::new(ts(1250), ts(2000)).unwrap()]);
+        assert!(parse_spans(r#"[{"start": 3, "end": 2}]"#).is_err());
+    }
+
+    #[test]
+    fn command_words() {
+        assert_eq!(shell_words("-map 0  -avoid_negative_ts make_zero").unwrap(), ["-map", "0", "-avoid_negative_ts", "make_zero"]);
+        assert_eq!(shell_words(r#"-metadata 'title=My Talk' -vf "scale=1280:-2" a\ b ''"#).unwrap(), ["-metadata", "title=My Talk", "-vf", "scale=1280:-2", "a b", ""]);
+        assert_eq!(shell_words(r#""say \"hi\" \n""#).unwrap(), [r#"say "hi" \n"#]);
+        assert!(shell_words("'open").is_err());
+        assert!(shell_words("").unwrap().is_empty());
+    }
+}
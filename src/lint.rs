@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::input::{read_spans, Entry, SpanSource};
+use crate::{parse_duration, probe, Timestamp};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "lint", bin_name = "xclips lint", setting = AppSettings::AllowNegativeNumbers)]
+pub struct LintOpt {
+    #[structopt(flatten)]
+    spans: SpanSource,
+
+    /// Report uncovered regions longer than this duration (e.g. 30s)
+    #[structopt(long = "max-gap", parse(try_from_str = parse_duration))]
+    max_gap: Option<Duration>,
+
+    #[structopt(name = "FILE", parse(from_os_str))]
+    file: PathBuf,
+}
+
+/// Checks the spans for overlaps, ordering problems, zero-length spans,
+/// duplicates, and (with `--max-gap`) large uncovered regions. Exits with a
+/// non-zero status if anything was reported.
+pub fn run(opt: LintOpt) -> Result<()> {
+    let entries = read_spans(&opt.spans, &opt.file)?;
+    let mut problems = 0;
+    let mut report = |origin: &str, msg: String| {
+        println!("{}: {}", origin, msg);
+        problems += 1;
+    };
+
+    for (i, entry) in entries.iter().enumerate() {
+        if entry.span.start == entry.span.end {
+            report(&entry.origin, format!("span {} has zero length", entry.span));
+        }
+        if i > 0 && entry.span < entries[i - 1].span {
+            report(&entry.origin, format!("span {} is out of order (comes before {})", entry.span, entries[i - 1].span));
+        }
+    }
+
+    let mut sorted: Vec<&Entry> = entries.iter().collect();
+    sorted.sort_by_key(|e| e.span);
+    // the entry that extends furthest so far, for overlap and gap detection
+    let mut furthest: Option<&Entry> = None;
+    for (i, entry) in sorted.iter().enumerate() {
+        if i > 0 && entry.span == sorted[i - 1].span {
+            report(&entry.origin, format!("span {} duplicates {}", entry.span, sorted[i - 1].origin));
+        } else if let Some(prev) = furthest {
+            if entry.span.start < prev.span.end {
+                report(&entry.origin, format!("span {} overlaps {} from {}", entry.span, prev.span, prev.origin));
+            } else {
+                check_gap(&mut report, &entry.origin, prev.span.end, entry.span.start, opt.max_gap);
+            }
+        } else {
+            check_gap(&mut report, &entry.origin, Timestamp::from_millis(0), entry.span.start, opt.max_gap);
+        }
+        if furthest.is_none_or(|f| entry.span.end > f.span.end) {
+            furthest = Some(entry);
+        }
+    }
+
+    if let (Some(max_gap), Some(last)) = (opt.max_gap, furthest) {
+        match opt.spans.duration.or_else(|| probe::duration(&opt.file)) {
+            Some(duration) => {
+                let end = Timestamp::from_millis(duration.as_millis() as u64);
+                check_gap(&mut report, &opt.file.display().to_string(), last.span.end, end, Some(max_gap));
+            }
+            None => eprintln!("warning: cannot determine duration of {}, skipping trailing gap check", opt.file.display()),
+        }
+    }
+
+    if problems > 0 {
+        return Err(XclipsError::Failed(format!("{} problem(s) found", problems)));
+    }
+    Ok(())
+}
+
+fn check_gap<F: FnMut(&str, String)>(report: &mut F, origin: &str, from: Timestamp, to: Timestamp, max_gap: Option<Duration>) {
+    if let Some(max_gap) = max_gap {
+        if to > from && Duration::from_millis(to.as_millis() - from.as_millis()) > max_gap {
+            report(origin, format!("uncovered gap {}-{} is longer than maximum gap", from, to));
+        }
+    }
+}
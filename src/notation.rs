@@ -0,0 +1,147 @@
+//! Custom timestamp notations, for span lists written by tools that don't
+//! use any of the usual ones. Once registered, a notation is tried before
+//! the built-in ones wherever a timestamp is parsed.
+//!
+//! A notation is written as a pattern, where `H`, `M`, and `S` stand for
+//! hours, minutes, and seconds, `f` for a digit of the fraction of a second,
+//! and anything else for itself. A doubled letter (`MM`) is exactly two
+//! digits; a single one is any number of them:
+//!
+//! ```text
+//! MM.SS,fff     02.05,250 is 2 minutes and 5.25 seconds
+//! H:MM:SS       1:02:03
+//! SSs           95s
+//! ```
+//!
+//! Anything more unusual can be a regular expression with named groups `h`,
+//! `m`, `s`, and `f`, each optional, matched against the whole timestamp.
+
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::Timestamp;
+
+lazy_static! {
+    static ref REGISTERED: RwLock<Vec<TimestampFormat>> = RwLock::new(Vec::new());
+}
+
+/// A custom way of writing timestamps.
+#[derive(Clone, Debug)]
+pub struct TimestampFormat {
+    re: Regex,
+}
+
+impl TimestampFormat {
+    /// A notation written as a pattern like `MM.SS,fff`.
+    pub fn pattern(pattern: &str) -> Result<TimestampFormat, String> {
+        let mut re = String::from("^");
+        let mut chars = pattern.chars().peekable();
+        let mut seen = String::new();
+        while let Some(c) = chars.next() {
+            let group = match c {
+                'H' => "h",
+                'M' => "m",
+                'S' => "s",
+                'f' => "f",
+                _ => {
+                    re.push_str(&regex::escape(&c.to_string()));
+                    continue;
+                }
+            };
+            if seen.contains(c) {
+                return Err(format!("{} appears twice in timestamp pattern {}", c, pattern));
+            }
+            seen.push(c);
+            let mut n = 1;
+            while chars.next_if_eq(&c).is_some() {
+                n += 1;
+            }
+            match (c, n) {
+                (_, 1) if c != 'f' => re.push_str(&format!(r"(?P<{}>\d+)", group)),
+                ('f', _) => re.push_str(&format!(r"(?P<f>\d{{{}}})", n)),
+                (_, 2) => re.push_str(&format!(r"(?P<{}>\d{{2}})", group)),
+                _ => return Err(format!("expected {} or {}{} in timestamp pattern {}", c, c, c, pattern)),
+            }
+        }
+        if seen.is_empty() {
+            return Err(format!("timestamp pattern {} has none of H, M, S, or f", pattern));
+        }
+        re.push('$');
+        Ok(TimestampFormat { re: Regex::new(&re).unwrap() })
+    }
+
+    /// A notation given as a regular expression with named groups `h`, `m`,
+    /// `s`, and `f`.
+    pub fn regex(re: &str) -> Result<TimestampFormat, String> {
+        let re = Regex::new(&format!("^(?:{})$", re)).map_err(|err| format!("invalid timestamp regex: {}", err))?;
+        if !re.capture_names().flatten().any(|name| ["h", "m", "s", "f"].contains(&name)) {
+            return Err("a timestamp regex needs at least one of the groups h, m, s, or f".to_string());
+        }
+        Ok(TimestampFormat { re })
+    }
+
+    /// Makes the notation one that timestamps are parsed in, from now on.
+    pub fn register(self) {
+        REGISTERED.write().unwrap().push(self);
+    }
+
+    fn parse(&self, s: &str) -> Option<Timestamp> {
+        let captures = self.re.captures(s)?;
+        let number = |name| captures.name(name).map_or(Some(0), |m| m.as_str().parse::<u64>().ok());
+        let seconds = number("h")? * 3600 + number("m")? * 60 + number("s")?;
+        // the fraction's first three digits are milliseconds
+        let millis = captures.name("f").map_or(0, |m| {
+            let digits: String = m.as_str().chars().chain("000".chars()).take(3).collect();
+            digits.parse::<u64>().unwrap()
+        });
+        Some(Timestamp::from_millis(seconds.checked_mul(1000)? + millis))
+    }
+}
+
+/// Parses `s` in the first registered notation that it matches.
+pub(crate) fn parse(s: &str) -> Option<Timestamp> {
+    REGISTERED.read().unwrap().iter().find_map(|format| format.parse(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(format: &TimestampFormat, s: &str) -> Option<u64> {
+        format.parse(s).map(|t| t.as_millis())
+    }
+
+    #[test]
+    fn patterns() {
+        let legacy = TimestampFormat::pattern("MM.SS,fff").unwrap();
+        assert_eq!(ms(&legacy, "02.05,250"), Some(125250));
+        assert_eq!(ms(&legacy, "2.05,250"), None);
+        assert_eq!(ms(&legacy, "02.05.250"), None);
+        let suffixed = TimestampFormat::pattern("SSs").unwrap();
+        assert_eq!(ms(&suffixed, "95s"), Some(95000));
+        assert_eq!(ms(&suffixed, "5s"), None);
+        let tenths = TimestampFormat::pattern("S.f").unwrap();
+        assert_eq!(ms(&tenths, "95.5"), Some(95500));
+        assert_eq!(ms(&tenths, "95.25"), None);
+        let hours = TimestampFormat::pattern("Hh MMm").unwrap();
+        assert_eq!(ms(&hours, "1h 02m"), Some(3720000));
+    }
+
+    #[test]
+    fn bad_patterns() {
+        assert!(TimestampFormat::pattern("MM:MM").is_err());
+        assert!(TimestampFormat::pattern("MMM").is_err());
+        assert!(TimestampFormat::pattern("::").is_err());
+    }
+
+    #[test]
+    fn regexes() {
+        let primes = TimestampFormat::regex(r#"(?:(?P<m>\d+)')?(?P<s>\d+)""#).unwrap();
+        assert_eq!(ms(&primes, "1'30\""), Some(90000));
+        assert_eq!(ms(&primes, "45\""), Some(45000));
+        assert!(TimestampFormat::regex(r"(\d+)").is_err());
+        assert!(TimestampFormat::regex(r"(?P<s>\d+").is_err());
+    }
+}
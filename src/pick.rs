@@ -0,0 +1,150 @@
+//! `--interactive`: a checklist of the planned clips, drawn on the terminal,
+//! for choosing which ones to cut.
+//!
+//! The list reads keys from and draws on `/dev/tty` directly, so it works
+//! even when spans come in on standard input.
+
+use crate::error::Result;
+
+const HELP: &str = "up/down or j/k: move   space: check   a: check all or none   enter: cut checked clips   q: quit";
+
+/// Shows `items`, all checked to begin with, and returns which ones are
+/// checked when the list is confirmed, or `None` if it's abandoned.
+#[cfg(unix)]
+pub fn choose(items: &[String]) -> Result<Option<Vec<bool>>> {
+    use std::fs::OpenOptions;
+    use std::io::{Read, Write};
+
+    use crate::error::XclipsError;
+
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .map_err(|err| XclipsError::io("--interactive needs a terminal", err))?;
+    let _raw = RawMode::enable(&tty).map_err(|err| XclipsError::io("cannot set up the terminal", err))?;
+    let height = (terminal_rows(&tty).unwrap_or(24) as usize).saturating_sub(3).max(1);
+
+    let mut checked = vec![true; items.len()];
+    let mut cursor = 0;
+    let mut top = 0;
+    let mut drawn = 0;
+    loop {
+        if cursor < top {
+            top = cursor;
+        } else if cursor >= top + height {
+            top = cursor + 1 - height;
+        }
+        let mut screen = String::new();
+        if drawn > 0 {
+            // back to the top of the last drawing, clearing it
+            screen.push_str(&format!("\r\x1b[{}A\x1b[J", drawn));
+        }
+        let count = checked.iter().filter(|&&c| c).count();
+        screen.push_str(&format!("{} of {} clips checked\r\n", count, items.len()));
+        for (i, item) in items.iter().enumerate().skip(top).take(height) {
+            let pointer = if i == cursor { '>' } else { ' ' };
+            let check = if checked[i] { 'x' } else { ' ' };
+            screen.push_str(&format!("{} [{}] {}\r\n", pointer, check, item));
+        }
+        screen.push_str(HELP);
+        drawn = items.len().min(height) + 1;
+        tty.write_all(screen.as_bytes()).and_then(|_| tty.flush()).map_err(|err| XclipsError::io("cannot draw the clip list", err))?;
+
+        let mut key = [0u8; 1];
+        tty.read_exact(&mut key).map_err(|err| XclipsError::io("cannot read from the terminal", err))?;
+        match key[0] {
+            b'k' => cursor = cursor.saturating_sub(1),
+            b'j' => cursor = (cursor + 1).min(items.len().saturating_sub(1)),
+            b'\x1b' => {
+                // arrow keys arrive as ESC [ A (up) and ESC [ B (down)
+                let mut rest = [0u8; 2];
+                if tty.read_exact(&mut rest).is_ok() && rest[0] == b'[' {
+                    match rest[1] {
+                        b'A' => cursor = cursor.saturating_sub(1),
+                        b'B' => cursor = (cursor + 1).min(items.len().saturating_sub(1)),
+                        _ => (),
+                    }
+                }
+            }
+            b' ' if !items.is_empty() => checked[cursor] = !checked[cursor],
+            b'a' => {
+                let all = checked.iter().all(|&c| c);
+                checked.iter_mut().for_each(|c| *c = !all);
+            }
+            b'\r' | b'\n' => {
+                let _ = tty.write_all(b"\r\n");
+                return Ok(Some(checked));
+            }
+            // q, or ctrl-c (which doesn't interrupt in raw mode)
+            b'q' | 3 => {
+                let _ = tty.write_all(b"\r\n");
+                return Ok(None);
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn choose(_items: &[String]) -> Result<Option<Vec<bool>>> {
+    Err(crate::error::XclipsError::Usage("--interactive is not supported on this platform".to_string()))
+}
+
+/// Puts the terminal in raw mode (keys arrive one at a time, unechoed) until
+/// dropped.
+#[cfg(unix)]
+struct RawMode {
+    fd: libc::c_int,
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl RawMode {
+    fn enable(tty: &std::fs::File) -> std::io::Result<RawMode> {
+        use std::os::unix::io::AsRawFd;
+
+        let fd = tty.as_raw_fd();
+        // SAFETY: tcgetattr and tcsetattr only read and write the termios
+        // structs passed to them, for a descriptor that stays open while
+        // `tty` does.
+        unsafe {
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut saved) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let mut raw = saved;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(RawMode { fd, saved })
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        // SAFETY: as in `enable`
+        unsafe {
+            libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn terminal_rows(tty: &std::fs::File) -> Option<u16> {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: TIOCGWINSZ fills in the winsize struct it's given.
+    unsafe {
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(tty.as_raw_fd(), libc::TIOCGWINSZ, &mut size) != 0 || size.ws_row == 0 {
+            return None;
+        }
+        Some(size.ws_row)
+    }
+}
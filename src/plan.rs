@@ -0,0 +1,145 @@
+//! The ffmpeg commands for cutting a list of spans, for tools that embed
+//! xclips rather than run it.
+//!
+//! A [`ClipPlan`] covers what a plain `xclips INPUT -f SPANS` run does: each
+//! span cut to its own file, named by the template, copying the streams (or
+//! converting them, for outputs like `.gif` or `.mp3`). Everything else the
+//! command can do (filters, bumpers, verification, and so on) is left to the
+//! command.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::formats::{self, Encoding};
+use crate::{ffmpeg, template};
+use crate::Span;
+
+pub struct ClipPlan {
+    /// The video to cut from.
+    pub input: PathBuf,
+    /// The path outputs are named after (as with `-o`), whose extension sets
+    /// their format; the input's by default.
+    pub output: Option<PathBuf>,
+    /// How to name each output, as with `--name-template`.
+    pub name_template: String,
+    /// Re-encode the clips (as with `--reencode`) rather than copy the
+    /// streams, for cuts that don't have to start on a keyframe.
+    pub reencode: bool,
+}
+
+/// One clip of a plan: the span, where it goes, and the ffmpeg arguments
+/// that cut it.
+#[derive(Clone, Debug)]
+pub struct ClipJob {
+    pub span: Span,
+    pub output: String,
+    pub args: Vec<String>,
+}
+
+impl ClipPlan {
+    pub fn new(input: impl Into<PathBuf>) -> ClipPlan {
+        ClipPlan { input: input.into(), output: None, name_template: template::DEFAULT.to_string(), reencode: false }
+    }
+
+    /// Plans a clip for each of `spans`, in order.
+    pub fn jobs(&self, spans: &[Span]) -> Result<Vec<ClipJob>, String> {
+        let output = self.output.as_deref().unwrap_or(&self.input);
+        let (base, ext) = split_output(&output.to_string_lossy()).ok_or("output filename does not have a file extension")?;
+        let input = self.input.to_string_lossy();
+        let input_stem = self.input.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let codec_args: Vec<String> = if self.reencode {
+            formats::reencoded_codec_args(&ext, &Encoding::default())
+        } else {
+            formats::codec_args(&ext).into_iter().map(String::from).collect()
+        };
+        spans
+            .iter()
+            .enumerate()
+            .map(|(index, span)| {
+                let vars = template::Vars { base: &base, ext: &ext, index, count: spans.len(), input_stem: &input_stem, span: *span, label: None, stable_id: None, scheme: template::NumberScheme::Digits };
+                let output = template::render(&self.name_template, &vars).map_err(|err| format!("cannot use name template {}: {}", self.name_template, err))?;
+                let mut args = vec!["-ss".to_string(), span.start.to_string(), "-i".to_string(), input.to_string(), "-t".to_string(), seconds_arg(span)];
+                args.extend(codec_args.iter().cloned());
+                args.push(output.clone());
+                Ok(ClipJob { span: *span, output, args })
+            })
+            .collect()
+    }
+}
+
+impl ClipJob {
+    /// The ffmpeg command that cuts the clip.
+    pub fn command(&self) -> Command {
+        let mut command = ffmpeg();
+        command.args(&self.args);
+        command
+    }
+}
+
+/// Splits an output path into everything before its extension and the
+/// extension, which outputs are named and formatted from.
+pub(crate) fn split_output(path: &str) -> Option<(String, String)> {
+    let (base, ext) = path.rsplit_once('.')?;
+    Some((base.to_string(), ext.to_string()))
+}
+
+/// A span's length as ffmpeg's `-t` takes it.
+fn seconds_arg(span: &Span) -> String {
+    let d = span.duration();
+    format!("{}.{:03}", d.as_secs(), d.subsec_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(list: &str) -> Vec<Span> {
+        crate::parse_spans(list).unwrap()
+    }
+
+    #[test]
+    fn copies_each_span() {
+        let jobs = ClipPlan::new("talk.mp4").jobs(&spans("1-2.5\n1:00-1:10")).unwrap();
+        let outputs: Vec<&str> = jobs.iter().map(|j| j.output.as_str()).collect();
+        assert_eq!(outputs, ["talk_clip0.mp4", "talk_clip1.mp4"]);
+        assert_eq!(jobs[0].args, ["-ss", "1.000", "-i", "talk.mp4", "-t", "1.500", "-c", "copy", "talk_clip0.mp4"]);
+        assert_eq!(jobs[1].args[1], "60.000");
+        assert_eq!(jobs[1].args[5], "10.000");
+    }
+
+    #[test]
+    fn single_clip_has_no_index() {
+        let jobs = ClipPlan::new("talk.mp4").jobs(&spans("1-2")).unwrap();
+        assert_eq!(jobs[0].output, "talk_clip.mp4");
+    }
+
+    #[test]
+    fn output_sets_name_and_format() {
+        let mut plan = ClipPlan::new("talk.mp4");
+        plan.output = Some("out/best.mp3".into());
+        plan.name_template = "{base}-{index}.{ext}".to_string();
+        let jobs = plan.jobs(&spans("1-2\n3-4")).unwrap();
+        assert_eq!(jobs[1].output, "out/best-1.mp3");
+        assert!(jobs[1].args.windows(2).any(|w| w == ["-c:a", "libmp3lame"]));
+    }
+
+    #[test]
+    fn reencodes() {
+        let mut plan = ClipPlan::new("talk.mp4");
+        plan.reencode = true;
+        let jobs = plan.jobs(&spans("1-2")).unwrap();
+        assert!(jobs[0].args.windows(2).any(|w| w == ["-c:v", "libx264"]));
+        assert!(!jobs[0].args.contains(&"copy".to_string()));
+    }
+
+    #[test]
+    fn needs_an_extension() {
+        assert!(ClipPlan::new("talk").jobs(&spans("1-2")).is_err());
+    }
+
+    #[test]
+    fn splits_outputs() {
+        assert_eq!(split_output("a/b.c.mp4"), Some(("a/b.c".to_string(), "mp4".to_string())));
+        assert_eq!(split_output("noext"), None);
+    }
+}
@@ -0,0 +1,233 @@
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+
+use crate::json::{self, Value};
+use crate::{ffmpeg, Span, Timestamp};
+
+/// Asks ffprobe for the duration of the media file at `path`.
+///
+/// Returns `None` if ffprobe can't be run or doesn't report a duration.
+pub fn duration(path: &Path) -> Option<Duration> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let seconds: f64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(Duration::from_millis((seconds * 1000.0).round() as u64))
+}
+
+/// Decodes `length` of the media file at `path` from `at`, throwing the
+/// result away, to see that it can be read there; if it can't, returns what
+/// ffmpeg said about it.
+pub fn decodes(path: &Path, at: Timestamp, length: Duration) -> Result<(), String> {
+    let output = ffmpeg()
+        .args(["-v", "error", "-nostdin", "-ss", &at.to_string(), "-i"])
+        .arg(path)
+        .args(["-t", &format!("{}.{:03}", length.as_secs(), length.subsec_millis()), "-f", "null", "-"])
+        .output()
+        .map_err(|_| "failed to spawn ffmpeg".to_string())?;
+    let log = String::from_utf8_lossy(&output.stderr);
+    match log.lines().find(|l| !l.trim().is_empty()) {
+        Some(line) => Err(line.trim().to_string()),
+        None if !output.status.success() => Err("ffmpeg command returned non-zero exit status".to_string()),
+        None => Ok(()),
+    }
+}
+
+/// Asks ffprobe for the codec type (`video`, `audio`, `subtitle`, ...) of
+/// each stream in the media file at `path`.
+pub fn stream_types(path: &Path) -> Option<Vec<String>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "stream=codec_type", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+}
+
+/// Asks ffprobe whether the media file at `path` has video, not counting
+/// cover art (which audio files carry as a video stream of one picture).
+pub fn has_video(path: &Path) -> Option<bool> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "stream=codec_type:stream_disposition=attached_pic", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    Some(text.lines().map(|l| l.trim().split(',')).any(|mut fields| fields.next() == Some("video") && fields.next() != Some("1")))
+}
+
+/// Asks ffprobe for the codec of the first audio stream in the media file at
+/// `path`, like `mp3` or `flac`.
+pub fn audio_codec(path: &Path) -> Option<String> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a:0", "-show_entries", "stream=codec_name", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|c| !c.is_empty())
+}
+
+/// Asks ffprobe for the codecs of the video and audio streams in the media
+/// file at `path`, like `h264` or `aac`.
+pub fn codecs(path: &Path) -> Option<Vec<String>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "stream=codec_type,codec_name", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    // csv output keeps ffprobe's own order of the fields, name first
+    let streams = text.lines().filter_map(|l| l.trim().split_once(','));
+    Some(streams.filter(|(_, kind)| *kind == "video" || *kind == "audio").map(|(codec, _)| codec.to_string()).collect())
+}
+
+/// Asks ffprobe for the presentation time of every keyframe in the first
+/// video stream, in milliseconds. This reads packet headers rather than
+/// decoding, but still has to scan the whole file.
+pub fn keyframes(path: &Path) -> Option<Vec<u64>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries", "packet=pts_time,flags", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut times: Vec<u64> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (pts, flags) = line.split_once(',')?;
+            if !flags.contains('K') {
+                return None;
+            }
+            let seconds: f64 = pts.trim().parse().ok()?;
+            Some((seconds * 1000.0).round() as u64)
+        })
+        .collect();
+    times.sort_unstable();
+    times.dedup();
+    Some(times)
+}
+
+/// Describes the streams of the media file at `path` (codec, dimensions,
+/// pixel format, sample rate, channels), one string per stream. Files with
+/// equal descriptions can be joined without re-encoding.
+pub fn stream_signature(path: &Path) -> Option<Vec<String>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "stream=codec_type,codec_name,width,height,pix_fmt,sample_rate,channels", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).lines().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect())
+}
+
+/// Asks ffprobe for the color primaries, transfer characteristics, and color
+/// space of the first video stream, in the names ffmpeg's options use. Any
+/// the file doesn't record are `None`.
+pub fn color(path: &Path) -> Option<[Option<String>; 3]> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=color_primaries,color_transfer,color_space", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut fields = text.lines().next().unwrap_or_default().split(',').map(|f| {
+        let f = f.trim();
+        (!f.is_empty() && f != "unknown").then(|| f.to_string())
+    });
+    Some([fields.next().flatten(), fields.next().flatten(), fields.next().flatten()])
+}
+
+/// Asks ffprobe for the width, height, and frame rate (like `30000/1001`) of
+/// the first video stream.
+pub fn video_format(path: &Path) -> Option<(u32, u32, String)> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0", "-show_entries", "stream=width,height,r_frame_rate", "-of", "csv=p=0"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut fields = text.lines().next()?.split(',').map(str::trim);
+    let width = fields.next()?.parse().ok()?;
+    let height = fields.next()?.parse().ok()?;
+    let fps = fields.next().filter(|f| !f.is_empty() && *f != "0/0")?.to_string();
+    Some((width, height, fps))
+}
+
+/// The language and title tags of an audio stream.
+#[derive(Clone, Debug, Default)]
+pub struct AudioTags {
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+/// Asks ffprobe for the tags of each audio stream of the media file at
+/// `path`, in order.
+pub fn audio_tags(path: &Path) -> Option<Vec<AudioTags>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "a", "-show_entries", "stream=index:stream_tags=language,title", "-of", "json"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let doc = json::parse(&String::from_utf8_lossy(&output.stdout)).ok()?;
+    let tag = |stream: &Value, key: &str| stream.get("tags")?.get(key)?.as_str().filter(|t| !t.is_empty()).map(String::from);
+    Some(doc.get("streams")?.as_array()?.iter().map(|s| AudioTags { language: tag(s, "language"), title: tag(s, "title") }).collect())
+}
+
+/// Asks ffprobe for the chapters of the media file at `path`: each one's
+/// span, and its title if it has one.
+pub fn chapters(path: &Path) -> Option<Vec<(Span, Option<String>)>> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_chapters", "-of", "json"])
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let doc = json::parse(&String::from_utf8_lossy(&output.stdout)).ok()?;
+    // times are strings of seconds, like "62.500000"
+    let time = |chapter: &Value, key: &str| {
+        let seconds: f64 = chapter.get(key)?.as_str()?.parse().ok()?;
+        Some(Timestamp::from_millis((seconds.max(0.0) * 1000.0).round() as u64))
+    };
+    doc.get("chapters")?
+        .as_array()?
+        .iter()
+        .map(|chapter| {
+            let span = Span::new(time(chapter, "start_time")?, time(chapter, "end_time")?).ok()?;
+            let title = chapter.get("tags").and_then(|t| t.get("title")).and_then(Value::as_str).filter(|t| !t.is_empty()).map(String::from);
+            Some((span, title))
+        })
+        .collect()
+}
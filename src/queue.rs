@@ -0,0 +1,203 @@
+//! A persistent job queue kept in an SQLite database, so that large batches
+//! can be stopped and resumed, worked on from several sessions at once, and
+//! inspected while they run.
+//!
+//! The database is accessed through the `sqlite3` command-line shell, the
+//! same way media is handled through `ffmpeg` and `ffprobe`.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::job::{Job, RunOptions};
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS jobs (
+    id INTEGER PRIMARY KEY,
+    output TEXT NOT NULL UNIQUE,
+    span TEXT NOT NULL,
+    args TEXT NOT NULL,
+    watch_errors INTEGER NOT NULL,
+    expected_streams TEXT NOT NULL,
+    fallback TEXT,
+    state TEXT NOT NULL DEFAULT 'pending',
+    message TEXT,
+    updated TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);";
+
+// separators used by the shell's ascii mode, and for lists within a column
+const COLUMN_SEP: char = '\x1f';
+const ROW_SEP: char = '\x1e';
+const LIST_SEP: char = '\x1d';
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "queue", bin_name = "xclips queue")]
+pub enum QueueOpt {
+    /// Show how many jobs are in each state, and why any failed
+    Status {
+        #[structopt(name = "DB", parse(from_os_str))]
+        db: PathBuf,
+    },
+    /// Work through the pending jobs
+    Run {
+        #[structopt(name = "DB", parse(from_os_str))]
+        db: PathBuf,
+    },
+    /// Return running (interrupted) and failed jobs to the pending state
+    Reset {
+        #[structopt(name = "DB", parse(from_os_str))]
+        db: PathBuf,
+    },
+}
+
+pub fn run(opt: QueueOpt) -> Result<()> {
+    match opt {
+        QueueOpt::Status { db } => {
+            let rows = query(&db, "SELECT state, COUNT(*) FROM jobs GROUP BY state ORDER BY state;")?;
+            for row in rows {
+                println!("{:>8} {}", row[1], row[0]);
+            }
+            for row in query(&db, "SELECT output, span, message FROM jobs WHERE state = 'failed' ORDER BY id;")? {
+                println!("failed: {} ({}): {}", row[0], row[1], row[2]);
+            }
+        }
+        QueueOpt::Run { db } => {
+            let failed = work(&db, &RunOptions::default())?;
+            if failed > 0 {
+                return Err(XclipsError::Failed(format!("{} job(s) failed", failed)));
+            }
+        }
+        QueueOpt::Reset { db } => {
+            query(&db, "UPDATE jobs SET state = 'pending', message = NULL WHERE state IN ('running', 'failed');")?;
+        }
+    }
+    Ok(())
+}
+
+/// Adds jobs to the queue, skipping any whose output is already queued (so
+/// re-running the same command resumes rather than duplicates the batch).
+pub fn add(db: &Path, jobs: &[Job]) -> Result<()> {
+    upgrade(db)?;
+    let mut script = String::from("BEGIN;\n");
+    for job in jobs {
+        script.push_str(&format!(
+            "INSERT OR IGNORE INTO jobs (output, span, args, watch_errors, expected_streams, fallback) VALUES ({}, {}, {}, {}, {}, {});\n",
+            quote(&job.output),
+            quote(&job.span.to_string()),
+            quote(&join(&job.args)),
+            job.watch_errors as u8,
+            quote(&join(&job.expected_streams)),
+            job.fallback.as_ref().map_or("NULL".to_string(), |args| quote(&join(args))),
+        ));
+    }
+    script.push_str("COMMIT;");
+    query(db, &script)?;
+    Ok(())
+}
+
+/// The outputs of every job in the queue, whatever its state.
+pub fn outputs(db: &Path) -> Result<Vec<String>> {
+    Ok(query(db, "SELECT output FROM jobs;")?.into_iter().filter_map(|row| row.into_iter().next()).collect())
+}
+
+/// Claims and executes pending jobs one at a time until none are left,
+/// returning the number that failed.
+pub fn work(db: &Path, options: &RunOptions) -> Result<usize> {
+    upgrade(db)?;
+    // an output already there was left by this queue, by a job that was
+    // interrupted or failed, since `add` is only given new ones
+    let options = &RunOptions { overwrite: true, ..*options };
+    let mut failed = 0;
+    loop {
+        let rows = query(
+            db,
+            "UPDATE jobs SET state = 'running', updated = CURRENT_TIMESTAMP
+             WHERE id = (SELECT id FROM jobs WHERE state = 'pending' ORDER BY id LIMIT 1)
+             RETURNING id, output, span, args, watch_errors, expected_streams, fallback;",
+        )?;
+        let row = match rows.into_iter().next() {
+            None => return Ok(failed),
+            Some(row) => row,
+        };
+        let span = row[2].parse().map_err(|_| XclipsError::Parse(format!("corrupt job {} in queue: bad span {}", row[0], row[2])))?;
+        let job = Job {
+            output: row[1].clone(),
+            span,
+            args: split(&row[3]),
+            watch_errors: row[4] == "1",
+            expected_streams: split(&row[5]),
+            segment: None,
+            poster: None,
+            bumpers: None,
+            shrink: None,
+            report: None,
+            // NULL comes back empty, and a fallback never is
+            fallback: Some(split(&row[6])).filter(|args| !args.is_empty()),
+        };
+        let (state, message) = match job.execute(options) {
+            Ok(_) => ("done", "NULL".to_string()),
+            Err(failure) => {
+                eprintln!("warning: {} ({}): {}", job.output, job.span, failure);
+                failed += 1;
+                ("failed", quote(&failure.to_string()))
+            }
+        };
+        query(
+            db,
+            &format!("UPDATE jobs SET state = '{}', message = {}, updated = CURRENT_TIMESTAMP WHERE id = {};", state, message, row[0]),
+        )?;
+    }
+}
+
+/// Adds the columns later versions keep to a queue made before them.
+fn upgrade(db: &Path) -> Result<()> {
+    let columns = query(db, "SELECT COUNT(*) FROM pragma_table_info('jobs') WHERE name = 'fallback';")?;
+    if columns.first().is_some_and(|row| row[0] == "0") {
+        query(db, "ALTER TABLE jobs ADD COLUMN fallback TEXT;")?;
+    }
+    Ok(())
+}
+
+/// Runs an SQL script against the database (creating the schema if needed)
+/// and returns the rows it produces.
+fn query(db: &Path, script: &str) -> Result<Vec<Vec<String>>> {
+    let mut child = Command::new("sqlite3")
+        .args(["-batch", "-ascii"])
+        .arg(db)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|_| XclipsError::Spawn("sqlite3".to_string()))?;
+    let input = format!(".timeout 10000\n{}\n{}\n", SCHEMA, script);
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(input.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|err| XclipsError::io("failed to wait for sqlite3", err))?;
+    if !output.status.success() {
+        return Err(XclipsError::Command(format!("cannot update queue {}: {}", db.display(), String::from_utf8_lossy(&output.stderr).trim())));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split(ROW_SEP)
+        .filter(|row| !row.is_empty())
+        .map(|row| row.split(COLUMN_SEP).map(String::from).collect())
+        .collect())
+}
+
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn join(items: &[String]) -> String {
+    items.join(&LIST_SEP.to_string())
+}
+
+fn split(s: &str) -> Vec<String> {
+    if s.is_empty() {
+        Vec::new()
+    } else {
+        s.split(LIST_SEP).map(String::from).collect()
+    }
+}
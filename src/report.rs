@@ -0,0 +1,102 @@
+//! Machine-readable reports of a run (`--json`), which can also be fed back
+//! in with `--from-report` to redo part of a batch.
+//!
+//! ```text
+//! {
+//!   "input": "talk.mp4",
+//!   "clips": [
+//!     {"index": 0, "start": 1.5, "end": 3.0, "label": null, "ext": null,
+//!      "output": "talk_clip0.mp4", "status": "ok", "error": null,
+//!      "reencoded": false},
+//!     ...
+//!   ]
+//! }
+//! ```
+//!
+//! A clip's status is `ok`, `failed`, or `pending` (not attempted because an
+//! earlier clip stopped the run). `reencoded` is whether the clip had to be
+//! re-encoded because copying its streams failed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, XclipsError};
+use crate::input::Entry;
+use crate::json::{self, Value};
+use crate::{Span, Timestamp};
+
+/// The outcome of one planned clip.
+pub struct Record<'a> {
+    pub entry: &'a Entry,
+    pub output: &'a str,
+    pub status: &'static str,
+    pub error: Option<String>,
+    /// With `--upload`, "ok" or "failed" (and why in `upload_error`).
+    pub upload: Option<&'static str>,
+    pub upload_error: Option<String>,
+    /// The clip was re-encoded after copying its streams failed.
+    pub reencoded: bool,
+}
+
+pub fn write(path: &Path, input: &Path, records: &[Record]) -> Result<()> {
+    let clips: Vec<Value> = records
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            json::object([
+                ("index", (i as u64).into()),
+                ("start", seconds(r.entry.span.start).into()),
+                ("end", seconds(r.entry.span.end).into()),
+                ("label", r.entry.label.clone().into()),
+                ("ext", r.entry.ext.clone().into()),
+                ("output", r.output.into()),
+                ("status", r.status.into()),
+                ("error", r.error.clone().into()),
+                ("reencoded", r.reencoded.into()),
+                ("upload", r.upload.into()),
+                ("upload_error", r.upload_error.clone().into()),
+            ])
+        })
+        .collect();
+    let doc = json::object([("input", input.to_string_lossy().into_owned().into()), ("clips", Value::Array(clips))]);
+    fs::write(path, format!("{}\n", doc)).map_err(|err| XclipsError::io(format!("cannot write report {}", path.display()), err))
+}
+
+fn seconds(t: Timestamp) -> f64 {
+    t.as_millis() as f64 / 1000.0
+}
+
+/// A clip read back from a report.
+pub struct PastClip {
+    pub entry: Entry,
+    pub status: String,
+}
+
+/// Reads a report written by [`write`], returning its input file and clips.
+pub fn read(path: &Path) -> Result<(PathBuf, Vec<PastClip>)> {
+    let fail = |msg: &str| XclipsError::Parse(format!("cannot read report {}: {}", path.display(), msg));
+    let text = fs::read_to_string(path).map_err(|err| XclipsError::io(format!("cannot read report {}", path.display()), err))?;
+    let doc = json::parse(&text).map_err(|err| fail(&err))?;
+    let input = doc.get("input").and_then(Value::as_str).ok_or_else(|| fail("missing input"))?;
+    let clips = doc.get("clips").and_then(Value::as_array).ok_or_else(|| fail("missing clips"))?;
+    let clips = clips
+        .iter()
+        .map(|clip| {
+            let time = |key: &str| match clip.get(key) {
+                Some(Value::Number(n)) if *n >= 0.0 => Ok(Timestamp::from_millis((n * 1000.0).round() as u64)),
+                _ => Err(fail(&format!("clip is missing {}", key))),
+            };
+            let index = match clip.get("index") {
+                Some(Value::Number(n)) => *n as u64,
+                _ => return Err(fail("clip is missing index")),
+            };
+            let mut entry = Entry::new(format!("{} clip {}", path.display(), index), Span { start: time("start")?, end: time("end")? });
+            entry.label = clip.get("label").and_then(Value::as_str).map(String::from);
+            entry.ext = clip.get("ext").and_then(Value::as_str).map(String::from);
+            entry.output = clip.get("output").and_then(Value::as_str).map(String::from);
+            let status = clip.get("status").and_then(Value::as_str).unwrap_or("pending").to_string();
+            Ok(PastClip { entry, status })
+        })
+        .collect::<Result<_>>()?;
+    Ok((PathBuf::from(input), clips))
+}
@@ -0,0 +1,47 @@
+use std::time::Duration;
+
+use crate::{Span, Timestamp};
+
+/// Spreads `n` spans of the given length evenly over a file of the given
+/// duration, each centered in its share of the file.
+pub fn even(duration: Duration, n: usize, length: Duration) -> Vec<Span> {
+    let total = duration.as_millis() as u64;
+    let length = (length.as_millis() as u64).min(total);
+    (0..n as u64)
+        .map(|k| {
+            let center = total * (2 * k + 1) / (2 * n as u64);
+            let start = center.saturating_sub(length / 2).min(total - length);
+            Span { start: Timestamp::from_millis(start), end: Timestamp::from_millis(start + length) }
+        })
+        .collect()
+}
+
+/// Picks `n` spans of the given length at random positions within a file of
+/// the given duration. The same seed always gives the same spans.
+pub fn random(duration: Duration, n: usize, length: Duration, seed: u64) -> Vec<Span> {
+    let total = duration.as_millis() as u64;
+    let length = (length.as_millis() as u64).min(total);
+    let mut rng = SplitMix64(seed);
+    let mut spans: Vec<Span> = (0..n)
+        .map(|_| {
+            let start = rng.next() % (total - length + 1);
+            Span { start: Timestamp::from_millis(start), end: Timestamp::from_millis(start + length) }
+        })
+        .collect();
+    spans.sort();
+    spans
+}
+
+/// A tiny, well-distributed PRNG; its output for a given seed will never
+/// change, which keeps samples reproducible across versions.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
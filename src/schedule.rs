@@ -0,0 +1,108 @@
+//! `--start-at` and `--pause-between`: holding a batch back until a time of
+//! day, and spacing clips out.
+//!
+//! Waits are measured against the wall clock, checked every so often, rather
+//! than slept off in one go: a suspended machine doesn't count down a sleep,
+//! so a single long sleep would overshoot by however long the machine slept.
+
+use std::fmt;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Result, XclipsError};
+use crate::ParseErr;
+
+/// How often a wait looks at the clock.
+const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A local time of day, like `02:00`.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeOfDay {
+    hour: u32,
+    minute: u32,
+}
+
+impl FromStr for TimeOfDay {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let err = ParseErr("not a time of day like 02:00");
+        let (hour, minute) = s.split_once(':').ok_or(err.clone())?;
+        let hour: u32 = hour.parse().map_err(|_| err.clone())?;
+        let minute: u32 = minute.parse().map_err(|_| err.clone())?;
+        if hour > 23 || minute > 59 {
+            return Err(err);
+        }
+        Ok(TimeOfDay { hour, minute })
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute)
+    }
+}
+
+/// Waits until the next time the local clock reads `time` (today if that's
+/// still ahead, otherwise tomorrow).
+pub fn wait_until(time: TimeOfDay) -> Result<()> {
+    let deadline = next(time)?;
+    if let Ok(wait) = deadline.duration_since(SystemTime::now()) {
+        eprintln!("waiting until {} to start ({}m from now)", time, wait.as_secs().div_ceil(60));
+    }
+    sleep_until(deadline);
+    Ok(())
+}
+
+/// Waits for `duration` of wall-clock time.
+pub fn pause(duration: Duration) {
+    sleep_until(SystemTime::now() + duration);
+}
+
+fn sleep_until(deadline: SystemTime) {
+    while let Ok(left) = deadline.duration_since(SystemTime::now()) {
+        if left.is_zero() {
+            break;
+        }
+        thread::sleep(left.min(CHECK_INTERVAL));
+    }
+}
+
+/// The next moment the local clock reads `time`. This goes through mktime
+/// rather than adding up seconds, so it stays right across daylight saving
+/// changes.
+#[cfg(unix)]
+fn next(time: TimeOfDay) -> Result<SystemTime> {
+    // SAFETY: localtime_r fills in `tm` from `now`, and mktime only reads and
+    // normalizes the `tm` it's given.
+    let at = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return Err(XclipsError::Usage("cannot determine local time".to_string()));
+        }
+        tm.tm_hour = time.hour as libc::c_int;
+        tm.tm_min = time.minute as libc::c_int;
+        tm.tm_sec = 0;
+        tm.tm_isdst = -1;
+        let mut at = libc::mktime(&mut tm);
+        if at <= now {
+            tm.tm_mday += 1;
+            tm.tm_hour = time.hour as libc::c_int;
+            tm.tm_min = time.minute as libc::c_int;
+            tm.tm_isdst = -1;
+            at = libc::mktime(&mut tm);
+        }
+        at
+    };
+    if at == -1 {
+        return Err(XclipsError::Usage(format!("cannot work out when it will next be {}", time)));
+    }
+    Ok(SystemTime::UNIX_EPOCH + Duration::from_secs(at as u64))
+}
+
+#[cfg(not(unix))]
+fn next(_time: TimeOfDay) -> Result<SystemTime> {
+    Err(XclipsError::Usage("--start-at is not supported on this platform".to_string()))
+}
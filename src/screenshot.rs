@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use regex::Regex;
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::input::read_lines;
+use crate::job::Failure;
+use crate::{ffmpeg, template, Span, Timestamp};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "screenshot", bin_name = "xclips screenshot")]
+pub struct ScreenshotOpt {
+    /// File with one timestamp per line, or - for standard input
+    #[structopt(short = "f", long = "timestamps-file", parse(from_os_str))]
+    timestamps_file: Option<PathBuf>,
+
+    #[structopt(short = "t", long = "at")]
+    at: Vec<String>,
+
+    #[structopt(short = "o", long = "output")]
+    output: Option<PathBuf>,
+
+    /// Image format, used as the extension when -o isn't given (png, jpg, ...)
+    #[structopt(long = "format", default_value = "png")]
+    format: String,
+
+    /// Output filename template (see xclips --help for placeholders)
+    #[structopt(long = "name-template", default_value = "{base}_shot{index}.{ext}")]
+    name_template: String,
+
+    #[structopt(name = "FILE", parse(from_os_str))]
+    file: PathBuf,
+}
+
+/// Extracts a full-quality still at each timestamp.
+pub fn run(opt: ScreenshotOpt) -> Result<()> {
+    let mut times: Vec<Timestamp> = Vec::new();
+    if let Some(ref path) = opt.timestamps_file {
+        for line in read_lines(path)?.iter().filter(|l| !l.trim().is_empty()) {
+            times.push(parse_timestamp(line.trim())?);
+        }
+    }
+    for t in &opt.at {
+        times.push(parse_timestamp(t)?);
+    }
+    times.sort();
+
+    let input_file = opt.file.to_string_lossy().into_owned();
+    let input_stem = opt.file.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let (base, ext) = match opt.output {
+        Some(ref output) => {
+            let output = output.to_string_lossy();
+            let captures = Regex::new(r"^(.*)\.(.*)$")
+                .unwrap()
+                .captures(&output)
+                .ok_or_else(|| XclipsError::Usage("output filename does not have a file extension".to_string()))?;
+            (captures[1].to_string(), captures[2].to_string())
+        }
+        None => {
+            let base = opt.file.with_extension("").to_string_lossy().into_owned();
+            (base, opt.format.clone())
+        }
+    };
+
+    for (i, t) in times.iter().enumerate() {
+        let vars = template::Vars { base: &base, ext: &ext, index: i, count: times.len(), input_stem: &input_stem, span: Span::new(*t, *t).unwrap(), label: None, stable_id: None, scheme: template::NumberScheme::Digits };
+        let output_filename =
+            template::render(&opt.name_template, &vars).map_err(|err| XclipsError::Parse(format!("cannot use name template {}: {}", opt.name_template, err)))?;
+        // -q:v 1 asks for the best quality lossy formats (like jpg) can give
+        let status = ffmpeg()
+            .args(["-ss", &t.to_string(), "-i", &input_file, "-frames:v", "1", "-q:v", "1", &output_filename])
+            .status()
+            .map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+        if !status.success() {
+            return Err(XclipsError::Encode { output: output_filename, failure: Failure::Ffmpeg });
+        }
+    }
+    Ok(())
+}
+
+/// Extracts the first and last frames of `span` as `PREFIX.first.jpg` and
+/// `PREFIX.last.jpg`, to check where a clip will start and end.
+pub fn boundaries(input: &str, span: &Span, prefix: &str) -> Result<()> {
+    if span.start == span.end {
+        return Ok(());
+    }
+    // the last frame is whichever decodes last before the end, found by
+    // writing every frame of the last second over the same image
+    let tail = span.duration().min(Duration::from_secs(1));
+    let tail_start = Timestamp::from_millis(span.end.as_millis() - tail.as_millis() as u64);
+    let first = format!("{}.first.jpg", prefix);
+    let last = format!("{}.last.jpg", prefix);
+    let first_args = ["-ss", &span.start.to_string(), "-i", input, "-frames:v", "1"];
+    let tail = format!("{}.{:03}", tail.as_secs(), tail.subsec_millis());
+    let last_args = ["-ss", &tail_start.to_string(), "-i", input, "-t", &tail, "-update", "1"];
+    for (args, output) in [(&first_args[..], first), (&last_args[..], last)] {
+        let status = ffmpeg()
+            .args(["-v", "error", "-y"])
+            .args(args)
+            .args(["-an", "-q:v", "2", &output])
+            .status()
+            .map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+        if !status.success() {
+            return Err(XclipsError::Encode { output, failure: Failure::Ffmpeg });
+        }
+    }
+    Ok(())
+}
+
+fn parse_timestamp(s: &str) -> Result<Timestamp> {
+    s.parse().map_err(|_| XclipsError::Parse(format!("cannot parse {} as a timestamp", s)))
+}
@@ -0,0 +1,173 @@
+//! Image sequences as input, the way animation renders come out:
+//! `xclips frames_%05d.png --frame-rate 24 -c 1-3` makes `frames_clip.mp4`
+//! out of frames 24 through 71. The `%05d` (or `%d`) in the name stands for
+//! each frame's number, which needn't start from 0: the sequence is whatever
+//! files match, from the lowest number up to the first one missing.
+//!
+//! An image sequence has no frame rate of its own, so it needs
+//! `--frame-rate`, and each clip is read as the range of frames its span
+//! covers (the same frames `f24`-`f72` would name).
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::error::{Result, XclipsError};
+use crate::frames::FrameRate;
+use crate::{Span, Timestamp};
+
+/// What a sequence's frames can be, by extension.
+const IMAGE_EXTS: &[&str] = &["png", "jpg", "jpeg", "tif", "tiff", "bmp", "tga", "exr", "dpx"];
+
+/// A file name with a frame number in it, like `frames_%05d.png`.
+#[derive(PartialEq, Debug)]
+struct Pattern {
+    prefix: String,
+    /// How many digits the number is padded to with zeros, if any.
+    width: usize,
+    suffix: String,
+}
+
+impl Pattern {
+    fn parse(name: &str) -> Option<Pattern> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^(.*?)%(0\d+)?d(.*)$").unwrap();
+        }
+        let captures = RE.captures(name)?;
+        let width = captures.get(2).map_or(0, |w| w.as_str().parse().unwrap_or(0));
+        Some(Pattern { prefix: captures[1].to_string(), width, suffix: captures[3].to_string() })
+    }
+
+    /// The frame number of a file named `name`, if it's one of the frames.
+    fn number(&self, name: &str) -> Option<u64> {
+        let digits = name.strip_prefix(&self.prefix)?.strip_suffix(&self.suffix)?;
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let n: u64 = digits.parse().ok()?;
+        // only the one way the pattern writes each number
+        (format!("{:0width$}", n, width = self.width) == digits).then_some(n)
+    }
+}
+
+/// Whether `path` names an image sequence rather than a single file.
+pub fn is_sequence(path: &Path) -> bool {
+    let is_image = path.extension().is_some_and(|e| is_image(&e.to_string_lossy()));
+    is_image && path.file_name().and_then(|n| Pattern::parse(&n.to_string_lossy())).is_some()
+}
+
+/// Whether an output with the given extension is a still image (or a
+/// sequence of them), rather than a video.
+pub fn is_image(ext: &str) -> bool {
+    IMAGE_EXTS.contains(&ext.to_ascii_lowercase().as_str())
+}
+
+/// The name of a sequence with the frame number taken out, like `frames`
+/// for `frames_%05d.png`, for naming what's made from it.
+pub fn stem(path: &Path) -> String {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let stem = match Pattern::parse(&stem) {
+        Some(pattern) => format!("{}{}", pattern.prefix, pattern.suffix),
+        None => stem,
+    };
+    let stem = stem.trim_matches(|c: char| "_-. ".contains(c));
+    if stem.is_empty() { "frames" } else { stem }.to_string()
+}
+
+/// The video clips of a sequence go in by default, beside its frames.
+pub fn default_output(path: &Path) -> PathBuf {
+    path.with_file_name(format!("{}.mp4", stem(path)))
+}
+
+/// The frames of an image sequence, and the rate they play at.
+#[derive(Debug)]
+pub struct Sequence {
+    path: PathBuf,
+    first: u64,
+    frames: u64,
+    rate: FrameRate,
+}
+
+impl Sequence {
+    /// Finds the frames of the sequence `path` names.
+    pub fn open(path: &Path, rate: Option<FrameRate>) -> Result<Sequence> {
+        let rate = rate.ok_or_else(|| XclipsError::Usage(format!("{} is an image sequence, which needs a --frame-rate to play at", path.display())))?;
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let pattern = Pattern::parse(&name).ok_or_else(|| XclipsError::Usage(format!("{} is not an image sequence", path.display())))?;
+        let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let entries = fs::read_dir(dir).map_err(|err| XclipsError::io(format!("cannot list the frames of {}", path.display()), err))?;
+        let mut numbers: Vec<u64> = entries.filter_map(|e| e.ok()).filter_map(|e| pattern.number(&e.file_name().to_string_lossy())).collect();
+        numbers.sort_unstable();
+        let (first, frames) = run(&numbers).ok_or_else(|| XclipsError::Usage(format!("no frames of {} found", path.display())))?;
+        Ok(Sequence { path: path.to_path_buf(), first, frames, rate })
+    }
+
+    /// How long the sequence plays for.
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.rate.start_of(self.frames).map_or(u64::MAX, |end| end.as_millis()))
+    }
+
+    /// Input options reading the sequence from its first frame.
+    pub fn input_args(&self) -> Vec<String> {
+        self.args(self.first, None)
+    }
+
+    /// Input options reading just the frames of `span`.
+    pub fn span_args(&self, span: &Span) -> Vec<String> {
+        let (start, end) = (self.rate.frame_at(span.start), self.rate.frame_at(span.end));
+        self.args(self.first + start, self.rate.start_of(end - start))
+    }
+
+    fn args(&self, start_number: u64, length: Option<Timestamp>) -> Vec<String> {
+        let mut args = vec!["-framerate".to_string(), self.rate.to_string(), "-start_number".to_string(), start_number.to_string()];
+        if let Some(length) = length {
+            // the frame after the last is where the length runs out, so it
+            // isn't read
+            args.extend(["-t".to_string(), length.to_string()]);
+        }
+        args.extend(["-i".to_string(), self.path.to_string_lossy().into_owned()]);
+        args
+    }
+}
+
+/// The first of a sorted list of numbers and how many follow on from it
+/// without a gap.
+fn run(numbers: &[u64]) -> Option<(u64, u64)> {
+    let first = *numbers.first()?;
+    let frames = numbers.iter().enumerate().take_while(|&(i, &n)| n == first + i as u64).count();
+    Some((first, frames as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        let pattern = Pattern::parse("frames_%05d.png").unwrap();
+        assert_eq!(pattern, Pattern { prefix: "frames_".to_string(), width: 5, suffix: ".png".to_string() });
+        assert_eq!(pattern.number("frames_01001.png"), Some(1001));
+        assert_eq!(pattern.number("frames_123456.png"), Some(123456));
+        assert_eq!(pattern.number("frames_1001.png"), None);
+        assert_eq!(pattern.number("frames_01001.jpg"), None);
+        assert_eq!(Pattern::parse("shot%d.exr").unwrap().number("shot7.exr"), Some(7));
+        assert!(Pattern::parse("talk.mp4").is_none());
+        assert!(is_sequence(Path::new("renders/frames_%05d.png")));
+        assert!(!is_sequence(Path::new("talk_%d.mp4")));
+        assert_eq!(stem(Path::new("renders/frames_%05d.png")), "frames");
+        assert_eq!(stem(Path::new("%04d.png")), "frames");
+        assert_eq!(run(&[3, 4, 5, 7]), Some((3, 3)));
+    }
+
+    #[test]
+    fn spans_read_frame_ranges() {
+        let sequence = Sequence { path: PathBuf::from("f_%03d.png"), first: 1001, frames: 240, rate: "24".parse().unwrap() };
+        assert_eq!(sequence.duration(), Duration::from_secs(10));
+        let span: Span = "1-3".parse().unwrap();
+        assert_eq!(sequence.span_args(&span).join(" "), "-framerate 24 -start_number 1025 -t 2.000 -i f_%03d.png");
+        assert_eq!(sequence.input_args().join(" "), "-framerate 24 -start_number 1001 -i f_%03d.png");
+    }
+}
@@ -0,0 +1,438 @@
+//! `xclips serve`: a small HTTP API for submitting clipping jobs to the
+//! machine that has the footage.
+//!
+//! A job is a list of ordinary xclips command-line arguments, not a
+//! subcommand's. Jobs run one at a time, each in its own xclips process.
+//!
+//! ```text
+//! POST   /jobs               {"args": ["-c", "1:00-1:30", "in.mp4"]}
+//! GET    /jobs               list all jobs and their states
+//! GET    /jobs/ID            state, exit code, and log of one job
+//! GET    /jobs/ID/results    paths of the clips a job produced
+//! DELETE /jobs/ID            cancel a queued or running job
+//! ```
+//!
+//! Every request needs the token the server prints when it starts, as an
+//! `Authorization: Bearer TOKEN` header, and requests with a body need it to
+//! be `Content-Type: application/json`. Web pages in a browser can send
+//! requests to a local server too, but can't do either of those (or read the
+//! token), so they can't submit jobs. Even so, options that run commands of
+//! their own (`--hook`, `--spans-from-cmd`, `--detect-exec`, and
+//! `--ffmpeg-path`) are refused.
+
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use structopt::StructOpt;
+
+use crate::cli::SUBCOMMANDS;
+use crate::error::{self, XclipsError};
+use crate::json::{self, Value};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "serve", bin_name = "xclips serve")]
+pub struct ServeOpt {
+    /// Address to listen on; keep this local unless the network is trusted
+    #[structopt(long = "listen", default_value = "127.0.0.1:8080")]
+    listen: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum State {
+    Queued,
+    Running,
+    Done,
+    Failed,
+    Cancelled,
+}
+
+impl State {
+    fn name(self) -> &'static str {
+        match self {
+            State::Queued => "queued",
+            State::Running => "running",
+            State::Done => "done",
+            State::Failed => "failed",
+            State::Cancelled => "cancelled",
+        }
+    }
+}
+
+struct ServerJob {
+    id: u64,
+    args: Vec<String>,
+    state: State,
+    exit_code: Option<i32>,
+    log: String,
+    outputs: Vec<String>,
+    child: Option<Child>,
+}
+
+impl ServerJob {
+    fn summary(&self) -> Value {
+        json::object([("id", self.id.into()), ("state", self.state.name().into())])
+    }
+
+    fn details(&self) -> Value {
+        json::object([
+            ("id", self.id.into()),
+            ("state", self.state.name().into()),
+            ("args", self.args.clone().into()),
+            ("exit_code", self.exit_code.map(|c| c as f64).into()),
+            ("log", self.log.clone().into()),
+        ])
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    all: Vec<ServerJob>,
+    pending: VecDeque<u64>,
+}
+
+type Shared = Arc<(Mutex<Jobs>, Condvar)>;
+
+/// Options a job can't be given, since they have xclips run commands (or
+/// programs) other than ffmpeg.
+const REFUSED_OPTIONS: &[&str] = &["--hook", "--spans-from-cmd", "--detect-exec", "--ffmpeg-path"];
+
+/// The largest request body read, in bytes; bigger ones are answered with
+/// 413 without being read.
+pub const MAX_BODY: usize = 1 << 20;
+
+/// How long a client gets to send each part of its request before it's
+/// dropped, so one that stalls doesn't hold its connection open forever.
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub fn run(opt: ServeOpt) -> error::Result<()> {
+    let listener = TcpListener::bind(&opt.listen).map_err(|err| XclipsError::io(format!("cannot listen on {}", opt.listen), err))?;
+    let token = Arc::new(new_token());
+    eprintln!("listening on http://{}; send each request with the header Authorization: Bearer {}", opt.listen, token);
+    let shared: Shared = Arc::new((Mutex::new(Jobs::default()), Condvar::new()));
+    {
+        let shared = shared.clone();
+        thread::spawn(move || worker(shared));
+    }
+    for stream in listener.incoming().flatten() {
+        let (shared, token) = (shared.clone(), token.clone());
+        thread::spawn(move || {
+            if let Err(err) = handle(stream, &shared, &token) {
+                eprintln!("warning: error handling request: {}", err);
+            }
+        });
+    }
+    Err(XclipsError::Failed("stopped accepting connections".to_string()))
+}
+
+/// Runs queued jobs one at a time.
+fn worker(shared: Shared) {
+    let exe = env::current_exe().unwrap_or_else(|_| "xclips".into());
+    let (lock, cvar) = &*shared;
+    loop {
+        let (id, args) = {
+            let mut jobs = lock.lock().unwrap();
+            let id = loop {
+                match jobs.pending.pop_front() {
+                    Some(id) => break id,
+                    None => jobs = cvar.wait(jobs).unwrap(),
+                }
+            };
+            let job = &jobs.all[id as usize];
+            if job.state != State::Queued {
+                continue;
+            }
+            (id, job.args.clone())
+        };
+        let mut command = Command::new(&exe);
+        command.arg("--list-outputs").args(&args).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+        // in a process group of its own, so cancelling the job can stop the
+        // ffmpeg runs it started along with it
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut command, 0);
+        let child = command.spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) => {
+                let mut jobs = lock.lock().unwrap();
+                let job = &mut jobs.all[id as usize];
+                job.state = State::Failed;
+                job.log = format!("failed to spawn xclips: {}", err);
+                continue;
+            }
+        };
+        let readers = [
+            spawn_reader(child.stdout.take().map(|s| Box::new(s) as Box<dyn Read + Send>), shared.clone(), id, true),
+            spawn_reader(child.stderr.take().map(|s| Box::new(s) as Box<dyn Read + Send>), shared.clone(), id, false),
+        ];
+        {
+            let mut jobs = lock.lock().unwrap();
+            let job = &mut jobs.all[id as usize];
+            // it may have been cancelled while starting, before there was a
+            // child for the cancel to kill
+            if job.state == State::Cancelled {
+                kill(&mut child);
+            } else {
+                job.state = State::Running;
+            }
+            job.child = Some(child);
+        }
+        // poll rather than block in wait(), so a cancel can get at the child
+        let status = loop {
+            {
+                let mut jobs = lock.lock().unwrap();
+                let job = &mut jobs.all[id as usize];
+                match job.child.as_mut().map(|c| c.try_wait()) {
+                    Some(Ok(Some(status))) => break Some(status),
+                    Some(Ok(None)) => (),
+                    _ => break None,
+                }
+            }
+            thread::sleep(Duration::from_millis(100));
+        };
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+        let mut jobs = lock.lock().unwrap();
+        let job = &mut jobs.all[id as usize];
+        job.child = None;
+        job.exit_code = status.and_then(|s| s.code());
+        if job.state != State::Cancelled {
+            job.state = if status.is_some_and(|s| s.success()) { State::Done } else { State::Failed };
+        }
+    }
+}
+
+/// Stops a job's process, and on Unix everything it started.
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // the job leads its own process group, whose id is its pid
+        if unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } == 0 {
+            return;
+        }
+    }
+    let _ = child.kill();
+}
+
+/// Collects a child's output into its job: stdout lines are result paths
+/// (from `--list-outputs`), stderr is kept as the log.
+fn spawn_reader(stream: Option<Box<dyn Read + Send>>, shared: Shared, id: u64, outputs: bool) -> Option<thread::JoinHandle<()>> {
+    let stream = stream?;
+    Some(thread::spawn(move || {
+        for line in BufReader::new(stream).lines().map_while(Result::ok) {
+            let mut jobs = shared.0.lock().unwrap();
+            let job = &mut jobs.all[id as usize];
+            if outputs {
+                job.outputs.push(line);
+            } else {
+                job.log.push_str(&line);
+                job.log.push('\n');
+            }
+        }
+    }))
+}
+
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Whether the body was over `MAX_BODY`, and so left unread.
+    pub too_large: bool,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+pub fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Request> {
+    stream.get_ref().set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut line = String::new();
+    stream.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if stream.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let mut request = Request { method, path, headers, body: Vec::new(), too_large: false };
+    let length = request.header("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+    if length > MAX_BODY {
+        request.too_large = true;
+    } else {
+        request.body = vec![0; length];
+        stream.read_exact(&mut request.body)?;
+    }
+    Ok(request)
+}
+
+/// Writes a complete response; `extra_headers` are full header lines.
+pub fn respond_with(stream: &mut TcpStream, status: &str, content_type: &str, extra_headers: &[String], body: &[u8]) -> io::Result<()> {
+    write!(stream, "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, content_type, body.len())?;
+    for header in extra_headers {
+        write!(stream, "{}\r\n", header)?;
+    }
+    stream.write_all(b"\r\n")?;
+    stream.write_all(body)
+}
+
+pub fn respond(stream: &mut TcpStream, status: &str, body: &Value) -> io::Result<()> {
+    respond_with(stream, status, "application/json", &[], body.to_string().as_bytes())
+}
+
+fn error(msg: &str) -> Value {
+    json::object([("error", msg.into())])
+}
+
+/// A new secret for clients to prove they were given, in hex.
+pub fn new_token() -> String {
+    // each RandomState is keyed from the operating system's random numbers
+    (0..2).map(|_| format!("{:016x}", RandomState::new().build_hasher().finish())).collect()
+}
+
+/// Checks that a request comes from a client that was given `token`, and
+/// that any body it has is JSON, returning the status and error to answer
+/// with if not.
+pub fn authorize(request: &Request, token: &str) -> Result<(), (&'static str, &'static str)> {
+    let given = request.header("authorization").and_then(|a| a.strip_prefix("Bearer ")).unwrap_or_default();
+    // compared in full whatever the first difference, so timing doesn't
+    // give the token away
+    let matches = given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+    if !matches {
+        return Err(("401 Unauthorized", "expected the header Authorization: Bearer TOKEN, with the server's token"));
+    }
+    let json = request.header("content-type").is_some_and(|t| t.split(';').next().unwrap_or_default().trim().eq_ignore_ascii_case("application/json"));
+    if !json && (request.method == "POST" || !request.body.is_empty()) {
+        return Err(("415 Unsupported Media Type", "expected Content-Type: application/json"));
+    }
+    Ok(())
+}
+
+/// The first of `args` that a job can't be given, if any.
+fn refused_option(args: &[String]) -> Option<&str> {
+    args.iter().take_while(|a| *a != "--").map(String::as_str).find(|a| REFUSED_OPTIONS.iter().any(|o| a == o || a.strip_prefix(o).is_some_and(|rest| rest.starts_with('='))))
+}
+
+fn handle(stream: TcpStream, shared: &Shared, token: &str) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let request = read_request(&mut reader)?;
+    let mut stream = stream;
+    if request.too_large {
+        return respond(&mut stream, "413 Payload Too Large", &error("request body too large"));
+    }
+    if let Err((status, msg)) = authorize(&request, token) {
+        return respond(&mut stream, status, &error(msg));
+    }
+    // worked out under the lock, but sent after it's released, so a slow
+    // client doesn't hold up the worker or anyone else
+    let (status, body) = answer(&request, shared);
+    respond(&mut stream, status, &body)
+}
+
+fn answer(request: &Request, shared: &Shared) -> (&'static str, Value) {
+    let (lock, cvar) = &**shared;
+    let segments: Vec<&str> = request.path.trim_matches('/').split('/').collect();
+    let id: Option<u64> = segments.get(1).and_then(|s| s.parse().ok());
+    let mut jobs = lock.lock().unwrap();
+    let exists = id.is_some_and(|id| (id as usize) < jobs.all.len());
+    match (request.method.as_str(), segments.as_slice()) {
+        ("POST", ["jobs"]) => {
+            let args = json::parse(&String::from_utf8_lossy(&request.body))
+                .ok()
+                .and_then(|body| body.get("args")?.as_array()?.iter().map(|a| a.as_str().map(String::from)).collect::<Option<Vec<_>>>());
+            match args {
+                None => ("400 Bad Request", error("expected {\"args\": [strings]}")),
+                Some(args) if args.first().is_some_and(|a| SUBCOMMANDS.contains(&a.as_str())) => {
+                    ("400 Bad Request", error("jobs take ordinary xclips arguments, not a subcommand"))
+                }
+                Some(args) if refused_option(&args).is_some() => {
+                    let msg = format!("{} runs commands, which jobs aren't allowed to", refused_option(&args).unwrap_or_default());
+                    ("403 Forbidden", error(&msg))
+                }
+                Some(args) => {
+                    let id = jobs.all.len() as u64;
+                    jobs.all.push(ServerJob { id, args, state: State::Queued, exit_code: None, log: String::new(), outputs: Vec::new(), child: None });
+                    jobs.pending.push_back(id);
+                    cvar.notify_one();
+                    ("201 Created", json::object([("id", id.into())]))
+                }
+            }
+        }
+        ("GET", ["jobs"]) => {
+            let list: Vec<Value> = jobs.all.iter().map(ServerJob::summary).collect();
+            ("200 OK", Value::Array(list))
+        }
+        ("GET", ["jobs", _]) if exists => ("200 OK", jobs.all[id.unwrap() as usize].details()),
+        ("GET", ["jobs", _, "results"]) if exists => {
+            let job = &jobs.all[id.unwrap() as usize];
+            ("200 OK", json::object([("state", job.state.name().into()), ("outputs", job.outputs.clone().into())]))
+        }
+        ("DELETE", ["jobs", _]) if exists => {
+            let job = &mut jobs.all[id.unwrap() as usize];
+            match job.state {
+                State::Queued | State::Running => {
+                    if let Some(child) = job.child.as_mut() {
+                        kill(child);
+                    }
+                    job.state = State::Cancelled;
+                    ("200 OK", job.summary())
+                }
+                _ => ("409 Conflict", error("job has already finished")),
+            }
+        }
+        (_, ["jobs", ..]) => ("404 Not Found", error("no such job")),
+        _ => ("404 Not Found", error("no such endpoint")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, headers: &[(&str, &str)], body: &str) -> Request {
+        let headers = headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect();
+        Request { method: method.to_string(), path: "/jobs".to_string(), headers, body: body.as_bytes().to_vec(), too_large: false }
+    }
+
+    #[test]
+    fn requests_need_the_token() {
+        let token = new_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, new_token());
+        let bearer = format!("Bearer {}", token);
+        let json = ("Content-Type", "application/json; charset=utf-8");
+        assert!(authorize(&request("POST", &[("Authorization", &bearer), json], "{}"), &token).is_ok());
+        assert!(authorize(&request("GET", &[("authorization", &bearer)], ""), &token).is_ok());
+        assert_eq!(authorize(&request("POST", &[json], "{}"), &token).unwrap_err().0, "401 Unauthorized");
+        assert_eq!(authorize(&request("POST", &[("Authorization", "Bearer guess"), json], "{}"), &token).unwrap_err().0, "401 Unauthorized");
+        let form = ("Content-Type", "text/plain");
+        assert_eq!(authorize(&request("POST", &[("Authorization", &bearer), form], "{}"), &token).unwrap_err().0, "415 Unsupported Media Type");
+    }
+
+    #[test]
+    fn refuses_options_that_run_commands() {
+        let args = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(refused_option(&args(&["-c", "1-2", "in.mp4"])), None);
+        assert_eq!(refused_option(&args(&["--hook", "rm -rf ~", "in.mp4"])), Some("--hook"));
+        assert_eq!(refused_option(&args(&["--spans-from-cmd=curl x", "in.mp4"])), Some("--spans-from-cmd=curl x"));
+        assert_eq!(refused_option(&args(&["--hooked", "in.mp4"])), None);
+        assert_eq!(refused_option(&args(&["in.mp4", "--", "--hook"])), None);
+    }
+}
@@ -0,0 +1,40 @@
+//! `--sidecar`: a JSON file next to each clip (`talk_clip0.mp4.json` for
+//! `talk_clip0.mp4`) saying where it came from and how it was made, so asset
+//! management keeps the clip's provenance wherever the files go.
+//!
+//! ```text
+//! {"input": "/home/me/talks/talk.mp4", "start": 1.5, "end": 3.0,
+//!  "label": null, "output": "talk_clip0.mp4", "sha256": "9f86d0...",
+//!  "ffmpeg_args": ["-ss", "1.500", ...], "xclips_version": "0.1.0"}
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::job::Job;
+use crate::json;
+use crate::sha256;
+
+/// Where the sidecar of the clip at `output` goes.
+pub fn path(output: &str) -> PathBuf {
+    PathBuf::from(format!("{}.json", output))
+}
+
+/// Writes the sidecar of a finished clip, cut from `input`.
+pub fn write(job: &Job, label: Option<&str>, input: &Path) -> Result<(), String> {
+    let checksum = sha256::file(Path::new(&job.output)).map_err(|err| format!("cannot read {}: {}", job.output, err))?;
+    let input = fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf());
+    let seconds = |ms: u64| ms as f64 / 1000.0;
+    let file_name = Path::new(&job.output).file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let sidecar = json::object([
+        ("input", input.to_string_lossy().into_owned().into()),
+        ("start", seconds(job.span.start.as_millis()).into()),
+        ("end", seconds(job.span.end.as_millis()).into()),
+        ("label", label.into()),
+        ("output", file_name.into()),
+        ("sha256", checksum.into()),
+        ("ffmpeg_args", job.args.clone().into()),
+        ("xclips_version", env!("CARGO_PKG_VERSION").into()),
+    ]);
+    fs::write(path(&job.output), format!("{}\n", sidecar)).map_err(|err| err.to_string())
+}
@@ -0,0 +1,86 @@
+//! SubRip (`.srt`) subtitles as a source of spans: each cue becomes a span,
+//! labeled with its text.
+//!
+//! ```text
+//! 12
+//! 00:01:02,500 --> 00:01:05,000
+//! Somewhere in here
+//! is the good part.
+//! ```
+//!
+//! Cue numbers are optional, and anything after the end time (positions,
+//! which some files have) is ignored, as are formatting tags in the text.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{Span, Timestamp};
+
+#[derive(Debug)]
+pub struct Cue {
+    pub span: Span,
+    /// The cue's lines, joined with spaces, without formatting tags.
+    pub text: String,
+}
+
+pub fn parse(text: &str) -> Result<Vec<Cue>, String> {
+    lazy_static! {
+        static ref TIMING: Regex = Regex::new(r"^(\d+:\d{2}:\d{2}[,.]\d{1,3})\s*-->\s*(\d+:\d{2}:\d{2}[,.]\d{1,3})").unwrap();
+        static ref TAG: Regex = Regex::new(r"</?[A-Za-z][^>]*>|\{\\[^}]*\}").unwrap();
+    }
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut cues = Vec::new();
+    let mut lines = text.lines().map(str::trim).enumerate().peekable();
+    while let Some((n, line)) = lines.next() {
+        let Some(captures) = TIMING.captures(line) else {
+            // cue numbers, and blank lines between cues
+            if line.is_empty() || line.chars().all(|c| c.is_ascii_digit()) {
+                continue;
+            }
+            return Err(format!("line {}: expected a cue's timing, like 00:01:02,500 --> 00:01:05,000", n + 1));
+        };
+        let timestamp = |s: &str| s.replace(',', ".").parse::<Timestamp>().map_err(|err| format!("line {}: {}", n + 1, err));
+        let (start, end) = (timestamp(&captures[1])?, timestamp(&captures[2])?);
+        let span = Span::new(start, end).map_err(|err| format!("line {}: {}", n + 1, err))?;
+        let mut words = Vec::new();
+        while let Some(&(_, line)) = lines.peek() {
+            if line.is_empty() {
+                break;
+            }
+            words.push(TAG.replace_all(line, "").trim().to_string());
+            lines.next();
+        }
+        cues.push(Cue { span, text: words.join(" ") });
+    }
+    Ok(cues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span(s: &str) -> Span {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn cues() {
+        let text = "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\n<i>there</i>\r\n\r\n2\r\n00:01:00.000 --> 00:01:01,250 X1:0 X2:9\r\n{\\an8}Up top\r\n";
+        let cues = parse(text).unwrap();
+        assert_eq!(cues.len(), 2);
+        assert_eq!((cues[0].span, cues[0].text.as_str()), (span("1-2.5"), "Hello there"));
+        assert_eq!((cues[1].span, cues[1].text.as_str()), (span("60-61.25"), "Up top"));
+    }
+
+    #[test]
+    fn numbers_are_optional() {
+        let cues = parse("00:00:01,000 --> 00:00:02,000\na\n\n00:00:03,000 --> 00:00:04,000\nb\n").unwrap();
+        assert_eq!(cues.iter().map(|c| c.text.as_str()).collect::<Vec<_>>(), ["a", "b"]);
+    }
+
+    #[test]
+    fn bad_cues() {
+        assert!(parse("1\n00:00:01 --> 00:00:02\nno milliseconds\n").unwrap_err().starts_with("line 2:"));
+        assert!(parse("00:00:05,000 --> 00:00:02,000\nbackwards\n").unwrap_err().contains("end is before start"));
+    }
+}
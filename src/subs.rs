@@ -0,0 +1,255 @@
+//! `--ass-subtitles`: a styled (ASS) subtitle file for each clip, with the
+//! events retimed to the clip and the fonts they use copied out of the
+//! input's attachments, so the clip renders as it does inside the input.
+//!
+//! The subtitles are read out of the input once. Each clip gets the events
+//! that show during its span, cut to the span and shifted to start at zero,
+//! in `CLIP.ass`, and the fonts named by those events' styles (or by `\fn`
+//! overrides in their text) in `CLIP.fonts/`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::{ffmpeg, join};
+use crate::Span;
+
+pub struct Subtitles {
+    /// Everything up to and including the `Format:` line of `[Events]`.
+    header: String,
+    /// Sections after `[Events]`.
+    trailer: String,
+    columns: Columns,
+    events: Vec<Event>,
+    /// Font names (lowercased) of each style.
+    style_fonts: HashMap<String, String>,
+    fonts: Vec<Font>,
+}
+
+struct Event {
+    /// The fields of a `Dialogue:` line, the text last.
+    fields: Vec<String>,
+    start: u64,
+    end: u64,
+}
+
+struct Font {
+    filename: String,
+    /// Family and full names (lowercased) from the font's name table.
+    names: Vec<String>,
+    data: Vec<u8>,
+}
+
+/// Positions of the fields xclips looks at in `Dialogue:` lines.
+struct Columns {
+    start: usize,
+    end: usize,
+    style: usize,
+    count: usize,
+}
+
+impl Subtitles {
+    /// Reads the first subtitle stream of `input`, which has to be ASS (or
+    /// SSA), and its font attachments.
+    pub fn extract(input: &Path) -> Result<Subtitles, String> {
+        let ass = join::temp_path("subtitles.ass");
+        let status = ffmpeg()
+            .args(["-v", "error", "-nostdin", "-y", "-i"])
+            .arg(input)
+            .args(["-map", "0:s:0", "-c:s", "copy"])
+            .arg(&ass)
+            .status()
+            .map_err(|_| "failed to spawn ffmpeg".to_string())?;
+        let text = fs::read(&ass).map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+        let _ = fs::remove_file(&ass);
+        if !status.success() {
+            return Err("the input has no ASS subtitles".to_string());
+        }
+        let text = text.map_err(|err| err.to_string())?;
+        let mut subtitles = parse(&text)?;
+        subtitles.fonts = attachments(input)?;
+        Ok(subtitles)
+    }
+
+    /// Writes the subtitles for a clip of `span` to `BASE.ass` (and any fonts
+    /// to `BASE.fonts/`), with `offset` milliseconds before the span begins
+    /// in the clip (from an intro).
+    pub fn write_clip(&self, span: &Span, offset: u64, base: &str) -> Result<(), String> {
+        let (from, to) = (span.start.as_millis(), span.end.as_millis());
+        let mut text = self.header.clone();
+        let mut used: Vec<String> = Vec::new();
+        for event in self.events.iter().filter(|e| e.start < to && e.end > from) {
+            let mut fields = event.fields.clone();
+            let columns = &self.columns;
+            fields[columns.start] = format_time(event.start.max(from) - from + offset);
+            fields[columns.end] = format_time(event.end.min(to) - from + offset);
+            text.push_str("Dialogue: ");
+            text.push_str(&fields.join(","));
+            text.push('\n');
+
+            if let Some(font) = self.style_fonts.get(&fields[columns.style].to_lowercase()) {
+                used.push(font.clone());
+            }
+            lazy_static! {
+                static ref FN_RE: Regex = Regex::new(r"\\fn@?([^\\}]+)").unwrap();
+            }
+            used.extend(FN_RE.captures_iter(fields.last().unwrap()).map(|c| c[1].trim().to_lowercase()));
+        }
+        text.push_str(&self.trailer);
+        fs::write(format!("{}.ass", base), text).map_err(|err| err.to_string())?;
+
+        let needed: Vec<&Font> = self.fonts.iter().filter(|font| font.names.iter().any(|n| used.contains(n))).collect();
+        if !needed.is_empty() {
+            let dir = PathBuf::from(format!("{}.fonts", base));
+            fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+            for font in needed {
+                fs::write(dir.join(&font.filename), &font.data).map_err(|err| err.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse(text: &str) -> Result<Subtitles, String> {
+    let mut header = String::new();
+    let mut trailer = String::new();
+    let mut events = Vec::new();
+    let mut style_fonts = HashMap::new();
+    let mut section = String::new();
+    let mut style_format: Vec<String> = Vec::new();
+    let mut columns: Option<Columns> = None;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            section = trimmed.to_lowercase();
+        }
+        if section == "[events]" {
+            if let Some(format) = trimmed.strip_prefix("Format:") {
+                let names: Vec<String> = format.split(',').map(|f| f.trim().to_lowercase()).collect();
+                columns = Some(columns_from(&names)?);
+            } else if let (Some(dialogue), Some(columns)) = (trimmed.strip_prefix("Dialogue:"), &columns) {
+                let fields: Vec<String> = dialogue.trim_start().splitn(columns.count, ',').map(String::from).collect();
+                if fields.len() == columns.count {
+                    if let (Some(start), Some(end)) = (parse_time(&fields[columns.start]), parse_time(&fields[columns.end])) {
+                        events.push(Event { fields, start, end });
+                    }
+                }
+                continue;
+            } else if !trimmed.starts_with('[') {
+                // comments, and blank lines between events
+                continue;
+            }
+        } else if let Some(format) = trimmed.strip_prefix("Format:") {
+            style_format = format.split(',').map(|f| f.trim().to_lowercase()).collect();
+        } else if let Some(style) = trimmed.strip_prefix("Style:") {
+            let fields: Vec<&str> = style.split(',').map(str::trim).collect();
+            let field = |name: &str| style_format.iter().position(|f| f == name).and_then(|i| fields.get(i));
+            if let (Some(name), Some(font)) = (field("name"), field("fontname")) {
+                style_fonts.insert(name.to_lowercase(), font.trim_start_matches('@').to_lowercase());
+            }
+        }
+        // everything else (script info, styles, embedded fonts) is kept as it
+        // is, on whichever side of the events it was
+        let out = if columns.is_some() && section != "[events]" { &mut trailer } else { &mut header };
+        out.push_str(line);
+        out.push('\n');
+    }
+    let columns = columns.ok_or("the subtitles have no [Events] format")?;
+    Ok(Subtitles { header, trailer, columns, events, style_fonts, fonts: Vec::new() })
+}
+
+fn columns_from(names: &[String]) -> Result<Columns, String> {
+    let find = |name: &str| names.iter().position(|n| n == name).ok_or_else(|| format!("the subtitle events have no {} field", name));
+    let columns = Columns { start: find("start")?, end: find("end")?, style: find("style")?, count: names.len() };
+    if find("text")? != names.len() - 1 {
+        return Err("the subtitle events' text isn't their last field".to_string());
+    }
+    Ok(columns)
+}
+
+/// Parses an ASS time like `0:01:02.50` into milliseconds.
+fn parse_time(s: &str) -> Option<u64> {
+    let mut parts = s.trim().split(':');
+    let h: u64 = parts.next()?.parse().ok()?;
+    let m: u64 = parts.next()?.parse().ok()?;
+    let (s, cs) = parts.next()?.split_once('.')?;
+    let s: u64 = s.parse().ok()?;
+    let cs: u64 = format!("{:0<2}", cs).get(..2)?.parse().ok()?;
+    Some(((h * 60 + m) * 60 + s) * 1000 + cs * 10)
+}
+
+fn format_time(ms: u64) -> String {
+    let cs = ms / 10;
+    format!("{}:{:02}:{:02}.{:02}", cs / 360000, cs / 6000 % 60, cs / 100 % 60, cs % 100)
+}
+
+/// Dumps the input's attachments and keeps the fonts among them.
+fn attachments(input: &Path) -> Result<Vec<Font>, String> {
+    let dir = join::temp_path("attachments");
+    fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+    // ffmpeg complains that there's no output after dumping, so its status
+    // doesn't say anything
+    let _ = ffmpeg()
+        .args(["-v", "quiet", "-nostdin", "-y", "-dump_attachment:t", "", "-i"])
+        .arg(fs::canonicalize(input).unwrap_or_else(|_| input.to_path_buf()))
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    let mut fonts = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|err| err.to_string())?.flatten() {
+        let data = fs::read(entry.path()).unwrap_or_default();
+        let names = font_names(&data);
+        if !names.is_empty() {
+            fonts.push(Font { filename: entry.file_name().to_string_lossy().into_owned(), names, data });
+        }
+    }
+    let _ = fs::remove_dir_all(&dir);
+    Ok(fonts)
+}
+
+/// The family and full names (name IDs 1 and 4) of a TrueType or OpenType
+/// font, or of each font in a collection, lowercased. Anything that isn't a
+/// font has none.
+fn font_names(data: &[u8]) -> Vec<String> {
+    let u16_at = |at: usize| data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize);
+    let u32_at = |at: usize| data.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]) as usize);
+    let offsets: Vec<usize> = if data.starts_with(b"ttcf") {
+        let count = u32_at(8).unwrap_or(0);
+        (0..count).filter_map(|i| u32_at(12 + 4 * i)).collect()
+    } else {
+        vec![0]
+    };
+    let mut names = Vec::new();
+    for font in offsets {
+        let tables = u16_at(font + 4).unwrap_or(0);
+        let name_table = (0..tables).map(|i| font + 12 + 16 * i).find(|&record| data.get(record..record + 4) == Some(b"name"));
+        let Some(table) = name_table.and_then(|record| u32_at(record + 8)) else {
+            continue;
+        };
+        let (count, strings) = (u16_at(table + 2).unwrap_or(0), table + u16_at(table + 4).unwrap_or(0));
+        for i in 0..count {
+            let record = table + 6 + 12 * i;
+            let (Some(platform), Some(id), Some(length), Some(offset)) = (u16_at(record), u16_at(record + 6), u16_at(record + 8), u16_at(record + 10)) else {
+                break;
+            };
+            let Some(bytes) = data.get(strings + offset..strings + offset + length).filter(|_| id == 1 || id == 4) else {
+                continue;
+            };
+            let name = match platform {
+                // Windows and Unicode names are UTF-16 big-endian
+                0 | 3 => String::from_utf16_lossy(&bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect::<Vec<_>>()),
+                _ => bytes.iter().map(|&b| b as char).collect(),
+            };
+            let name = name.to_lowercase();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
@@ -0,0 +1,312 @@
+//! Output filename templates.
+//!
+//! A template is a filename with `{placeholder}`s in it:
+//!
+//! ```text
+//! {base}         output path without its extension (from -o, or FILE)
+//! {ext}          output extension
+//! {index}        zero-padded position of the clip; empty when there's only one
+//!                (with --stable-names, a hash of the input name and span, and
+//!                with --number-scheme, letters, roman numerals, or the label)
+//! {input_stem}   input filename without directory or extension
+//! {duration}     length of the clip in seconds, e.g. 12.500
+//! {start}        where the clip starts, e.g. 00h05m10s
+//! {end}          where the clip ends, likewise
+//! {label}        the span's label (with / and the like replaced by _), or
+//!                empty
+//! {env:VAR}      value of environment variable VAR
+//! {date:FORMAT}  current local date/time, formatted with strftime(3)
+//! ```
+//!
+//! `{start}` and `{end}` take an optional format, as in `{start:MM.SS.fff}`,
+//! where `H`, `M`, and `S` stand for hours, minutes, and seconds (each
+//! padded to as many digits as there are letters), `f` for digits of the
+//! fraction of a second, and anything else for itself. The largest unit
+//! counts everything above it too: `{start:SSS}` of 1:05 is `065`. The
+//! default is `HHhMMmSSs`.
+//!
+//! `{{` and `}}` stand for literal braces.
+
+use std::env;
+#[cfg(unix)]
+use std::ffi::{CStr, CString};
+use std::str::FromStr;
+
+use crate::{ParseErr, Span, Timestamp};
+
+pub const DEFAULT: &str = "{base}_clip{index}.{ext}";
+/// What `DEFAULT` becomes for a labeled clip, which is named by its label.
+pub const LABELED: &str = "{base}_{label}.{ext}";
+
+/// Values available to a template for one clip.
+pub struct Vars<'a> {
+    pub base: &'a str,
+    pub ext: &'a str,
+    pub index: usize,
+    pub count: usize,
+    pub input_stem: &'a str,
+    pub span: Span,
+    pub label: Option<&'a str>,
+    /// Stands in for the index when set, so names don't depend on position.
+    pub stable_id: Option<u64>,
+    pub scheme: NumberScheme,
+}
+
+/// How `{index}` numbers clips.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NumberScheme {
+    /// `0`, `1`, ... (`00`, `01`, ... with more than ten clips).
+    #[default]
+    Digits,
+    /// `a`, `b`, ... (`aa`, `ab`, ... with more than 26 clips).
+    Letters,
+    /// `i`, `ii`, `iii`, ..., counting from one.
+    Roman,
+    /// The label, lowercased with dashes for spaces and punctuation; the
+    /// clip's number if it has no label.
+    Label,
+}
+
+impl FromStr for NumberScheme {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "digits" => Ok(NumberScheme::Digits),
+            "letters" => Ok(NumberScheme::Letters),
+            "roman" => Ok(NumberScheme::Roman),
+            "label" => Ok(NumberScheme::Label),
+            _ => Err(ParseErr("expected digits, letters, roman, or label")),
+        }
+    }
+}
+
+impl NumberScheme {
+    /// Writes the `index`th of `count` clips' number.
+    fn number(self, index: usize, count: usize, label: Option<&str>) -> String {
+        let digits = || format!("{:0width$}", index, width = crate::log10_ceil(count));
+        match self {
+            NumberScheme::Digits => digits(),
+            NumberScheme::Letters => {
+                // as many letters as the last clip needs, so names sort
+                let mut width = 1;
+                while 26usize.saturating_pow(width as u32) < count {
+                    width += 1;
+                }
+                let mut letters = vec![b'a'; width];
+                let mut n = index;
+                for letter in letters.iter_mut().rev() {
+                    *letter += (n % 26) as u8;
+                    n /= 26;
+                }
+                String::from_utf8(letters).unwrap()
+            }
+            NumberScheme::Roman => roman(index + 1),
+            NumberScheme::Label => match label.map(slug).filter(|s| !s.is_empty()) {
+                Some(slug) => slug,
+                None => digits(),
+            },
+        }
+    }
+}
+
+/// `n` in lowercase roman numerals.
+fn roman(mut n: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] =
+        [(1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"), (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i")];
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// A label as lowercase words joined by dashes.
+fn slug(label: &str) -> String {
+    let lower = label.to_lowercase();
+    let words: Vec<&str> = lower.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    words.join("-")
+}
+
+impl Vars<'_> {
+    fn lookup(&self, name: &str) -> Result<String, String> {
+        if let Some(var) = name.strip_prefix("env:") {
+            return env::var(var).map_err(|_| format!("environment variable {} is not set", var));
+        }
+        if let Some(format) = name.strip_prefix("date:") {
+            return strftime(format);
+        }
+        if let Some((time, format)) = name.split_once(':').filter(|(t, _)| ["start", "end"].contains(t)) {
+            return format_time(if time == "start" { self.span.start } else { self.span.end }, format);
+        }
+        if let (Some(id), "index") = (self.stable_id, name) {
+            return Ok(format!("{:08x}", id as u32));
+        }
+        Ok(match name {
+            "base" => self.base.to_string(),
+            "ext" => self.ext.to_string(),
+            "index" if self.count == 1 && self.scheme != NumberScheme::Label => String::new(),
+            "index" => self.scheme.number(self.index, self.count, self.label),
+            "input_stem" => self.input_stem.to_string(),
+            "duration" => {
+                let duration = self.span.duration();
+                format!("{}.{:03}", duration.as_secs(), duration.subsec_millis())
+            }
+            "start" => format_time(self.span.start, DEFAULT_TIME)?,
+            "end" => format_time(self.span.end, DEFAULT_TIME)?,
+            "label" => self.label.map(filename_safe).unwrap_or_default(),
+            _ => return Err(format!("unknown placeholder {{{}}}", name)),
+        })
+    }
+}
+
+const DEFAULT_TIME: &str = "HHhMMmSSs";
+
+/// Formats a timestamp for `{start:FORMAT}` and `{end:FORMAT}`.
+fn format_time(t: Timestamp, format: &str) -> Result<String, String> {
+    // runs of the same unit letter, and literal characters
+    let mut parts: Vec<(char, usize)> = Vec::new();
+    for c in format.chars() {
+        let continues = parts.last().is_some_and(|&(last, _)| last == c) && "HMSf".contains(c);
+        if continues {
+            parts.last_mut().unwrap().1 += 1;
+        } else if "HMSf".contains(c) && parts.iter().any(|&(p, _)| p == c) {
+            return Err(format!("time format {} has {} more than once", format, c));
+        } else {
+            parts.push((c, 1));
+        }
+    }
+    let (has_hours, has_minutes) = (parts.iter().any(|&(c, _)| c == 'H'), parts.iter().any(|&(c, _)| c == 'M'));
+    let ms = t.as_millis();
+    let (hours, minutes, seconds) = (ms / 3_600_000, ms / 60_000, ms / 1000);
+    let mut out = String::new();
+    for (c, n) in parts {
+        let value = match c {
+            'H' => hours,
+            'M' if has_hours => minutes % 60,
+            'M' => minutes,
+            'S' if has_hours || has_minutes => seconds % 60,
+            'S' => seconds,
+            'f' if n <= 3 => {
+                out.push_str(&format!("{:03}", ms % 1000)[..n]);
+                continue;
+            }
+            'f' => return Err(format!("time format {} has more than three digits of fractions", format)),
+            c => {
+                out.push(c);
+                continue;
+            }
+        };
+        out.push_str(&format!("{:0n$}", value, n = n));
+    }
+    Ok(out)
+}
+
+/// A label made fit for a filename: path separators, control characters,
+/// and characters Windows doesn't allow become underscores.
+fn filename_safe(label: &str) -> String {
+    label.trim().chars().map(|c| if c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c }).collect()
+}
+
+pub fn render(template: &str, vars: &Vars) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => name.push(c),
+                        None => return Err("unterminated placeholder".to_string()),
+                    }
+                }
+                out.push_str(&vars.lookup(&name)?);
+            }
+            '}' => return Err("unmatched }".to_string()),
+            c => out.push(c),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(unix)]
+pub fn strftime(format: &str) -> Result<String, String> {
+    extern "C" {
+        fn strftime(s: *mut libc::c_char, max: libc::size_t, format: *const libc::c_char, tm: *const libc::tm) -> libc::size_t;
+    }
+    let c_format = CString::new(format).map_err(|_| "invalid date format".to_string())?;
+    let mut buf = [0u8; 256];
+    // SAFETY: localtime_r fills in `tm` from `now`, and strftime writes at
+    // most `buf.len()` bytes (including the terminator) into `buf`.
+    let written = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return Err("cannot determine local time".to_string());
+        }
+        strftime(buf.as_mut_ptr() as *mut libc::c_char, buf.len(), c_format.as_ptr(), &tm)
+    };
+    if written == 0 && !format.is_empty() {
+        return Err(format!("date format {} produced no output", format));
+    }
+    let s = CStr::from_bytes_until_nul(&buf).map_err(|_| "invalid date format".to_string())?;
+    Ok(s.to_string_lossy().into_owned())
+}
+
+#[cfg(not(unix))]
+pub fn strftime(_format: &str) -> Result<String, String> {
+    Err("{date:...} is not supported on this platform".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_span(template: &str, span: &str, label: Option<&str>) -> Result<String, String> {
+        let vars = Vars { base: "lecture", ext: "mp4", index: 0, count: 1, input_stem: "lecture", span: span.parse().unwrap(), label, stable_id: None, scheme: NumberScheme::Digits };
+        render(template, &vars)
+    }
+
+    #[test]
+    fn times() {
+        assert_eq!(render_span("{base}_{start}-{end}.{ext}", "5:10-7:30", None).unwrap(), "lecture_00h05m10s-00h07m30s.mp4");
+        assert_eq!(render_span("{start:MM.SS.fff}", "1:02:03.25-1:03:00", None).unwrap(), "62.03.250");
+        assert_eq!(render_span("{start:SSS}", "1:05-2:00", None).unwrap(), "065");
+        assert_eq!(render_span("{end:S.f}", "1-2.75", None).unwrap(), "2.7");
+        assert!(render_span("{start:SS.SS}", "1-2", None).is_err());
+        assert!(render_span("{start:ffff}", "1-2", None).is_err());
+    }
+
+    #[test]
+    fn number_schemes() {
+        let number = |scheme: NumberScheme, index, count| scheme.number(index, count, None);
+        assert_eq!(number(NumberScheme::Digits, 7, 12), "07");
+        assert_eq!(number(NumberScheme::Letters, 1, 3), "b");
+        assert_eq!(number(NumberScheme::Letters, 27, 30), "bb");
+        assert_eq!(number(NumberScheme::Letters, 0, 30), "aa");
+        assert_eq!(number(NumberScheme::Roman, 3, 5), "iv");
+        assert_eq!(number(NumberScheme::Roman, 1993, 2000), "mcmxciv");
+        assert_eq!(NumberScheme::Label.number(2, 5, Some("Goal by Smith!")), "goal-by-smith");
+        assert_eq!(NumberScheme::Label.number(2, 5, Some("??")), "2");
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(render_span("{label}.{ext}", "1-2", Some(" Q&A: part 1/2 ")).unwrap(), "Q&A_ part 1_2.mp4");
+        assert_eq!(render_span("x{label}", "1-2", None).unwrap(), "x");
+        assert_eq!(render_span(LABELED, "1-2", Some("goal_by_smith")).unwrap(), "lecture_goal_by_smith.mp4");
+    }
+}
@@ -0,0 +1,239 @@
+//! `xclips timeline`: a picture of the input showing which parts the spans
+//! cover, where they overlap, and what they're labeled, to look over before
+//! cutting hours of footage.
+//!
+//! ```text
+//! talk.mp4: 3 span(s) cover 0:42 of 2:00 (34%), 0:02 of it twice or more
+//!  1             2 3
+//! |============--==##===========-------------------------------------------|
+//! 0:00                                                                  2:00
+//!    1  1.000-20.000   intro
+//!    2  24.000-30.000  (overlaps 3)
+//!    3  28.000-47.000  q&a (overlaps 2)
+//! ```
+//!
+//! `=` is covered by one span, `#` by more, and `-` by none. With `--svg`,
+//! it's drawn as an SVG image instead.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+use crate::error::Result;
+use crate::input::{input_duration, read_spans, Entry, SpanSource};
+
+/// How wide the SVG image is, in pixels.
+const SVG_WIDTH: u64 = 1000;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "timeline", bin_name = "xclips timeline", setting = AppSettings::AllowNegativeNumbers)]
+pub struct TimelineOpt {
+    #[structopt(flatten)]
+    spans: SpanSource,
+
+    /// Print an SVG image of the timeline instead of drawing it in text
+    #[structopt(long = "svg")]
+    svg: bool,
+
+    /// How many characters wide to draw the timeline
+    #[structopt(long = "columns", default_value = "72")]
+    columns: usize,
+
+    #[structopt(name = "FILE", parse(from_os_str))]
+    file: PathBuf,
+}
+
+/// A stretch of the input, in milliseconds, and how many spans cover it.
+#[derive(PartialEq, Debug)]
+struct Region {
+    start: u64,
+    end: u64,
+    spans: usize,
+}
+
+pub fn run(opt: TimelineOpt) -> Result<()> {
+    let mut entries = read_spans(&opt.spans, &opt.file)?;
+    entries.sort_by_key(|e| e.span);
+    let last = entries.iter().map(|e| e.span.end.as_millis()).max().unwrap_or(0);
+    let end = match input_duration(&opt.spans, &opt.file) {
+        Ok(length) => length.as_millis() as u64,
+        Err(_) => {
+            eprintln!("warning: cannot determine duration of {}, drawing up to where the last span ends", opt.file.display());
+            last
+        }
+    };
+    // spans that run past the end still get drawn
+    let end = end.max(last).max(1);
+    let regions = regions(&entries);
+    let name = opt.file.display().to_string();
+    if opt.svg {
+        print!("{}", svg(&name, &entries, &regions, end));
+    } else {
+        print!("{}", text(&name, &entries, &regions, end, opt.columns.max(10)));
+    }
+    Ok(())
+}
+
+/// The covered stretches of the input, split wherever the number of spans
+/// covering it changes. Spans that only touch don't overlap.
+fn regions(entries: &[Entry]) -> Vec<Region> {
+    let mut changes: Vec<(u64, i64)> = Vec::new();
+    for entry in entries {
+        changes.push((entry.span.start.as_millis(), 1));
+        changes.push((entry.span.end.as_millis(), -1));
+    }
+    // ends before starts at the same time
+    changes.sort();
+    let mut regions: Vec<Region> = Vec::new();
+    let mut covering = 0;
+    for (i, &(at, change)) in changes.iter().enumerate() {
+        covering += change;
+        let next = changes.get(i + 1).map_or(at, |c| c.0);
+        if covering > 0 && next > at {
+            match regions.last_mut() {
+                Some(last) if last.end == at && last.spans == covering as usize => last.end = next,
+                _ => regions.push(Region { start: at, end: next, spans: covering as usize }),
+            }
+        }
+    }
+    regions
+}
+
+/// The numbers (from 1) of the spans that overlap `entries[i]`.
+fn overlapping(entries: &[Entry], i: usize) -> Vec<usize> {
+    let span = entries[i].span;
+    (0..entries.len()).filter(|&j| j != i && entries[j].span.start < span.end && span.start < entries[j].span.end).map(|j| j + 1).collect()
+}
+
+fn summary(name: &str, entries: &[Entry], regions: &[Region], end: u64) -> String {
+    let covered: u64 = regions.iter().map(|r| r.end - r.start).sum();
+    let doubled: u64 = regions.iter().filter(|r| r.spans > 1).map(|r| r.end - r.start).sum();
+    let mut line = format!("{}: {} span(s) cover {} of {} ({}%)", name, entries.len(), clock(covered), clock(end), covered * 100 / end);
+    if doubled > 0 {
+        line.push_str(&format!(", {} of it twice or more", clock(doubled)));
+    }
+    line
+}
+
+fn text(name: &str, entries: &[Entry], regions: &[Region], end: u64, columns: usize) -> String {
+    let cell = |c: usize| (c as u64 * end / columns as u64, (c as u64 + 1) * end / columns as u64);
+    let bar: String = (0..columns)
+        .map(|c| {
+            let (from, to) = cell(c);
+            let most = regions.iter().filter(|r| r.start < to.max(from + 1) && r.end > from).map(|r| r.spans).max().unwrap_or(0);
+            match most {
+                0 => '-',
+                1 => '=',
+                _ => '#',
+            }
+        })
+        .collect();
+    // each span's number over where it starts, where there's room
+    let mut numbers = vec![' '; columns];
+    for (i, entry) in entries.iter().enumerate() {
+        let at = ((entry.span.start.as_millis() * columns as u64 / end) as usize).min(columns - 1);
+        let number: Vec<char> = (i + 1).to_string().chars().collect();
+        let room = at + number.len() <= columns && numbers[at.saturating_sub(1)..(at + number.len() + 1).min(columns)].iter().all(|&c| c == ' ');
+        if room {
+            numbers[at..at + number.len()].copy_from_slice(&number);
+        }
+    }
+    let mut out = String::new();
+    writeln!(out, "{}", summary(name, entries, regions, end)).unwrap();
+    writeln!(out, " {}", numbers.into_iter().collect::<String>().trim_end()).unwrap();
+    writeln!(out, "|{}|", bar).unwrap();
+    let (first, last) = (clock(0), clock(end));
+    writeln!(out, "{}{:>width$}", first, last, width = (columns + 2).saturating_sub(first.len())).unwrap();
+    let width = entries.iter().map(|e| e.span.to_string().len()).max().unwrap_or(0);
+    for (i, entry) in entries.iter().enumerate() {
+        let mut notes: Vec<String> = entry.label.iter().cloned().collect();
+        let overlaps = overlapping(entries, i);
+        if !overlaps.is_empty() {
+            notes.push(format!("(overlaps {})", overlaps.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")));
+        }
+        writeln!(out, "{:>4}  {:<width$}  {}", i + 1, entry.span.to_string(), notes.join(" "), width = width).unwrap();
+    }
+    out
+}
+
+fn svg(name: &str, entries: &[Entry], regions: &[Region], end: u64) -> String {
+    let x = |ms: u64| ms * SVG_WIDTH / end;
+    let height = 110 + 18 * entries.len() as u64;
+    let mut out = String::new();
+    writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif" font-size="12">"#, SVG_WIDTH + 20, height).unwrap();
+    writeln!(out, r#"<text x="10" y="16">{}</text>"#, escape(&summary(name, entries, regions, end))).unwrap();
+    writeln!(out, r##"<rect x="10" y="40" width="{}" height="30" fill="#e4e4e4"/>"##, SVG_WIDTH).unwrap();
+    for region in regions {
+        let fill = if region.spans > 1 { "#d9534f" } else { "#4a90d9" };
+        writeln!(out, r#"<rect x="{}" y="40" width="{}" height="30" fill="{}"/>"#, 10 + x(region.start), (x(region.end) - x(region.start)).max(1), fill).unwrap();
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        writeln!(out, r#"<text x="{}" y="34">{}</text>"#, 10 + x(entry.span.start.as_millis()), i + 1).unwrap();
+    }
+    for tick in 0..=10 {
+        let at = end * tick / 10;
+        let anchor = match tick {
+            0 => "start",
+            10 => "end",
+            _ => "middle",
+        };
+        writeln!(out, r#"<text x="{}" y="86" text-anchor="{}">{}</text>"#, 10 + x(at), anchor, clock(at)).unwrap();
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        let mut line = format!("{}  {}", i + 1, entry.span);
+        if let Some(ref label) = entry.label {
+            line.push_str(&format!("  {}", label));
+        }
+        let overlaps = overlapping(entries, i);
+        if !overlaps.is_empty() {
+            line.push_str(&format!("  (overlaps {})", overlaps.iter().map(usize::to_string).collect::<Vec<_>>().join(", ")));
+        }
+        writeln!(out, r#"<text x="10" y="{}">{}</text>"#, 110 + 18 * i as u64, escape(&line)).unwrap();
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+/// A time in milliseconds as `M:SS`, or `H:MM:SS` from an hour on.
+fn clock(ms: u64) -> String {
+    let s = ms / 1000;
+    if s >= 3600 {
+        format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+    } else {
+        format!("{}:{:02}", s / 60, s % 60)
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::parse_spans;
+
+    #[test]
+    fn covered_regions() {
+        let entries = parse_spans("1-20 intro\n24-30\n28-47 q&a\n47-50\n", "spans", false).unwrap();
+        let regions = regions(&entries);
+        let expected = [(1000, 20000, 1), (24000, 28000, 1), (28000, 30000, 2), (30000, 50000, 1)];
+        assert_eq!(regions, expected.map(|(start, end, spans)| Region { start, end, spans }));
+        assert_eq!(overlapping(&entries, 1), [3]);
+        assert!(overlapping(&entries, 3).is_empty());
+    }
+
+    #[test]
+    fn drawn_as_text() {
+        let entries = parse_spans("0-30 first\n15-60\n", "spans", false).unwrap();
+        let drawn = text("talk.mp4", &entries, &regions(&entries), 120000, 12);
+        let lines: Vec<&str> = drawn.lines().collect();
+        assert_eq!(lines[0], "talk.mp4: 2 span(s) cover 1:00 of 2:00 (50%), 0:15 of it twice or more");
+        assert_eq!(lines[1], " 1");
+        assert_eq!(lines[2], "|=##===------|");
+        assert_eq!(lines[3], "0:00      2:00");
+        assert_eq!(lines[4], "   1  0.000-30.000   first (overlaps 2)");
+    }
+}
@@ -0,0 +1,91 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::job::Failure;
+use crate::join::{self, Piece};
+use crate::{detect, ffmpeg, parse_duration, probe, sample, Span, Timestamp};
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "trailer", bin_name = "xclips trailer")]
+pub struct TrailerOpt {
+    /// Length of the finished reel (e.g. 1:00)
+    #[structopt(long = "target-duration", parse(try_from_str = parse_duration))]
+    target_duration: Duration,
+
+    /// Length of each segment in the reel
+    #[structopt(long = "segment-length", default_value = "3s", parse(try_from_str = parse_duration))]
+    segment_length: Duration,
+
+    /// Length of the fade in and out of each segment (0 for hard cuts)
+    #[structopt(long = "fade", default_value = "0.5s", parse(try_from_str = parse_duration))]
+    fade: Duration,
+
+    /// Scene-change score (0 to 1) above which a frame counts as a cut
+    #[structopt(long = "scene-threshold", default_value = "0.4")]
+    scene_threshold: f64,
+
+    #[structopt(short = "o", long = "output", parse(from_os_str))]
+    output: Option<PathBuf>,
+
+    #[structopt(name = "FILE", parse(from_os_str))]
+    file: PathBuf,
+}
+
+/// Assembles a rough highlight reel: segments starting at scene changes spread
+/// over the whole file (topped up with evenly spaced samples when there aren't
+/// enough scenes), joined with fades.
+pub fn run(opt: TrailerOpt) -> Result<()> {
+    let duration = probe::duration(&opt.file).ok_or_else(|| XclipsError::Probe { what: "duration", path: opt.file.clone() })?;
+    let segment = opt.segment_length.min(duration);
+    if segment.is_zero() {
+        return Err(XclipsError::Usage("segment length must be positive".to_string()));
+    }
+    let count = ((opt.target_duration.as_millis() / segment.as_millis()) as usize).max(1);
+    let total = duration.as_millis() as u64;
+    let length = segment.as_millis() as u64;
+
+    eprintln!("detecting scenes in {}", opt.file.display());
+    let scenes: Vec<u64> = detect::scenes(&opt.file, opt.scene_threshold)
+        .unwrap_or_else(|| {
+            eprintln!("warning: scene detection failed, using evenly spaced segments");
+            Vec::new()
+        })
+        .into_iter()
+        .filter(|&t| t + length <= total)
+        .collect();
+
+    let mut spans: Vec<Span> = if scenes.len() >= count {
+        // take every k-th scene so the reel covers the whole file
+        (0..count).map(|k| scenes[k * scenes.len() / count]).map(|t| Span { start: Timestamp::from_millis(t), end: Timestamp::from_millis(t + length) }).collect()
+    } else {
+        let mut spans: Vec<Span> = scenes.iter().map(|&t| Span { start: Timestamp::from_millis(t), end: Timestamp::from_millis(t + length) }).collect();
+        for span in sample::even(duration, count, segment) {
+            if spans.len() >= count {
+                break;
+            }
+            if !spans.iter().any(|s| s.start < span.end && span.start < s.end) {
+                spans.push(span);
+            }
+        }
+        spans
+    };
+    spans.sort();
+
+    let output = opt.output.clone().unwrap_or_else(|| {
+        let stem = opt.file.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = opt.file.extension().map_or("mp4".into(), |e| e.to_string_lossy());
+        opt.file.with_file_name(format!("{}_trailer.{}", stem, ext))
+    });
+    let input_file = opt.file.to_string_lossy();
+    let has_audio = probe::stream_types(&opt.file).is_some_and(|t| t.iter().any(|s| s == "audio"));
+    let pieces: Vec<Piece> = spans.iter().map(|&span| Piece { input: &input_file, span }).collect();
+    let args = join::filter_args(&pieces, has_audio, opt.fade, None, None, &output.to_string_lossy());
+    let status = ffmpeg().args(&args).status().map_err(|_| XclipsError::Spawn("ffmpeg".to_string()))?;
+    if !status.success() {
+        return Err(XclipsError::Encode { output: output.to_string_lossy().into_owned(), failure: Failure::Ffmpeg });
+    }
+    Ok(())
+}
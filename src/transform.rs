@@ -0,0 +1,93 @@
+//! Operations on whole lists of spans.
+
+use std::time::Duration;
+
+use crate::{Span, Timestamp};
+
+/// Coalesces overlapping (and touching) spans, returning them sorted.
+pub fn merge(spans: &[Span]) -> Vec<Span> {
+    let mut sorted = spans.to_vec();
+    sorted.sort();
+    let mut merged: Vec<Span> = Vec::new();
+    for span in sorted {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+    merged
+}
+
+/// Widens a span by `by` on each side, stopping at the start of the input.
+pub fn expand(span: Span, by: Duration) -> Span {
+    let by = by.as_millis() as u64;
+    Span { start: Timestamp::from_millis(span.start.as_millis().saturating_sub(by)), end: Timestamp::from_millis(span.end.as_millis() + by) }
+}
+
+/// Narrows a span by `by` on each side; a span shorter than twice that
+/// shrinks to nothing at its middle.
+pub fn contract(span: Span, by: Duration) -> Span {
+    let by = by.as_millis() as u64;
+    let (start, end) = (span.start.as_millis(), span.end.as_millis());
+    if end - start <= 2 * by {
+        let middle = Timestamp::from_millis(start + (end - start) / 2);
+        return Span { start: middle, end: middle };
+    }
+    Span { start: Timestamp::from_millis(start + by), end: Timestamp::from_millis(end - by) }
+}
+
+/// Cuts a span into consecutive pieces `every` long, the last one taking
+/// whatever is left over.
+pub fn split(span: Span, every: Duration) -> Vec<Span> {
+    let every = every.as_millis() as u64;
+    let mut pieces = Vec::new();
+    let mut start = span.start.as_millis();
+    while start + every < span.end.as_millis() {
+        pieces.push(Span { start: Timestamp::from_millis(start), end: Timestamp::from_millis(start + every) });
+        start += every;
+    }
+    pieces.push(Span { start: Timestamp::from_millis(start), end: span.end });
+    pieces
+}
+
+/// Returns the regions of `[0, end]` not covered by any span, in order.
+pub fn gaps(spans: &[Span], end: Timestamp) -> Vec<Span> {
+    let mut gaps = Vec::new();
+    let mut covered = Timestamp::from_millis(0);
+    for span in merge(spans) {
+        if span.start > covered {
+            gaps.push(Span { start: covered, end: span.start.min(end) });
+        }
+        covered = covered.max(span.end);
+    }
+    if end > covered {
+        gaps.push(Span { start: covered, end });
+    }
+    gaps.retain(|g| g.start < g.end);
+    gaps
+}
+
+/// Groups spans that overlap so much that copying their covering span out of
+/// the input once, and cutting each from that copy, reads noticeably less of
+/// the input than cutting each from the input. Returns each covering span
+/// with the indices of the spans it covers.
+pub fn shared_segments(spans: &[Span]) -> Vec<(Span, Vec<usize>)> {
+    let mut order: Vec<usize> = (0..spans.len()).collect();
+    order.sort_by_key(|&i| spans[i]);
+    let mut groups: Vec<(Span, Vec<usize>)> = Vec::new();
+    for i in order {
+        match groups.last_mut() {
+            Some((cover, members)) if spans[i].start < cover.end => {
+                cover.end = cover.end.max(spans[i].end);
+                members.push(i);
+            }
+            _ => groups.push((spans[i], vec![i])),
+        }
+    }
+    // worth it once the clips add up to 1.5 times the stretch they cover
+    groups.retain(|(cover, members)| {
+        let total: u128 = members.iter().map(|&i| spans[i].duration().as_millis()).sum();
+        members.len() > 1 && total * 2 >= cover.duration().as_millis() * 3
+    });
+    groups
+}
@@ -0,0 +1,160 @@
+//! Status lines for each clip on standard error: aligned (and colored, unless
+//! asked not to be) on a terminal, and plain `key=value` lines otherwise, so
+//! CI logs stay readable and scripts can pick them apart.
+
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::Mutex;
+
+use crate::{json, Span};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Status {
+    Ok,
+    Failed,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Ok => "ok",
+            Status::Failed => "failed",
+        }
+    }
+
+    /// ANSI color code.
+    fn color(self) -> &'static str {
+        match self {
+            Status::Ok => "32",
+            Status::Failed => "31",
+        }
+    }
+}
+
+pub struct Ui {
+    tty: bool,
+    color: bool,
+}
+
+impl Ui {
+    /// Color is off with `no_color`, when `NO_COLOR` is set, and when
+    /// standard error isn't a terminal.
+    pub fn new(no_color: bool) -> Ui {
+        let tty = io::stderr().is_terminal();
+        let color = tty && !no_color && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty());
+        Ui { tty, color }
+    }
+
+    /// Standard error as if it were a terminal.
+    #[cfg(test)]
+    pub fn terminal() -> Ui {
+        Ui { tty: true, color: false }
+    }
+
+    /// Whether ffmpeg's own log should be held back (and shown only when
+    /// ffmpeg fails), which is the case when it would clutter a log file.
+    pub fn quiet_ffmpeg(&self) -> bool {
+        !self.tty
+    }
+
+    /// Whether to draw progress bars, which only make sense on a terminal.
+    pub fn progress_bars(&self) -> bool {
+        self.tty
+    }
+
+    pub fn clip(&self, index: usize, count: usize, status: Status, output: &str, span: &Span, detail: Option<&str>) {
+        let mut line = if self.tty {
+            let name = format!("{:<6}", status.name());
+            let name = if self.color { format!("\x1b[{}m{}\x1b[0m", status.color(), name) } else { name };
+            let width = count.to_string().len();
+            format!("[{:>w$}/{}] {} {} ({})", index + 1, count, name, output, span, w = width)
+        } else {
+            format!("clip={}/{} status={} span={} output={}", index + 1, count, status.name(), span, value(output))
+        };
+        // over any bar for the whole run, which is drawn again as it moves
+        if self.tty {
+            line.insert_str(0, "\r\x1b[K");
+        }
+        if let Some(detail) = detail {
+            if self.tty {
+                line.push_str(": ");
+                line.push_str(detail);
+            } else {
+                line.push_str(" detail=");
+                line.push_str(&value(detail));
+            }
+        }
+        eprintln!("{}", line);
+    }
+}
+
+/// A progress bar for a clip being cut, like `clip 12/87 [#####     ] 50%`,
+/// `done` being how much of it is done, from 0 to 1.
+pub fn progress_bar(label: &str, done: f64) -> String {
+    const WIDTH: usize = 30;
+    let done = done.clamp(0.0, 1.0);
+    let filled = (done * WIDTH as f64).round() as usize;
+    format!("{} [{}{}] {:>3}%", label, "#".repeat(filled), " ".repeat(WIDTH - filled), (done * 100.0).floor())
+}
+
+/// One progress bar for a whole run whose clips are cut several at once,
+/// like `clips 12/87 [###       ] 14%`, counting how far along each clip is.
+#[derive(Debug)]
+pub struct Meter {
+    done: Mutex<Vec<f64>>,
+}
+
+impl Meter {
+    pub fn new(count: usize) -> Meter {
+        Meter { done: Mutex::new(vec![0.0; count]) }
+    }
+
+    /// Records that clips `first..first + clips` are `done` (from 0 to 1)
+    /// and draws the bar again.
+    pub fn update(&self, first: usize, clips: usize, done: f64) {
+        let mut all = self.done.lock().unwrap();
+        let end = (first + clips).min(all.len());
+        for clip in &mut all[first.min(end)..end] {
+            *clip = done.clamp(0.0, 1.0);
+        }
+        eprint!("\r{}\x1b[K", line(&all));
+    }
+}
+
+fn line(done: &[f64]) -> String {
+    let finished = done.iter().filter(|&&d| d >= 1.0).count();
+    let overall = done.iter().sum::<f64>() / done.len().max(1) as f64;
+    progress_bar(&format!("clips {}/{}", finished, done.len()), overall)
+}
+
+/// A value for a `key=value` line, quoted (as a JSON string) if it has
+/// anything that would make the line ambiguous.
+fn value(s: &str) -> String {
+    if s.is_empty() || s.chars().any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '=') {
+        json::Value::from(s).to_string()
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_bars() {
+        assert_eq!(progress_bar("clip 1/2", 0.5), format!("clip 1/2 [{}{}]  50%", "#".repeat(15), " ".repeat(15)));
+        assert_eq!(progress_bar("clip 2/2", 1.7), format!("clip 2/2 [{}] 100%", "#".repeat(30)));
+        assert!(progress_bar("clip 1/1", 0.0).ends_with("   0%"));
+    }
+
+    #[test]
+    fn meter() {
+        let meter = Meter::new(4);
+        meter.update(1, 1, 1.0);
+        meter.update(2, 2, 0.5);
+        assert_eq!(line(&meter.done.lock().unwrap()), progress_bar("clips 1/4", 0.5));
+        meter.update(3, 5, 1.0);
+        assert_eq!(line(&meter.done.lock().unwrap()), progress_bar("clips 2/4", 0.625));
+    }
+}
@@ -0,0 +1,110 @@
+//! `--upload`: copying each finished clip somewhere else with rclone or
+//! rsync, retrying when the copy fails.
+//!
+//! Destinations name the tool first, then whatever it takes as a
+//! destination: `rclone:remote:path` runs `rclone copy CLIP remote:path`, and
+//! `rsync:host:path` runs `rsync -a CLIP host:path`. A bundle directory is
+//! copied as a directory of the same name.
+
+use std::fmt;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use crate::ParseErr;
+
+/// How long to wait before retrying a failed upload, doubled each retry.
+const BACKOFF: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug)]
+pub enum Destination {
+    Rclone(String),
+    Rsync(String),
+}
+
+impl FromStr for Destination {
+    type Err = ParseErr;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tool, dest) = s.split_once(':').ok_or(ParseErr("expected rclone:REMOTE:PATH or rsync:HOST:PATH"))?;
+        if dest.is_empty() {
+            return Err(ParseErr("missing the destination after the tool"));
+        }
+        match tool {
+            "rclone" => Ok(Destination::Rclone(dest.to_string())),
+            "rsync" => Ok(Destination::Rsync(dest.to_string())),
+            _ => Err(ParseErr("expected rclone:REMOTE:PATH or rsync:HOST:PATH")),
+        }
+    }
+}
+
+impl fmt::Display for Destination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Destination::Rclone(dest) => write!(f, "rclone:{}", dest),
+            Destination::Rsync(dest) => write!(f, "rsync:{}", dest),
+        }
+    }
+}
+
+impl Destination {
+    fn command(&self, path: &Path) -> Command {
+        match self {
+            Destination::Rclone(dest) => {
+                let mut command = Command::new("rclone");
+                command.arg("copy").arg(path);
+                if path.is_dir() {
+                    // rclone copies a directory's contents, not the directory
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    command.arg(format!("{}/{}", dest.trim_end_matches('/'), name));
+                } else {
+                    command.arg(dest);
+                }
+                command
+            }
+            Destination::Rsync(dest) => {
+                let mut command = Command::new("rsync");
+                command.arg("-a").arg(path).arg(dest);
+                command
+            }
+        }
+    }
+
+    fn program(&self) -> &'static str {
+        match self {
+            Destination::Rclone(_) => "rclone",
+            Destination::Rsync(_) => "rsync",
+        }
+    }
+
+    /// Copies `path` (a file or a directory) to the destination, trying up to
+    /// `retries` more times if it fails. The tool's own output is shown only
+    /// if the last try fails.
+    pub fn upload(&self, path: &Path, retries: u32) -> Result<(), String> {
+        let mut backoff = BACKOFF;
+        let mut tries = 0;
+        loop {
+            let output = self
+                .command(path)
+                .stdin(Stdio::null())
+                .output()
+                .map_err(|_| format!("failed to spawn {}", self.program()))?;
+            if output.status.success() {
+                return Ok(());
+            }
+            if tries >= retries {
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+                return Err(match output.status.code() {
+                    Some(code) => format!("{} exited with status {}", self.program(), code),
+                    None => format!("{} was killed", self.program()),
+                });
+            }
+            eprintln!("warning: uploading {} failed, retrying in {}s", path.display(), backoff.as_secs());
+            thread::sleep(backoff);
+            backoff *= 2;
+            tries += 1;
+        }
+    }
+}
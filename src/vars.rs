@@ -0,0 +1,85 @@
+//! Variables in span lists, so one list can serve a recording that recurs
+//! with different timings: `{{intro_end}}-{{qa_start}}` takes its
+//! timestamps from a values file of `name = value` lines (with `#` comments),
+//! which is `--vars`, or else the input's name with a `.vars` extension.
+//!
+//! ```text
+//! # week 12
+//! intro_end = 4:10
+//! qa_start = 51:32
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, XclipsError};
+use crate::input::read_text;
+
+/// The values file to use for `input`: `given`, or else the input's own.
+pub fn path(given: Option<&Path>, input: &Path) -> PathBuf {
+    given.map_or_else(|| input.with_extension("vars"), Path::to_path_buf)
+}
+
+/// Reads a values file.
+pub fn read(path: &Path) -> Result<HashMap<String, String>> {
+    let (text, _) = read_text(path)?;
+    parse(&text).map_err(|err| XclipsError::Parse(format!("{}:{}", path.display(), err)))
+}
+
+fn parse(text: &str) -> std::result::Result<HashMap<String, String>, String> {
+    let mut values = HashMap::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, value) = line.split_once('=').ok_or_else(|| format!("{}: expected NAME = VALUE", n + 1))?;
+        values.insert(name.trim().to_string(), value.trim().to_string());
+    }
+    Ok(values)
+}
+
+/// Whether `text` has any variables in it.
+pub fn used(text: &str) -> bool {
+    text.contains("{{")
+}
+
+/// Replaces each `{{name}}` in `text` with its value.
+pub fn substitute(text: &str, values: &HashMap<String, String>) -> std::result::Result<String, String> {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find("{{") {
+        out.push_str(&rest[..at]);
+        let after = &rest[at + 2..];
+        let end = after.find("}}").ok_or("unterminated {{ variable")?;
+        let name = after[..end].trim();
+        out.push_str(values.get(name).ok_or_else(|| format!("no value for {{{{{}}}}}", name))?);
+        rest = &after[end + 2..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn substitutes() {
+        let values = parse("# week 12\nintro_end = 4:10\n\nqa_start=51:32\n").unwrap();
+        assert_eq!(substitute("{{intro_end}}-{{ qa_start }}\n0-5\n", &values).unwrap(), "4:10-51:32\n0-5\n");
+        assert_eq!(substitute("{{outro}}-", &values).unwrap_err(), "no value for {{outro}}");
+        assert!(substitute("{{intro_end-1", &values).is_err());
+    }
+
+    #[test]
+    fn bad_values() {
+        assert_eq!(parse("a = 1\nb\n").unwrap_err(), "2: expected NAME = VALUE");
+    }
+
+    #[test]
+    fn values_file_beside_input() {
+        assert_eq!(path(None, Path::new("rec/week12.mp4")), Path::new("rec/week12.vars"));
+        assert_eq!(path(Some(Path::new("v.txt")), Path::new("week12.mp4")), Path::new("v.txt"));
+    }
+}
@@ -0,0 +1,186 @@
+//! `xclips web`: a browser interface for marking spans while watching the
+//! video, which writes an ordinary timestamps file and can run the cut.
+//!
+//! Changing the spans or running the cut takes the same kind of token and
+//! JSON body as `xclips serve`, so other pages open in the browser can't; the
+//! page the server hands out has the token in it. Requests naming any host
+//! but the address it listens on are refused, so a site whose name has been
+//! pointed at that address can't be handed the page (or the token) either.
+//!
+//! Only built with the `web` feature.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
+
+use structopt::StructOpt;
+
+use crate::error::{Result, XclipsError};
+use crate::json;
+use crate::server::{authorize, new_token, read_request, respond, respond_with};
+
+const PAGE: &str = include_str!("web.html");
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "web", bin_name = "xclips web")]
+pub struct WebOpt {
+    /// Address to listen on
+    #[structopt(long = "listen", default_value = "127.0.0.1:8080")]
+    listen: String,
+
+    /// Timestamps file to load and save (default: next to FILE, ending in _spans.txt)
+    #[structopt(short = "f", long = "timestamps-file", parse(from_os_str))]
+    timestamps_file: Option<PathBuf>,
+
+    #[structopt(name = "FILE", parse(from_os_str))]
+    file: PathBuf,
+}
+
+struct Context {
+    file: PathBuf,
+    spans_file: PathBuf,
+    token: String,
+    /// The Host headers requests may have.
+    hosts: Vec<String>,
+}
+
+pub fn run(opt: WebOpt) -> Result<()> {
+    let spans_file = opt.timestamps_file.clone().unwrap_or_else(|| {
+        let stem = opt.file.file_stem().unwrap_or_default().to_string_lossy();
+        opt.file.with_file_name(format!("{}_spans.txt", stem))
+    });
+    let listener = TcpListener::bind(&opt.listen).map_err(|err| XclipsError::io(format!("cannot listen on {}", opt.listen), err))?;
+    eprintln!("open http://{} in a browser; spans are saved to {}", opt.listen, spans_file.display());
+    let addr = listener.local_addr().map_err(|err| XclipsError::io(format!("cannot listen on {}", opt.listen), err))?;
+    let context = Arc::new(Context { file: opt.file, spans_file, token: new_token(), hosts: hosts(&opt.listen, addr) });
+    for stream in listener.incoming().flatten() {
+        let context = context.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle(stream, &context) {
+                eprintln!("warning: error handling request: {}", err);
+            }
+        });
+    }
+    Err(XclipsError::Failed("stopped accepting connections".to_string()))
+}
+
+/// The Host headers a browser sends to the server at `addr`, named by
+/// `listen`: either of those, and `localhost` for a loopback address.
+fn hosts(listen: &str, addr: SocketAddr) -> Vec<String> {
+    let mut hosts = vec![listen.to_string(), addr.to_string()];
+    if addr.ip().is_loopback() {
+        hosts.push(format!("localhost:{}", addr.port()));
+    }
+    hosts
+}
+
+fn handle(stream: TcpStream, context: &Context) -> io::Result<()> {
+    let request = read_request(&mut BufReader::new(stream.try_clone()?))?;
+    let mut stream = stream;
+    if request.too_large {
+        return respond(&mut stream, "413 Payload Too Large", &json::object([("error", "request body too large".into())]));
+    }
+    if !request.header("host").is_some_and(|host| context.hosts.iter().any(|h| h.eq_ignore_ascii_case(host))) {
+        return respond(&mut stream, "403 Forbidden", &json::object([("error", "unexpected Host header".into())]));
+    }
+    if request.method != "GET" {
+        if let Err((status, msg)) = authorize(&request, &context.token) {
+            return respond(&mut stream, status, &json::object([("error", msg.into())]));
+        }
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => {
+            let page = PAGE.replace("{{TOKEN}}", &context.token);
+            respond_with(&mut stream, "200 OK", "text/html; charset=utf-8", &[], page.as_bytes())
+        }
+        ("GET", "/info") => respond(&mut stream, "200 OK", &json::object([("file", context.file.display().to_string().into())])),
+        ("GET", "/media") => serve_media(&mut stream, &context.file, request.header("range")),
+        ("GET", "/spans") => {
+            let text = fs::read_to_string(&context.spans_file).unwrap_or_default();
+            respond_with(&mut stream, "200 OK", "text/plain; charset=utf-8", &[], text.as_bytes())
+        }
+        ("POST", "/spans") => {
+            let body = json::parse(&String::from_utf8_lossy(&request.body)).ok();
+            match body.as_ref().and_then(|b| b.get("spans")?.as_str()) {
+                None => respond(&mut stream, "400 Bad Request", &json::object([("error", "expected {\"spans\": string}".into())])),
+                Some(text) => match fs::write(&context.spans_file, text) {
+                    Ok(()) => respond(&mut stream, "200 OK", &json::object([("ok", true.into())])),
+                    Err(err) => respond(&mut stream, "500 Internal Server Error", &json::object([("error", err.to_string().into())])),
+                },
+            }
+        }
+        ("POST", "/cut") => {
+            let exe = env::current_exe().unwrap_or_else(|_| "xclips".into());
+            // cutting again after changing the spans replaces the last cut
+            let result = Command::new(exe).arg("--overwrite").arg("-f").arg(&context.spans_file).arg(&context.file).output();
+            let (ok, log) = match result {
+                Ok(output) => (output.status.success(), String::from_utf8_lossy(&output.stderr).into_owned()),
+                Err(err) => (false, format!("failed to spawn xclips: {}", err)),
+            };
+            respond(&mut stream, "200 OK", &json::object([("ok", ok.into()), ("log", log.into())]))
+        }
+        _ => respond(&mut stream, "404 Not Found", &json::object([("error", "not found".into())])),
+    }
+}
+
+/// Serves the media file, honoring a single `Range: bytes=` request so the
+/// browser can seek without downloading everything.
+fn serve_media(stream: &mut TcpStream, path: &Path, range: Option<&str>) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let content_type = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("mp4") | Some("m4v") | Some("mov") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mkv") => "video/x-matroska",
+        Some("mp3") => "audio/mpeg",
+        Some("m4a") => "audio/mp4",
+        Some("ogg") => "audio/ogg",
+        Some("wav") => "audio/wav",
+        _ => "application/octet-stream",
+    };
+    let requested = range.and_then(|r| r.strip_prefix("bytes=")).and_then(|r| {
+        let (start, end) = r.split_once('-')?;
+        let start: u64 = start.parse().ok()?;
+        let end: u64 = if end.is_empty() { len.saturating_sub(1) } else { end.parse().ok()? };
+        if start <= end && end < len {
+            Some((start, end))
+        } else {
+            None
+        }
+    });
+    // cap each response so a single request doesn't read a huge file into memory
+    const CHUNK: u64 = 8 << 20;
+    let (status, start, end) = match requested {
+        Some((start, end)) => ("206 Partial Content", start, end.min(start + CHUNK - 1)),
+        None if len <= CHUNK => ("200 OK", 0, len.saturating_sub(1)),
+        None => ("206 Partial Content", 0, CHUNK - 1),
+    };
+    let mut body = Vec::new();
+    if len > 0 {
+        file.seek(SeekFrom::Start(start))?;
+        file.take(end - start + 1).read_to_end(&mut body)?;
+    }
+    let headers = [
+        "Accept-Ranges: bytes".to_string(),
+        format!("Content-Range: bytes {}-{}/{}", start, end, len),
+    ];
+    let headers = if status.starts_with("206") { &headers[..] } else { &headers[..1] };
+    respond_with(stream, status, content_type, headers, &body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allowed_hosts() {
+        assert_eq!(hosts("127.0.0.1:8080", "127.0.0.1:8080".parse().unwrap()), ["127.0.0.1:8080", "127.0.0.1:8080", "localhost:8080"]);
+        assert_eq!(hosts("localhost:0", "[::1]:4000".parse().unwrap()), ["localhost:0", "[::1]:4000", "localhost:4000"]);
+        assert_eq!(hosts("192.168.1.5:8080", "192.168.1.5:8080".parse().unwrap()), ["192.168.1.5:8080", "192.168.1.5:8080"]);
+    }
+}